@@ -3,10 +3,12 @@ use std::{fs::{self, create_dir_all}, path::PathBuf, sync::Arc};
 use log::*;
 use reqwest::Client;
 use slint::{ModelRc, VecModel};
+use md5::{Digest, Md5};
+use uuid::Builder;
 
-use crate::{launcher::authentication::auth_structs::*, slint_generatedMainWindow::{SlAccounts, SlMCAccount, SlMCSkin}, YetaLauncher};
+use crate::{launcher::authentication::{auth_structs::*, errors::{AResult, AuthError}, AuthServices}, slint_generatedMainWindow::{SlAccounts, SlMCAccount, SlMCSkin}, YetaLauncher};
 
-use super::{consts::ACCOUNT_FILE_NAME, slint_utils::SlintOption, utils::get_config_dir};
+use super::{consts::ACCOUNT_FILE_NAME, encryption::TokenCipher, redaction::add_secrets, slint_utils::SlintOption, utils::{get_config_dir, write_private_file}};
 
 
 
@@ -28,9 +30,18 @@ impl Accounts {
         let accounts_path = Self::get_path();
     
         if let Ok(file) = fs::read_to_string(&accounts_path) {
-            if let Ok(account_list) = serde_json::from_str::<Accounts>(&file) {
+            if let Ok(mut account_list) = serde_json::from_str::<Accounts>(&file) {
                 debug!("Successfully loaded {} account(s)", account_list.accounts.len());
 
+                if account_list.decrypt_tokens() {
+                    info!("Encrypting the plain text tokens of accounts.json...");
+                    account_list.save();
+                }
+                for account in &account_list.accounts {
+                    if let Account::Microsoft(account) = account {
+                        account.add_secrets();
+                    }
+                }
                 return account_list
             }
         }
@@ -38,20 +49,79 @@ impl Accounts {
         let fallback_list = Accounts {
             accounts: Vec::new(),
             selected_index: None,
+            encryption: None
         };
     
-        fs::write(accounts_path, serde_json::to_string_pretty(&fallback_list).unwrap()).expect("Failed to write to accounts file");
+        write_private_file(&accounts_path, &serde_json::to_string_pretty(&fallback_list).unwrap()).expect("Failed to write to accounts file");
         fallback_list
     }
 
     pub fn save(&self) {
         debug!("Saving accounts...");
-        fs::write(
-            Self::get_path(), 
-            serde_json::to_string_pretty(self).expect("Failed to serialize accounts to json")
+        let mut account_list = self.clone();
+        if !account_list.encrypt_tokens() {
+            error!("Not saving accounts, as the tokens would be written in plain text or with another key to the encrypted accounts.json");
+            return;
+        }
+
+        write_private_file(
+            &Self::get_path(), 
+            &serde_json::to_string_pretty(&account_list).expect("Failed to serialize accounts to json")
         ).expect("Failed to write to accounts.json");
     }
 
+    /// ### Encrypts the tokens for writing them to accounts.json
+    /// Tokens that are still encrypted, because they couldn't be decrypted, are kept as they are.
+    /// Fails if the file was encrypted before but there is no key now, as the tokens would end up in plain text.
+    /// Also fails if the key changed while tokens are still encrypted with the old one, as they could never be decrypted again.
+    fn encrypt_tokens(&mut self) -> bool {
+        let Some(cipher) = TokenCipher::get(self.encryption.as_ref()) else {
+            return self.encryption.is_none() || !self.has_tokens(|token| !TokenCipher::is_encrypted(token));
+        };
+
+        if self.encryption.as_ref().is_some_and(|encryption| *encryption != cipher.encryption) && self.has_tokens(TokenCipher::is_encrypted) {
+            return false;
+        }
+
+        for account in &mut self.accounts {
+            if let Account::Microsoft(account) = account {
+                for token in account.get_tokens_mut() {
+                    if !TokenCipher::is_encrypted(token) {
+                        *token = cipher.encrypt(token);
+                    }
+                }
+            }
+        }
+        self.encryption = Some(cipher.encryption.clone());
+        true
+    }
+
+    fn has_tokens(&mut self, predicate: impl Fn(&str) -> bool) -> bool {
+        self.accounts.iter_mut().any(|account| match account {
+            Account::Microsoft(account) => account.get_tokens_mut().iter().any(|token| predicate(token)),
+            Account::Offline(_) => false
+        })
+    }
+
+    /// ### Decrypts the tokens read from accounts.json
+    /// Tokens that can't be decrypted, like while the keyring is locked, stay encrypted and are tried again before the account is used.
+    /// Returns whether there were tokens in plain text, which should be encrypted by saving.
+    fn decrypt_tokens(&mut self) -> bool {
+        let cipher = TokenCipher::get(self.encryption.as_ref());
+        let mut has_plain_text = false;
+
+        for account in &mut self.accounts {
+            let Account::Microsoft(account) = account else { continue };
+
+            has_plain_text |= account.get_tokens_mut().iter().any(|token| !TokenCipher::is_encrypted(token));
+            if !account.decrypt_tokens(cipher) {
+                warn!("Failed to decrypt the tokens of account '{}'", account.mc_profile.name);
+            }
+        }
+
+        has_plain_text && cipher.is_some()
+    }
+
     fn get_path() -> PathBuf {
         let accounts_path = get_config_dir().join(ACCOUNT_FILE_NAME);
         if let Some(parent) = accounts_path.parent() {
@@ -63,33 +133,39 @@ impl Accounts {
         accounts_path
     }
 
-    pub async fn get_account_from_app(app: Arc<YetaLauncher>, client: &Client, force: bool) -> Option<MCAccount> {
-        let mut accounts = {
-            app.accounts.write().unwrap().clone()
+    /// ### Refreshes the selected account and writes it back to the app
+    /// The account is written back even if refreshing failed, so it is shown as needing a new login.
+    pub async fn get_account_from_app(app: Arc<YetaLauncher>, client: &Client, force: bool) -> AResult<Account> {
+        let services = app.settings.read().unwrap().auth_services.clone();
+        let (original, encryption) = {
+            let accounts = app.accounts.read().unwrap();
+            (accounts.get_selected().cloned().ok_or(AuthError::NoAccountSelected)?, accounts.encryption.clone())
         };
 
-        accounts.get_active_account(client, force).await.cloned()
+        let mut account = original.clone();
+        if let Account::Microsoft(mc_account) = &mut account {
+            if !mc_account.decrypt_tokens(TokenCipher::get(encryption.as_ref())) {
+                return Err(AuthError::TokensLocked);
+            }
+        }
+        let result = account.refresh(client, &services, force).await;
+
+        if account != original {
+            app.accounts.write().unwrap().update_account(original, account.clone());
+        }
+
+        result.map(|_| account)
     }
 
-    pub async fn get_active_account(&mut self, client: &Client, force: bool) -> Option<&MCAccount> {
-        if let Some(index) = self.selected_index {
-            let i: usize = index.try_into().unwrap_or(0);
-            {
-                let account = self.accounts.get_mut(i);
-                if let Some(acc) = account {
-                    acc.refresh(client, force).await;
-                }
-            }
-            self.save();
-            self.accounts.get(i)
-        } else { None }
+    pub fn get_selected(&self) -> Option<&Account> {
+        self.accounts.get(self.selected_index? as usize)
     }
 
-    pub fn save_new_account(&mut self, account: MCAccount) {
+    pub fn save_new_account(&mut self, account: Account) {
         let existing = self.accounts.iter_mut()
         .enumerate()
         .find(
-            |(_, acc)| acc.mc_profile.id == account.mc_profile.id
+            |(_, acc)| acc.get_uuid() == account.get_uuid()
         );
 
         if let Some((i, acc)) = existing {
@@ -103,7 +179,7 @@ impl Accounts {
         }
     }
 
-    pub fn update_account(&mut self, account: MCAccount, new_data: MCAccount) {
+    pub fn update_account(&mut self, account: Account, new_data: Account) {
         for acc in self.accounts.iter_mut() {
             if *acc == account {
                 *acc = new_data;
@@ -124,12 +200,123 @@ impl Accounts {
     }
 }
 
+impl Account {
+    pub fn get_name(&self) -> &str {
+        match self {
+            Account::Microsoft(account) => &account.mc_profile.name,
+            Account::Offline(account) => &account.name
+        }
+    }
+
+    pub fn get_uuid(&self) -> &str {
+        match self {
+            Account::Microsoft(account) => &account.mc_profile.id,
+            Account::Offline(account) => &account.id
+        }
+    }
+
+    /// The game only checks the token when joining online mode servers, so offline accounts get a dummy
+    pub fn get_access_token(&self) -> &str {
+        match self {
+            Account::Microsoft(account) => &account.mc_response.access_token,
+            Account::Offline(_) => "0"
+        }
+    }
+
+    pub fn get_user_type(&self) -> &'static str {
+        match self {
+            Account::Microsoft(_) => "msa",
+            Account::Offline(_) => "legacy"
+        }
+    }
+
+    pub fn needs_relogin(&self) -> bool {
+        match self {
+            Account::Microsoft(account) => account.needs_relogin,
+            Account::Offline(_) => false
+        }
+    }
+
+    pub async fn refresh(&mut self, client: &Client, services: &AuthServices, force: bool) -> AResult<()> {
+        match self {
+            Account::Microsoft(account) => account.refresh(client, services, force).await,
+            Account::Offline(_) => Ok(())
+        }
+    }
+
+    pub fn to_slint(&self, index: i32) -> SlMCAccount {
+        match self {
+            Account::Microsoft(account) => account.to_slint(index),
+            Account::Offline(account) => SlMCAccount {
+                username: account.name.to_string().into(),
+                uuid: account.id.to_string().into(),
+                index,
+                offline: true,
+                needs_relogin: false,
+                capes: ModelRc::default(),
+                skins: ModelRc::default()
+            }
+        }
+    }
+}
+
+impl OfflineAccount {
+    /// ### Creates an offline account for a player name
+    /// The UUID is derived from the name like the vanilla server does in offline mode, so the same name always gets the same UUID.
+    pub fn new(name: &str) -> Result<Self, String> {
+        if name.is_empty() || name.len() > 16 || !name.chars().all(|char| char.is_ascii_alphanumeric() || char == '_') {
+            return Err(format!("'{name}' is not a valid player name. Names have 1 to 16 characters, which can be letters, numbers and underscores."));
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            id: Builder::from_md5_bytes(Md5::digest(format!("OfflinePlayer:{name}")).into()).into_uuid().simple().to_string()
+        })
+    }
+}
+
 impl MCAccount {
+    /// Keeps the tokens and user hashes of the account out of logs and notifications
+    pub fn add_secrets(&self) {
+        add_secrets([self.msa_response.access_token.as_str(), &self.msa_response.refresh_token, &self.mc_response.access_token]);
+        self.xbl_response.add_secrets();
+        self.xsts_response.add_secrets();
+    }
+
+    /// Decrypts the tokens that are still encrypted, returns whether all of them could be
+    fn decrypt_tokens(&mut self, cipher: Option<&TokenCipher>) -> bool {
+        let mut decrypted_all = true;
+
+        for token in self.get_tokens_mut() {
+            if TokenCipher::is_encrypted(token) {
+                match cipher.and_then(|cipher| cipher.decrypt(token)) {
+                    Some(decrypted) => *token = decrypted,
+                    None => decrypted_all = false
+                }
+            }
+        }
+
+        decrypted_all
+    }
+
+    /// The fields that are encrypted in accounts.json
+    fn get_tokens_mut(&mut self) -> [&mut String; 5] {
+        [
+            &mut self.msa_response.access_token,
+            &mut self.msa_response.refresh_token,
+            &mut self.xbl_response.token,
+            &mut self.xsts_response.token,
+            &mut self.mc_response.access_token
+        ]
+    }
+
     pub fn to_slint(&self, index: i32) -> SlMCAccount {
         SlMCAccount {
             username: self.mc_profile.name.to_string().into(),
             uuid: self.mc_profile.id.to_string().into(),
             index,
+            offline: false,
+            needs_relogin: self.needs_relogin,
             capes: ModelRc::new(VecModel::from(
                 self.mc_profile.capes.iter().map(
                     |cape| (cape.id.to_string().into(), )
@@ -153,4 +340,23 @@ impl MCSkin {
             alias: SlintOption::from(self.alias.clone()).into()
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offline_uuid_matches_vanilla() {
+        let account = OfflineAccount::new("Notch").unwrap();
+        assert_eq!(uuid::Uuid::parse_str(&account.id).unwrap().hyphenated().to_string(), "b50ad385-829d-3141-a216-7e7d7539ba7f");
+    }
+
+    #[test]
+    fn offline_names_are_validated() {
+        assert!(OfflineAccount::new("").is_err());
+        assert!(OfflineAccount::new("way_too_long_player_name").is_err());
+        assert!(OfflineAccount::new("no spaces").is_err());
+        assert!(OfflineAccount::new("Steve_123").is_ok());
+    }
+}
@@ -1,9 +1,22 @@
 pub const SETTINGS_FILE_NAME: &str = "settings.json";
 pub const ACCOUNT_FILE_NAME: &str = "accounts.json";
 pub const META_FILE_NAME: &str = "yamcl-data.json";
+pub const NATIVE_FILE_NAME: &str = "yeta-instance.json";
+pub const CRASH_RULES_FILE_NAME: &str = "crash-rules.json";
+pub const MOD_BACKUP_FOLDER: &str = "mod-backups";
+pub const WORLD_BACKUP_FOLDER: &str = "world-backups";
+
+pub const KEYRING_SERVICE: &str = "yetalauncher";
+pub const PASSPHRASE_ENV_VAR: &str = "YETALAUNCHER_PASSPHRASE";
 
 pub const MINECRAFT_VERSION_URL: &str = "https://piston-meta.mojang.com/mc/game/version_manifest_v2.json";
 pub const MINECRAFT_LIBRARY_URL: &str = "https://libraries.minecraft.net";
+pub const MODRINTH_API_URL: &str = "https://api.modrinth.com/v2";
+pub const CURSEFORGE_API_URL: &str = "https://api.curseforge.com/v1";
+pub const CURSEFORGE_CDN_URL: &str = "https://edge.forgecdn.net";
+pub const MICROSOFT_LOGIN_URL: &str = "https://login.live.com";
+pub const XBOX_LIVE_AUTH_URL: &str = "https://user.auth.xboxlive.com";
+pub const XSTS_AUTH_URL: &str = "https://xsts.auth.xboxlive.com";
+pub const MINECRAFT_SERVICES_URL: &str = "https://api.minecraftservices.com";
 
-pub const MS_CLIENT_ID: &str = "5431ff2d-20f8-415b-aa2f-5218eba055ea"; // The YetaLauncher / Yet Another MC Launcher client_id. If you fork this project, please make sure to use your own!
-pub const REDIRECT_PORT: u16 = 32303;
\ No newline at end of file
+pub const MS_CLIENT_ID: &str = "5431ff2d-20f8-415b-aa2f-5218eba055ea"; // The YetaLauncher / Yet Another MC Launcher client_id. If you fork this project, please make sure to use your own!
\ No newline at end of file
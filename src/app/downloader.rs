@@ -3,6 +3,7 @@ use std::{io::{Cursor, Write}, path::PathBuf};
 use log::*;
 use reqwest::Client;
 use sha1_smol::Sha1;
+use sha2::{Digest, Sha512};
 use tokio::{fs, io, sync::mpsc::{unbounded_channel, UnboundedSender}, task::JoinSet};
 
 use super::{notifier::Notifier, utils::split_vec_into};
@@ -31,6 +32,7 @@ pub struct Downloader {
 #[derive(Debug, Clone)]
 pub struct Download {
     sha1: Option<String>,
+    sha512: Option<String>,
     size: Option<u32>,
     path: PathBuf,
     url: String
@@ -53,11 +55,11 @@ impl Downloader {
 
     pub fn add_from(&mut self, path: PathBuf, url: String, sha1: Option<String>, size: Option<u32>) {
         self.downloads.push(Download {
-            sha1, size, path, url
+            sha1, sha512: None, size, path, url
         });
     }
 
-    pub async fn download_all(&mut self, shuffle: bool, text: &str) {
+    pub async fn download_all(&mut self, shuffle: bool, text: &str) -> DResult {
         let mut tasks = JoinSet::new();
         let mut notifier = self.notifier.make_new();
 
@@ -79,6 +81,7 @@ impl Downloader {
                 Download::download_batch(batch, &client, &mut notifier, sender).await
             });
         }
+        drop(sender); // So the receiver closes once every batch has finished, even if one of them failed
 
         while let Some(()) = receiver.recv().await {
             count += 1;
@@ -89,19 +92,34 @@ impl Downloader {
             if count == total { break }
         }
 
-        while let Some(Ok(result)) = tasks.join_next().await {
-            result.unwrap();
+        let mut result = Ok(());
+
+        while let Some(Ok(batch_result)) = tasks.join_next().await {
+            if let Err(err) = batch_result {
+                error!("Failed to download {text}: {err:?}");
+                result = result.and(Err(err));
+            }
         }
 
         notifier.set_progress(0, 0);
-        notifier.send_success(&format!("Finished downloading {total} {text}"));
+        if result.is_ok() {
+            notifier.send_success(&format!("Finished downloading {total} {text}"));
+        } else {
+            notifier.send_error(&format!("Failed to download some of the {text}"));
+        }
+
+        result
     }
 }
 
 
 impl Download {
     pub fn new(path: PathBuf, url: &str, sha1: Option<String>, size: Option<u32>) -> Self {
-        Self { path, url: url.to_string(), sha1, size }
+        Self { path, url: url.to_string(), sha1, sha512: None, size }
+    }
+
+    pub fn with_sha512(self, sha512: Option<String>) -> Self {
+        Self { sha512, ..self }
     }
 
     pub async fn download_batch(batch: Vec<Self>, client: &Client, notifier: &mut Notifier, sender: UnboundedSender<()>) -> DResult {
@@ -194,11 +212,18 @@ impl Download {
     }
 
     async fn checksum_matches(&self) -> bool {
-        if let Some(sha1) = &self.sha1 {
-            if let Ok(contents) = fs::read(&self.path).await {
-                let checksum = Sha1::from(contents).digest().to_string();
-                &checksum == sha1
-            } else { false }
-        } else { true }
+        if self.sha1.is_none() && self.sha512.is_none() {
+            return true;
+        }
+
+        if let Ok(contents) = fs::read(&self.path).await {
+            self.sha1.as_ref().is_none_or(
+                |sha1| &Sha1::from(&contents).digest().to_string() == sha1
+            )
+            &&
+            self.sha512.as_ref().is_none_or(
+                |sha512| &format!("{:x}", Sha512::digest(&contents)) == sha512
+            )
+        } else { false }
     }
 }
\ No newline at end of file
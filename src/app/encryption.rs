@@ -0,0 +1,134 @@
+use std::{env, sync::OnceLock};
+
+use aes_gcm::{aead::{Aead, AeadCore, KeyInit, OsRng}, Aes256Gcm, Key, Nonce};
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::*;
+use serde::{Deserialize, Serialize};
+
+use super::consts::{KEYRING_SERVICE, PASSPHRASE_ENV_VAR};
+
+
+/// Marks tokens that are encrypted, so plain text tokens from older versions can be told apart
+const ENCRYPTED_PREFIX: &str = "enc:";
+const NONCE_LENGTH: usize = 12;
+
+/// Only set once a key was found, so a locked or unreachable keyring is tried again on the next use
+static CIPHER: OnceLock<TokenCipher> = OnceLock::new();
+
+/// Where the key that encrypts the tokens in accounts.json comes from
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(tag = "source", rename_all = "camelCase")]
+pub enum TokenEncryption {
+    /// A random key that is kept in the system's keyring
+    Keyring,
+    /// A key derived from the passphrase in [`PASSPHRASE_ENV_VAR`], for systems without a keyring
+    Passphrase { salt: String }
+}
+
+pub struct TokenCipher {
+    cipher: Aes256Gcm,
+    pub encryption: TokenEncryption
+}
+
+
+impl TokenCipher {
+    /// ### Gets the cipher for the tokens, set up on first use
+    /// `existing` is the encryption of the accounts file, so its key is used again. Only a file without encryption may pick a new key.
+    /// Is `None` if the key of `existing` (or for a new file, neither a keyring nor a passphrase) is available right now.
+    pub fn get(existing: Option<&TokenEncryption>) -> Option<&'static Self> {
+        if let Some(cipher) = CIPHER.get() {
+            return Some(cipher);
+        }
+
+        let cipher = match existing {
+            Some(TokenEncryption::Passphrase { salt }) => Self::from_passphrase(salt.to_string()),
+            Some(TokenEncryption::Keyring) => Self::from_keyring(),
+            None => Self::from_keyring().or_else(|| Self::from_passphrase(Self::create_salt()))
+        };
+
+        match cipher {
+            Some(cipher) => Some(CIPHER.get_or_init(|| cipher)),
+            None => {
+                match existing {
+                    Some(TokenEncryption::Keyring) => warn!("The keyring is not available, account tokens can't be decrypted or encrypted!"),
+                    Some(TokenEncryption::Passphrase { .. }) => warn!("{PASSPHRASE_ENV_VAR} is not set, account tokens can't be decrypted or encrypted!"),
+                    None => warn!("No keyring is available and {PASSPHRASE_ENV_VAR} is not set, account tokens can't be encrypted!")
+                }
+                None
+            }
+        }
+    }
+
+    fn from_keyring() -> Option<Self> {
+        let entry = keyring::Entry::new(KEYRING_SERVICE, "account-tokens")
+            .inspect_err(|err| warn!("Failed to access the keyring: {err}"))
+            .ok()?;
+
+        let key = match entry.get_password() {
+            Ok(key) => STANDARD.decode(key).ok().filter(|key| key.len() == 32),
+            Err(keyring::Error::NoEntry) => {
+                info!("Creating a new key for the account tokens in the keyring");
+                let key = Aes256Gcm::generate_key(OsRng);
+
+                entry.set_password(&STANDARD.encode(key))
+                    .inspect_err(|err| warn!("Failed to store the token key in the keyring: {err}"))
+                    .ok()
+                    .map(|_| key.to_vec())
+            },
+            Err(err) => {
+                warn!("Failed to read the token key from the keyring: {err}");
+                None
+            }
+        }?;
+
+        debug!("Using the token key from the keyring");
+        Some(Self {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)),
+            encryption: TokenEncryption::Keyring
+        })
+    }
+
+    fn from_passphrase(salt: String) -> Option<Self> {
+        let passphrase = env::var(PASSPHRASE_ENV_VAR).ok().filter(|passphrase| !passphrase.is_empty())?;
+
+        let mut key = [0; 32];
+        Argon2::default().hash_password_into(passphrase.as_bytes(), salt.as_bytes(), &mut key)
+            .inspect_err(|err| error!("Failed to derive the token key from the passphrase: {err}"))
+            .ok()?;
+
+        debug!("Using the token key derived from {PASSPHRASE_ENV_VAR}");
+        Some(Self {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)),
+            encryption: TokenEncryption::Passphrase { salt }
+        })
+    }
+
+    fn create_salt() -> String {
+        STANDARD.encode(Aes256Gcm::generate_nonce(&mut OsRng))
+    }
+
+    pub fn is_encrypted(token: &str) -> bool {
+        token.starts_with(ENCRYPTED_PREFIX)
+    }
+
+    pub fn encrypt(&self, token: &str) -> String {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self.cipher.encrypt(&nonce, token.as_bytes()).expect("Failed to encrypt token");
+
+        format!("{ENCRYPTED_PREFIX}{}", STANDARD.encode([nonce.as_slice(), &ciphertext].concat()))
+    }
+
+    /// Fails if the token was encrypted with another key, like after the keyring was reset
+    pub fn decrypt(&self, token: &str) -> Option<String> {
+        let data = STANDARD.decode(token.strip_prefix(ENCRYPTED_PREFIX)?).ok()?;
+        if data.len() < NONCE_LENGTH {
+            return None;
+        }
+
+        let (nonce, ciphertext) = data.split_at(NONCE_LENGTH);
+        let token = self.cipher.decrypt(Nonce::from_slice(nonce), ciphertext).ok()?;
+
+        String::from_utf8(token).ok()
+    }
+}
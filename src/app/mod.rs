@@ -1,7 +1,11 @@
 pub mod notifier;
 pub mod downloader;
 pub mod accounts;
+pub mod encryption;
 pub mod settings;
+pub mod redaction;
 pub mod utils;
 pub mod consts;
-pub mod slint_utils;
\ No newline at end of file
+pub mod slint_utils;
+#[cfg(test)]
+pub mod test_server;
\ No newline at end of file
@@ -4,7 +4,7 @@ use log::*;
 use tokio::{sync::mpsc::*, time::sleep};
 use tokio_util::sync::CancellationToken;
 
-use crate::slint_generatedMainWindow::{SlNotif, SlNotifState};
+use crate::{app::redaction::redact, slint_generatedMainWindow::{SlNotif, SlNotifState}};
 
 
 #[derive(Debug)]
@@ -27,7 +27,10 @@ pub struct Notif {
     pub progress: u32,
     pub max_progress: u32,
     pub status: NotificationState,
-    pub in_view: bool
+    pub in_view: bool,
+    /// A file or URL that can be opened from the notification
+    pub link: Option<String>,
+    pub link_text: String
 }
 
 #[derive(Debug, Clone)]
@@ -83,7 +86,11 @@ impl Notifier {
 
     pub fn send_notif(&self, notif: Notif) {
         self.notify(InternalNotif {
-            inner: notif,
+            inner: Notif {
+                text: redact(&notif.text),
+                link: notif.link.as_deref().map(redact),
+                ..notif
+            },
             id: self.id,
             typ: InternalNotifType::Schedule
         })
@@ -115,6 +122,27 @@ impl Notifier {
     pub fn send_error(&self, message: &str) {
         self.send(message, NotificationState::Error)
     }
+
+    pub fn send_error_with_link(&self, message: &str, link: &str) {
+        self.send_notif(Notif {
+            text: message.to_string(),
+            status: NotificationState::Error,
+            link: Some(link.to_string()),
+            link_text: "Open full report".to_string(),
+            ..Default::default()
+        })
+    }
+
+    /// Stays visible until it is replaced, as the link is part of an ongoing task
+    pub fn send_msg_with_link(&self, message: &str, link: &str, link_text: &str) {
+        self.send_notif(Notif {
+            text: message.to_string(),
+            status: NotificationState::Running,
+            link: Some(link.to_string()),
+            link_text: link_text.to_string(),
+            ..Default::default()
+        })
+    }
 }
 
 impl InternalNotifier {
@@ -170,10 +198,12 @@ impl InternalNotifier {
                             on_update(self.notifications.iter().map(|n| &n.inner).collect());
 
                             let timeout = match &notif.inner.status {
+                                NotificationState::Running => None,
+                                // Leave some time to click the link
+                                _ if notif.inner.link.is_some() => Some(30),
                                 NotificationState::Success => Some(3),
                                 NotificationState::Warning => Some(7),
-                                NotificationState::Error => Some(10),
-                                _ => None
+                                NotificationState::Error => Some(10)
                             };
         
                             if let Some(secs) = timeout {
@@ -236,7 +266,10 @@ impl Notif {
             progress: self.progress as i32,
             max_progress: self.max_progress as i32,
             in_view: self.in_view,
-            status: self.status.to_slint()
+            status: self.status.to_slint(),
+            // Sent between threads, so it can't be a model like other optional values
+            link: self.link.clone().unwrap_or_default().into(),
+            link_text: self.link_text.to_string().into()
         }
     }
 }
@@ -259,7 +292,9 @@ impl Default for Notif {
             progress: 0,
             max_progress: 0,
             in_view: false,
-            status: NotificationState::Running
+            status: NotificationState::Running,
+            link: None,
+            link_text: String::new()
         }
     }
 }
\ No newline at end of file
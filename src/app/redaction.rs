@@ -0,0 +1,152 @@
+use std::sync::RwLock;
+
+use log::{Log, Metadata, Record, SetLoggerError};
+use simple_logger::SimpleLogger;
+
+
+const MASK: &str = "<redacted>";
+/// Shorter values, like the dummy token of offline accounts, would mask unrelated parts of messages
+const MIN_SECRET_LENGTH: usize = 8;
+
+static SECRETS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// ### Masks the known secrets in all log records before they are written
+/// Wraps [`SimpleLogger`], which does the actual formatting and filtering.
+pub struct RedactingLogger<L: Log = SimpleLogger> {
+    inner: L
+}
+
+
+impl RedactingLogger {
+    pub fn init(inner: SimpleLogger) -> Result<(), SetLoggerError> {
+        #[cfg(windows)]
+        simple_logger::set_up_windows_color_terminal();
+
+        log::set_max_level(inner.max_level());
+        log::set_boxed_logger(Box::new(Self { inner }))
+    }
+}
+
+impl<L: Log> Log for RedactingLogger<L> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.inner.enabled(record.metadata()) {
+            return;
+        }
+
+        let message = redact(&record.args().to_string());
+        self.inner.log(
+            &Record::builder()
+            .metadata(record.metadata().clone())
+            .module_path(record.module_path())
+            .file(record.file())
+            .line(record.line())
+            .args(format_args!("{message}"))
+            .build()
+        );
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+}
+
+
+/// ### Registers values that must never show up in logs or notifications
+/// Called with every token as soon as it is received or read from disk.
+pub fn add_secrets<'a>(secrets: impl IntoIterator<Item = &'a str>) {
+    let mut known = SECRETS.write().unwrap();
+
+    for secret in secrets {
+        if secret.len() >= MIN_SECRET_LENGTH && !known.iter().any(|known| known == secret) {
+            known.push(secret.to_string());
+        }
+    }
+}
+
+/// Replaces every known secret in `text`
+pub fn redact(text: &str) -> String {
+    let secrets = SECRETS.read().unwrap();
+    let mut text = text.to_string();
+
+    for secret in secrets.iter() {
+        if text.contains(secret.as_str()) {
+            text = text.replace(secret.as_str(), MASK);
+        }
+    }
+
+    text
+}
+
+
+/// ### Keeps every log record of the tests after it went through [`RedactingLogger`]
+/// Tests run in parallel and share the logger, so they should look for their own records in [`captured_logs`].
+#[cfg(test)]
+struct CapturingLogger;
+
+#[cfg(test)]
+static CAPTURED: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+#[cfg(test)]
+impl Log for CapturingLogger {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        CAPTURED.lock().unwrap().push(record.args().to_string());
+    }
+
+    fn flush(&self) {}
+}
+
+/// Installs the capturing logger for the whole test binary, if that didn't happen yet
+#[cfg(test)]
+pub fn capture_logs() {
+    static INSTALLED: std::sync::Once = std::sync::Once::new();
+
+    INSTALLED.call_once(|| {
+        log::set_max_level(log::LevelFilter::Trace);
+        log::set_boxed_logger(Box::new(RedactingLogger { inner: CapturingLogger })).unwrap();
+    });
+}
+
+#[cfg(test)]
+pub fn captured_logs() -> Vec<String> {
+    CAPTURED.lock().unwrap().clone()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use log::info;
+
+    use super::*;
+
+    #[test]
+    fn registered_secrets_are_masked() {
+        add_secrets(["secret-token-1f3a9c"]);
+
+        assert_eq!(redact("Bearer secret-token-1f3a9c, again: secret-token-1f3a9c"), format!("Bearer {MASK}, again: {MASK}"));
+    }
+
+    #[test]
+    fn short_secrets_are_skipped() {
+        add_secrets(["short"]);
+
+        assert!(!SECRETS.read().unwrap().iter().any(|secret| secret == "short"));
+        assert_eq!(redact("a short message"), "a short message");
+    }
+
+    #[test]
+    fn logged_secrets_are_masked() {
+        capture_logs();
+        add_secrets(["secret-token-7d2b4e"]);
+
+        info!("Logging in with secret-token-7d2b4e");
+        assert!(captured_logs().contains(&format!("Logging in with {MASK}")));
+    }
+}
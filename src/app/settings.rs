@@ -4,9 +4,9 @@ use log::*;
 use serde::{Deserialize, Serialize};
 use slint::{ModelRc, VecModel};
 
-use crate::{slint_generatedMainWindow::{SlSettings, SlJavaDetails}, launcher::java::JavaDetails};
+use crate::{slint_generatedMainWindow::{SlSettings, SlJavaDetails}, launcher::{authentication::AuthServices, java::JavaDetails, worlds::backups::BackupSettings}};
 
-use super::{consts::SETTINGS_FILE_NAME, slint_utils::SlintOption, utils::get_config_dir};
+use super::{consts::{CURSEFORGE_API_URL, CURSEFORGE_CDN_URL, MODRINTH_API_URL, SETTINGS_FILE_NAME}, slint_utils::SlintOption, utils::get_config_dir};
 
 
 
@@ -16,9 +16,26 @@ pub struct AppSettings {
     pub instance_size: u16,
     pub instance_path: Option<String>,
     pub icon_path: Option<String>,
-    pub java_settings: Vec<JavaDetails>
+    pub java_settings: Vec<JavaDetails>,
+    #[serde(default = "default_curseforge_api_url")]
+    pub curseforge_api_url: String,
+    #[serde(default)]
+    pub curseforge_api_key: Option<String>,
+    /// Serves the files that have no download url in the API
+    #[serde(default = "default_curseforge_cdn_url")]
+    pub curseforge_cdn_url: String,
+    #[serde(default = "default_modrinth_api_url")]
+    pub modrinth_api_url: String,
+    #[serde(default)]
+    pub auth_services: AuthServices,
+    #[serde(default)]
+    pub backup_settings: BackupSettings
 }
 
+fn default_curseforge_api_url() -> String { CURSEFORGE_API_URL.to_string() }
+fn default_curseforge_cdn_url() -> String { CURSEFORGE_CDN_URL.to_string() }
+fn default_modrinth_api_url() -> String { MODRINTH_API_URL.to_string() }
+
 impl AppSettings {
     pub fn get() -> Self {
         info!("Reading settings...");
@@ -61,6 +78,12 @@ impl AppSettings {
             instance_path: None,
             icon_path: None,
             java_settings: Vec::new(),
+            curseforge_api_url: default_curseforge_api_url(),
+            curseforge_api_key: None,
+            curseforge_cdn_url: default_curseforge_cdn_url(),
+            modrinth_api_url: default_modrinth_api_url(),
+            auth_services: AuthServices::default(),
+            backup_settings: BackupSettings::default()
         };
 
         if let Some(parent) = path.parent() {
@@ -83,7 +106,8 @@ impl AppSettings {
             instance_size: self.instance_size.into(),
             java_settings: ModelRc::new(VecModel::from(
                 self.java_settings.iter().map(JavaDetails::to_slint).collect::<Vec<SlJavaDetails>>()
-            ))
+            )),
+            backup_settings: self.backup_settings.to_slint()
         }
     }
 }
\ No newline at end of file
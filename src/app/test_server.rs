@@ -0,0 +1,112 @@
+use std::{collections::HashMap, sync::{Arc, Mutex}};
+
+use tokio::{io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader}, net::TcpListener};
+
+
+/// ### A minimal HTTP server that stands in for the web services in tests
+/// Answers requests for known paths with their canned response, everything else with 404, and records every request it got.
+pub struct TestServer {
+    pub url: String,
+    routes: Arc<Mutex<HashMap<String, TestResponse>>>,
+    requests: Arc<Mutex<Vec<TestRequest>>>
+}
+
+#[derive(Debug, Clone)]
+pub struct TestRequest {
+    pub method: String,
+    /// The path including the query string
+    pub path: String,
+    pub body: String
+}
+
+#[derive(Debug, Clone)]
+pub struct TestResponse {
+    pub status: u16,
+    pub body: Vec<u8>
+}
+
+
+impl TestResponse {
+    pub fn ok(body: impl Into<Vec<u8>>) -> Self {
+        Self { status: 200, body: body.into() }
+    }
+
+    pub fn status(status: u16, body: impl Into<Vec<u8>>) -> Self {
+        Self { status, body: body.into() }
+    }
+}
+
+impl TestServer {
+    pub async fn start() -> Self {
+        let routes = Arc::new(Mutex::new(HashMap::new()));
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let (task_routes, task_requests) = (routes.clone(), requests.clone());
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let (routes, requests) = (task_routes.clone(), task_requests.clone());
+
+                tokio::spawn(async move {
+                    let mut stream = BufReader::new(stream);
+
+                    let mut request_line = String::new();
+                    stream.read_line(&mut request_line).await.ok()?;
+                    let mut parts = request_line.split_whitespace();
+                    let (method, path) = (parts.next()?.to_string(), parts.next()?.to_string());
+
+                    let mut content_length = 0;
+                    loop {
+                        let mut header = String::new();
+                        stream.read_line(&mut header).await.ok()?;
+                        if header.trim().is_empty() {
+                            break;
+                        }
+                        if let Some((name, value)) = header.split_once(':') {
+                            if name.eq_ignore_ascii_case("content-length") {
+                                content_length = value.trim().parse().unwrap_or(0);
+                            }
+                        }
+                    }
+
+                    let mut body = vec![0; content_length];
+                    stream.read_exact(&mut body).await.ok()?;
+
+                    let response = {
+                        let routes = routes.lock().unwrap();
+                        routes.get(&path)
+                        .or_else(|| routes.get(path.split('?').next().unwrap_or_default()))
+                        .cloned()
+                        .unwrap_or(TestResponse::status(404, "Not found"))
+                    };
+
+                    requests.lock().unwrap().push(TestRequest { method, path, body: String::from_utf8_lossy(&body).to_string() });
+
+                    let head = format!(
+                        "HTTP/1.1 {} Test\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        response.status, response.body.len()
+                    );
+                    let stream = stream.get_mut();
+                    stream.write_all(head.as_bytes()).await.ok()?;
+                    stream.write_all(&response.body).await.ok()?;
+                    stream.shutdown().await.ok()
+                });
+            }
+        });
+
+        Self { url, routes, requests }
+    }
+
+    /// ### Answers requests for `path` with `response` from now on
+    /// The path is matched with its query string first, then without it.
+    pub fn route(&self, path: &str, response: TestResponse) -> &Self {
+        self.routes.lock().unwrap().insert(path.to_string(), response);
+        self
+    }
+
+    pub fn requests(&self) -> Vec<TestRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
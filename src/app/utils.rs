@@ -1,4 +1,4 @@
-use std::{fmt::Write, io::Cursor, path::{Path, PathBuf}};
+use std::{fmt::Write, io::{Cursor, Read, Seek, Write as IoWrite}, path::{Path, PathBuf}};
 
 use chrono::TimeDelta;
 use log::*;
@@ -6,6 +6,7 @@ use reqwest::Client;
 use tokio::{fs::{self, create_dir_all, File}, io};
 use sha1_smol::Sha1;
 use dirs::{config_dir, data_dir};
+use zip::{result::ZipResult, write::SimpleFileOptions, ZipArchive, ZipWriter};
 
 
 /// Checks if the checksum of the file at `path` matches `checksum` and downloads it from `url` if not.
@@ -94,6 +95,115 @@ pub fn get_jar_main_class(jar_path: PathBuf) -> String {
     main_class.to_string()
 }
 
+/// Extracts every entry below `prefix` of the archive into `target`, with the prefix stripped.
+/// Entries that would end up outside of `target` are skipped. Returns the number of extracted files.
+pub fn extract_zip_folder<R: Read + Seek>(archive: &mut ZipArchive<R>, prefix: &str, target: &Path) -> ZipResult<u32> {
+    let mut count = 0;
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+
+        let rel_path = match file.enclosed_name() {
+            Some(path) => match path.strip_prefix(prefix) {
+                Ok(rel) if rel.as_os_str().is_empty() => continue,
+                Ok(rel) => rel.to_path_buf(),
+                Err(_) => continue
+            },
+            None => {
+                warn!("Skipping unsafe archive entry {}", file.name());
+                continue
+            }
+        };
+        let file_path = target.join(rel_path);
+
+        if file.is_dir() {
+            std::fs::create_dir_all(&file_path)?;
+        } else {
+            if let Some(parent) = file_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            trace!("Extracting {} to {file_path:?}", file.name());
+            std::io::copy(&mut file, &mut std::fs::File::create(&file_path)?)?;
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
+
+/// Writes the files at `files` (relative to `base`) into the archive, below `prefix`
+pub fn zip_files<W: IoWrite + Seek>(writer: &mut ZipWriter<W>, base: &Path, files: &[PathBuf], prefix: &str) -> ZipResult<()> {
+    let options = SimpleFileOptions::default();
+
+    for rel_path in files {
+        let name = Path::new(prefix).join(rel_path).to_string_lossy().replace('\\', "/");
+        trace!("Compressing {rel_path:?} as {name}");
+
+        writer.start_file(name, options)?;
+        std::io::copy(&mut std::fs::File::open(base.join(rel_path))?, writer)?;
+    }
+
+    Ok(())
+}
+
+/// Recursively lists all files below `dir` as paths relative to it, skipping top-level entries named in `excludes`
+pub fn list_files_relative(dir: &Path, excludes: &[&str]) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![PathBuf::new()];
+
+    while let Some(rel_dir) = dirs.pop() {
+        for entry in std::fs::read_dir(dir.join(&rel_dir))? {
+            let entry = entry?;
+            let rel_path = rel_dir.join(entry.file_name());
+
+            if rel_dir.as_os_str().is_empty() && excludes.iter().any(|&ex| entry.file_name() == ex) {
+                continue;
+            }
+
+            if entry.file_type()?.is_dir() {
+                dirs.push(rel_path);
+            } else {
+                files.push(rel_path);
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Recursively copies all files of `from` into `to`, creating it if needed
+pub fn copy_dir_all(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+
+    for rel_path in list_files_relative(from, &[])? {
+        if let Some(parent) = rel_path.parent() {
+            std::fs::create_dir_all(to.join(parent))?;
+        }
+        std::fs::copy(from.join(&rel_path), to.join(&rel_path))?;
+    }
+
+    Ok(())
+}
+
+/// ### Writes a file that only the current user can read, as it contains secrets
+/// Permissions of existing files are tightened as well, before anything is written to them.
+pub fn write_private_file(path: &Path, contents: &str) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = options.open(path)?;
+
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+
+    file.write_all(contents.as_bytes())
+}
+
 pub async fn create_dir_parents(path: &Path) {
     if let Some(p) = path.parent() {
         fs::create_dir_all(p).await.expect(&format!("Failed to create parent directories {p:?}"))
@@ -128,7 +238,6 @@ pub fn format_time_delta(delta: TimeDelta) -> String {
     output
 }
 
-
 pub fn get_classpath_separator() -> String { String::from(if cfg!(windows) { ";" } else { ":" }) }
 
 
@@ -141,6 +250,6 @@ pub fn get_assets_dir() -> PathBuf { get_data_dir().join("assets") }
 pub fn get_log4j_dir() -> PathBuf { get_data_dir().join("log4j_configs") }
 
 pub fn get_forge_cache_dir() -> PathBuf { get_data_dir().join("forge_cache") }
-pub fn get_installer_extracts_dir(mc_ver: &str, forge_ver: &str) -> PathBuf {
-    get_forge_cache_dir().join(format!("forge-{mc_ver}-{forge_ver}"))
+pub fn get_installer_extracts_dir(installer_name: &str) -> PathBuf {
+    get_forge_cache_dir().join(installer_name)
 }
\ No newline at end of file
@@ -1,12 +1,33 @@
 use chrono::{DateTime, Utc};
 use serde::{Serialize, Deserialize};
 
+use crate::app::encryption::TokenEncryption;
+
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Accounts {
-    pub accounts: Vec<MCAccount>,
-    pub selected_index: Option<u32>
+    pub accounts: Vec<Account>,
+    pub selected_index: Option<u32>,
+    /// How the tokens in the file are encrypted, files from older versions have them in plain text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<TokenEncryption>
+}
+
+/// Accounts from before offline accounts existed are read as Microsoft accounts
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(untagged)]
+#[allow(clippy::large_enum_variant)]
+pub enum Account {
+    Microsoft(MCAccount),
+    Offline(OfflineAccount)
+}
+
+/// An account without authentication, which can only join offline mode servers and LAN worlds
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct OfflineAccount {
+    pub name: String,
+    pub id: String
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -15,7 +36,10 @@ pub struct MCAccount {
     pub xbl_response: XBLResponse,
     pub xsts_response: XBLResponse,
     pub mc_response: MCResponse2,
-    pub mc_profile: MCProfile
+    pub mc_profile: MCProfile,
+    /// Set when refreshing failed in a way that only a new login can fix, like a revoked token
+    #[serde(default)]
+    pub needs_relogin: bool
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -60,6 +84,29 @@ pub struct MSAResponse2 /* Electric Boogaloo! */ {
     pub user_id: String
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct DeviceCodeResponse {
+    pub user_code: String,
+    pub device_code: String,
+    pub verification_uri: String,
+    pub expires_in: u32,
+    pub interval: Option<u32>
+}
+
+/// What the token endpoint answers while the device code login is polled
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(untagged)]
+pub enum DeviceTokenResponse {
+    Token(MSAResponse),
+    Error(OAuthError)
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct OAuthError {
+    pub error: String,
+    pub error_description: Option<String>
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 #[serde(rename_all = "PascalCase")]
 pub struct XBLResponse {
@@ -69,6 +116,16 @@ pub struct XBLResponse {
     pub display_claims: XBLDisplayClaims
 }
 
+/// The body of a refused XSTS request
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct XSTSError {
+    #[serde(rename = "XErr")]
+    pub x_err: u64,
+    pub message: Option<String>,
+    pub redirect: Option<String>
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct MCResponse {
     pub access_token: String,
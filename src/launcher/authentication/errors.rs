@@ -0,0 +1,99 @@
+use reqwest::StatusCode;
+use thiserror::Error;
+
+use super::auth_structs::{OAuthError, XSTSError};
+
+
+pub type AResult<T> = core::result::Result<T, AuthError>;
+
+#[derive(Debug, Error)]
+pub enum AuthError {
+    #[error("No account is selected")]
+    NoAccountSelected,
+    #[error("The tokens of this account can't be decrypted. Unlock the system keyring or set YETALAUNCHER_PASSPHRASE and try again, or log in again.")]
+    TokensLocked,
+    #[error("Failed to reach the {0} service: {1}")]
+    RequestFailed(&'static str, #[source] reqwest::Error),
+    #[error("The {0} service responded with {1}")]
+    BadResponse(&'static str, StatusCode),
+
+    #[error("Microsoft rejected the login ({0}). Please log in again.")]
+    LoginRejected(String),
+    #[error("The Microsoft login failed: {0}")]
+    LoginFailed(String),
+    #[error("The login was declined")]
+    LoginDeclined,
+    #[error("The code expired before it was entered")]
+    CodeExpired,
+
+    #[error("This Microsoft account has no Xbox profile yet. Sign in once at xbox.com to create one, then log in again.")]
+    NoXboxProfile,
+    #[error("This Microsoft account has to accept the Xbox terms first. Sign in once at xbox.com, then log in again.")]
+    XboxTermsNotAccepted,
+    #[error("This Xbox account is banned and can't be used to play.")]
+    XboxBanned,
+    #[error("Xbox Live is not available in the country of this account.")]
+    XboxUnavailableInCountry,
+    #[error("This account needs to complete the adult verification on the Xbox website.")]
+    AdultVerificationRequired,
+    #[error("This is a child account. An adult has to add it to a Microsoft family before it can play.")]
+    ChildAccount,
+    #[error("Xbox Live refused the login with error {0}")]
+    XstsFailed(u64),
+    #[error("Xbox Live answered without a user hash. Please log in again.")]
+    NoUserHash,
+
+    #[error("This account does not own Minecraft")]
+    NoMinecraftOwnership,
+    #[error("This account has no Minecraft profile yet. Launch the game once from the official launcher to create one.")]
+    NoMinecraftProfile
+}
+
+
+impl AuthError {
+    /// ### Decodes the `XErr` code that Xbox Live's XSTS service answers with
+    /// The codes aren't officially documented, these are the ones that players run into.
+    pub fn from_xsts_error(error: &XSTSError) -> Self {
+        match error.x_err {
+            2148916227 => Self::XboxBanned,
+            2148916233 => Self::NoXboxProfile,
+            2148916234 => Self::XboxTermsNotAccepted,
+            2148916235 => Self::XboxUnavailableInCountry,
+            2148916236 | 2148916237 => Self::AdultVerificationRequired,
+            2148916238 => Self::ChildAccount,
+            code => Self::XstsFailed(code)
+        }
+    }
+
+    /// ### Decodes an error of Microsoft's token endpoint
+    /// Only errors about the grant itself, like a revoked or expired refresh token, are rejections. Others like `temporarily_unavailable` can be retried.
+    pub fn from_oauth_error(error: OAuthError) -> Self {
+        let description = error.error_description.unwrap_or(error.error.clone());
+
+        match error.error.as_str() {
+            "invalid_grant" | "interaction_required" | "consent_required" | "login_required" => Self::LoginRejected(description),
+            _ => Self::LoginFailed(description)
+        }
+    }
+
+    /// Reads the reason for a failed response from its body, which every service formats differently
+    pub fn from_response(service: &'static str, status: StatusCode, body: &str) -> Self {
+        if let Ok(error) = serde_json::from_str::<XSTSError>(body) {
+            Self::from_xsts_error(&error)
+        } else if let Ok(error) = serde_json::from_str::<OAuthError>(body) {
+            Self::from_oauth_error(error)
+        } else if status == StatusCode::NOT_FOUND && service == "Minecraft profile" {
+            Self::NoMinecraftProfile
+        } else {
+            Self::BadResponse(service, status)
+        }
+    }
+
+    /// ### Whether the account can't be used until the user logs in again
+    /// Network errors and outages are worth retrying instead.
+    pub fn needs_relogin(&self) -> bool {
+        !matches!(self,
+            Self::NoAccountSelected | Self::TokensLocked | Self::RequestFailed(..) | Self::BadResponse(..) | Self::LoginFailed(..) | Self::XstsFailed(..)
+        )
+    }
+}
@@ -1,174 +1,396 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
-use afire::{Server, Method, Response, Status};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use chrono::{Utc, TimeDelta};
-use clone_macro::clone;
 use log::*;
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder, Url};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::json;
-use tokio::{runtime::Handle, sync::mpsc};
+use sha2::{Digest, Sha256};
+use tokio::{io::{AsyncBufReadExt, AsyncWriteExt, BufReader}, net::{TcpListener, TcpStream}, sync::mpsc, time::{sleep, timeout, Instant}};
 use uuid::Uuid;
 
-use crate::{app::{consts::{MS_CLIENT_ID, REDIRECT_PORT}, notifier::Notifier}, launcher::authentication::auth_structs::*, YetaLauncher};
+use crate::{app::{consts::{MICROSOFT_LOGIN_URL, MINECRAFT_SERVICES_URL, MS_CLIENT_ID, XBOX_LIVE_AUTH_URL, XSTS_AUTH_URL}, notifier::Notifier, redaction::add_secrets}, launcher::authentication::{auth_structs::*, errors::{AResult, AuthError}}, YetaLauncher};
 
 pub mod auth_structs;
+pub mod errors;
 
 
-fn get_login_url() -> String {
-    String::from_iter([
-        "https://login.live.com/oauth20_authorize.srf?client_id=",
-        MS_CLIENT_ID,
-        "&prompt=select_account",
-        "&cobrandid=8058f65d-ce06-4c30-9559-473c9275a65d",
-        "&response_type=code",
-        "&scope=XboxLive.signin%20XboxLive.offline_access",
-        "&redirect_uri=http%3A%2F%2F127.0.0.1%3A",
-        &REDIRECT_PORT.to_string()
-    ])
-}
-fn get_msa_url() -> String {
-    String::from("https://login.live.com/oauth20_token.srf")
-}
-fn get_xbl_url() -> String {
-    String::from("https://user.auth.xboxlive.com/user/authenticate")
-}
-fn get_xsts_url() -> String {
-    String::from("https://xsts.auth.xboxlive.com/xsts/authorize")
-}
-fn get_mc_url() -> String {
-    String::from("https://api.minecraftservices.com/launcher/login")
+/// The local login server is stopped if the login isn't finished in the browser by then
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// ### Where the services of the Microsoft login are reached
+/// Can be changed in the settings, e.g. to go through a proxy.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthServices {
+    pub microsoft_url: String,
+    pub xbox_live_url: String,
+    pub xsts_url: String,
+    pub minecraft_url: String
 }
-fn get_entitlements_url() -> String {
-    String::from("https://api.minecraftservices.com/entitlements/license")
+
+impl Default for AuthServices {
+    fn default() -> Self {
+        Self {
+            microsoft_url: MICROSOFT_LOGIN_URL.to_string(),
+            xbox_live_url: XBOX_LIVE_AUTH_URL.to_string(),
+            xsts_url: XSTS_AUTH_URL.to_string(),
+            minecraft_url: MINECRAFT_SERVICES_URL.to_string()
+        }
+    }
 }
-fn get_mc_profile_url() -> String {
-    String::from("https://api.minecraftservices.com/minecraft/profile")
+
+impl AuthServices {
+    fn get_login_url(&self, redirect_uri: &str, state: &str, code_challenge: &str) -> Result<Url, String> {
+        Url::parse_with_params(&format!("{}/oauth20_authorize.srf", self.microsoft_url), [
+            ("client_id", MS_CLIENT_ID),
+            ("prompt", "select_account"),
+            ("cobrandid", "8058f65d-ce06-4c30-9559-473c9275a65d"),
+            ("response_type", "code"),
+            ("scope", "XboxLive.signin XboxLive.offline_access"),
+            ("redirect_uri", redirect_uri),
+            ("state", state),
+            ("code_challenge", code_challenge),
+            ("code_challenge_method", "S256")
+        ]).map_err(|err| format!("Invalid login url {:?}: {err}", self.microsoft_url))
+    }
+    fn get_device_code_url(&self) -> String {
+        format!("{}/oauth20_connect.srf", self.microsoft_url)
+    }
+    fn get_msa_url(&self) -> String {
+        format!("{}/oauth20_token.srf", self.microsoft_url)
+    }
+    fn get_xbl_url(&self) -> String {
+        format!("{}/user/authenticate", self.xbox_live_url)
+    }
+    fn get_xsts_url(&self) -> String {
+        format!("{}/xsts/authorize", self.xsts_url)
+    }
+    fn get_mc_url(&self) -> String {
+        format!("{}/launcher/login", self.minecraft_url)
+    }
+    fn get_entitlements_url(&self) -> String {
+        format!("{}/entitlements/license", self.minecraft_url)
+    }
+    fn get_mc_profile_url(&self) -> String {
+        format!("{}/minecraft/profile", self.minecraft_url)
+    }
 }
 
 
 
-pub async fn add_account(rt: Handle, app: Arc<YetaLauncher>, notifier: Notifier, on_completion: mpsc::UnboundedSender<()>) {
+/// ### Logs in through the browser, which redirects back to a local server
+/// The server runs on a port picked by the OS and only accepts the redirect of this login, which is checked with `state`.
+/// PKCE makes sure that only this launcher can exchange the code, even if another program sees the redirect.
+pub async fn add_account(app: Arc<YetaLauncher>, notifier: Notifier, on_completion: mpsc::UnboundedSender<()>) {
     info!("Beginning login process...");
-
-    if let Err(err) = open::that(get_login_url()) {
+    let services = app.settings.read().unwrap().auth_services.clone();
+
+    let listener = match TcpListener::bind("127.0.0.1:0").await {
+        Ok(listener) => listener,
+        Err(err) => {
+            error!("Starting redirect server failed: {err}");
+            notifier.send_error(&format!("Failed to start the login server: {err}"));
+            return;
+        }
+    };
+    let port = listener.local_addr().map_or(0, |addr| addr.port());
+    let redirect_uri = format!("http://127.0.0.1:{port}");
+    info!("Started auth redirect server on port {port}");
+
+    let state = Uuid::new_v4().simple().to_string();
+    let (code_verifier, code_challenge) = create_pkce();
+
+    let login_url = match services.get_login_url(&redirect_uri, &state, &code_challenge) {
+        Ok(login_url) => login_url,
+        Err(err) => {
+            error!("{err}");
+            notifier.send_error(&format!("Failed to start the login: {err}"));
+            return;
+        }
+    };
+    if let Err(err) = open::that(login_url.as_str()) {
         error!("Failed to open login page in default browser: {err}");
         notifier.send_error("Failed to open auth link in a web browser!");
         return;
     }
 
-    let mut redirect_server = Server::<()>::new("127.0.0.1", REDIRECT_PORT).keep_alive(false);
-
     notifier.send_msg("Awaiting login... Check your web browser!");
 
-    redirect_server.route(Method::GET, "/", clone!([rt, app], move |req| {
-        if let Some(code) = req.query.get("code") {
+    let code = timeout(LOGIN_TIMEOUT, wait_for_code(&listener, &state)).await;
+    // The server is only needed for the one redirect
+    drop(listener);
+    info!("Stopped auth redirect server");
+
+    match code {
+        Ok(Ok(code)) => {
             info!("Code obtained!");
             notifier.send_msg("Beginning login process...");
 
-            rt.spawn(clone!([{ code.to_string() } as code, notifier, app, on_completion], async move {
-                add_account_code(&code, notifier, app).await;
-                on_completion.send(()).unwrap();
-            }));
-
-            Response::new()
-            .text("You may close this tab now.")
-            .status(Status::Ok)
-        } else {
-            error!("Getting Code failed!");
-            notifier.send_error("Response did not contain code!");
-
-            Response::new()
-            .text("Failed to get the authentication code!")
-            .status(Status::NotFound)
+            add_account_code(&code, &code_verifier, &redirect_uri, &services, notifier, app).await;
+            on_completion.send(()).ok();
+        },
+        Ok(Err(err)) => {
+            warn!("Login was cancelled: {err}");
+            notifier.send_error(&format!("Login cancelled: {err}"));
+        },
+        Err(_) => {
+            warn!("Login timed out");
+            notifier.send_error("Login cancelled, as it wasn't finished in time.");
         }
-    }));
+    }
+}
+
+/// Creates a random PKCE code verifier and its S256 challenge
+fn create_pkce() -> (String, String) {
+    let verifier = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+
+    (verifier, challenge)
+}
 
-    std::thread::spawn(move || {
-        info!("Starting auth redirect HTTP server on port {REDIRECT_PORT}...");
-        if let Err(e) = redirect_server.start() {
-            error!("Starting redirect server failed: {e}")
+/// ### Answers requests to the redirect server until the redirect of this login arrives
+/// Returns the authorization code, or the reason if the login was cancelled in the browser.
+async fn wait_for_code(listener: &TcpListener, state: &str) -> Result<String, String> {
+    loop {
+        let (mut stream, _) = listener.accept().await.map_err(|err| err.to_string())?;
+
+        let Some(url) = read_redirect_url(&mut stream).await else {
+            respond(&mut stream, "404 Not Found", "Not found").await;
+            continue;
         };
-    });
+        let get_param = |name: &str| url.query_pairs().find(|(key, _)| key == name).map(|(_, value)| value.to_string());
+
+        if get_param("state").as_deref() != Some(state) {
+            warn!("Rejected a login redirect with a mismatched state");
+            respond(&mut stream, "400 Bad Request", "This login was not started by YetaLauncher.").await;
+            continue;
+        }
+
+        if let Some(code) = get_param("code") {
+            respond(&mut stream, "200 OK", "You may close this tab now.").await;
+            return Ok(code);
+        }
+
+        let error = get_param("error_description").or(get_param("error")).unwrap_or("No code was received".to_string());
+        respond(&mut stream, "400 Bad Request", "Failed to get the authentication code!").await;
+        return Err(error);
+    }
+}
+
+/// Reads the request line of a redirect to `/`, other requests (like the favicon) are ignored
+async fn read_redirect_url(stream: &mut TcpStream) -> Option<Url> {
+    let mut request_line = String::new();
+    timeout(Duration::from_secs(5), BufReader::new(stream).read_line(&mut request_line)).await.ok()?.ok()?;
+
+    let path = request_line.strip_prefix("GET ")?.split_whitespace().next()?;
+    let url = Url::parse(&format!("http://127.0.0.1{path}")).ok()?;
+
+    (url.path() == "/").then_some(url)
+}
+
+async fn respond(stream: &mut TcpStream, status: &str, body: &str) {
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+
+    if let Err(err) = stream.write_all(response.as_bytes()).await {
+        debug!("Failed to answer request to the redirect server: {err}");
+    }
+    stream.shutdown().await.ok();
 }
 
-async fn add_account_code(code: &str, mut notifier: Notifier, app: Arc<YetaLauncher>) {
+async fn add_account_code(code: &str, code_verifier: &str, redirect_uri: &str, services: &AuthServices, mut notifier: Notifier, app: Arc<YetaLauncher>) {
     info!("Started adding new Minecraft account!");
     let client = Client::new();
 
     notifier.set_progress(1, 7);
     info!("Getting Microsoft Auth response...");
     notifier.send_msg("Getting Microsoft Auth reponse...");
-    let msa_response = MSAResponse2::from_code(code, &client).await;
-    // trace!("{:#?}", msa_response);
+    match MSAResponse2::from_code(code, code_verifier, redirect_uri, &client, services).await {
+        Ok(msa_response) => add_account_msa(msa_response, &client, services, notifier, app).await,
+        Err(err) => {
+            error!("Failed to get Microsoft Auth response: {err}");
+            notifier.set_progress(0, 0);
+            notifier.send_error(&format!("Login failed: {err}"));
+        }
+    }
+}
+
+/// ### Logs in by showing a code that has to be entered on Microsoft's website
+/// Unlike the browser redirect, this works without a browser on this machine and without a local server, e.g. over SSH or in containers.
+pub async fn add_account_device_code(app: Arc<YetaLauncher>, mut notifier: Notifier, on_completion: mpsc::UnboundedSender<()>) {
+    info!("Beginning device code login process...");
+    let client = Client::new();
+    let services = app.settings.read().unwrap().auth_services.clone();
+
+    let device_code = match DeviceCodeResponse::request(&client, &services).await {
+        Ok(device_code) => device_code,
+        Err(err) => {
+            error!("Failed to get a device code: {err}");
+            notifier.send_error(&format!("Failed to start the login: {err}"));
+            return;
+        }
+    };
+
+    notifier.send_msg_with_link(
+        &format!("To sign in, open {} and enter the code {}", device_code.verification_uri, device_code.user_code),
+        &device_code.verification_uri,
+        "Open login page"
+    );
+
+    match device_code.poll_token(&client, &services).await {
+        Ok(msa_response) => {
+            notifier.set_progress(1, 7);
+            notifier.send_msg("Beginning login process...");
+
+            add_account_msa(msa_response, &client, &services, notifier, app).await;
+            on_completion.send(()).ok();
+        },
+        Err(err) => {
+            warn!("Device code login failed: {err}");
+            notifier.send_error(&format!("Login failed: {err}"));
+        }
+    }
+}
 
+/// ### Finishes a login with the Microsoft token by going through Xbox Live, Xsts and Minecraft authentication
+/// The account is only saved if it owns Minecraft.
+async fn add_account_msa(msa_response: MSAResponse2, client: &Client, services: &AuthServices, mut notifier: Notifier, app: Arc<YetaLauncher>) {
+    match get_mc_account(msa_response, client, services, &mut notifier).await {
+        Ok(mc_account) => {
+            let username = mc_account.mc_profile.name.clone();
+
+            // trace!("{:#?}", mc_account);
+            info!("Saving new Minecraft account...");
+            notifier.send_progress("Saving new account...", 7);
+            app.accounts.write().unwrap().save_new_account(Account::Microsoft(mc_account));
+
+            notifier.set_progress(0, 0);
+            notifier.send_success(&format!("Successfully added account '{username}'"));
+            info!("Successfully added new account.");
+        },
+        Err(err) => {
+            error!("Failed to add account: {err}");
+            notifier.set_progress(0, 0);
+            notifier.send_error(&format!("Login failed: {err}"));
+        }
+    }
+}
+
+async fn get_mc_account(msa_response: MSAResponse2, client: &Client, services: &AuthServices, notifier: &mut Notifier) -> AResult<MCAccount> {
     info!("Getting Xbox Live Auth response...");
     notifier.send_progress("Getting Xbox Live Auth reponse...", 2);
-    let xbl_response = msa_response.get_xbl_reponse(&client).await;
+    let xbl_response = msa_response.get_xbl_reponse(client, services).await?;
     // trace!("{:#?}", xbl_response);
 
     info!("Getting Xsts Auth response...");
     notifier.send_progress("Getting Xsts Auth reponse...", 3);
-    let xsts_response = xbl_response.xbl_to_xsts_response(&client).await;
+    let xsts_response = xbl_response.xbl_to_xsts_response(client, services).await?;
     // trace!("{:#?}", xsts_response);
 
     info!("Getting Minecraft Auth response...");
     notifier.send_progress("Getting Minecraft Auth reponse...", 4);
-    let mc_response = xsts_response.xsts_to_mc_response(&client).await;
+    let mc_response = xsts_response.xsts_to_mc_response(client, services).await?;
     // trace!("{:#?}", mc_response);
 
     info!("Checking Minecraft ownership...");
     notifier.send_progress("Checking Minecraft ownership...", 5);
-    if !mc_response.has_mc_ownership(&client).await {
-        notifier.set_progress(0, 0);
-        notifier.send_error("Account does not own Minecraft!");
-        return;
+    if !mc_response.has_mc_ownership(client, services).await? {
+        return Err(AuthError::NoMinecraftOwnership);
     }
 
     info!("Getting Minecraft account...");
     notifier.send_progress("Getting Minecraft account...", 6);
-    let mc_profile = mc_response.get_mc_profile(&client).await;
+    let mc_profile = mc_response.get_mc_profile(client, services).await?;
     // trace!("{:#?}", mc_profile);
 
-    let mc_account = MCAccount {
+    Ok(MCAccount {
         msa_response,
         xbl_response,
         xsts_response,
         mc_response,
-        mc_profile
-    };
-    let username = mc_account.mc_profile.name.clone();
+        mc_profile,
+        needs_relogin: false
+    })
+}
 
-    // trace!("{:#?}", mc_account);
-    info!("Saving new Minecraft account...");
-    notifier.send_progress("Saving new account...", 7);
-    app.accounts.write().unwrap().save_new_account(mc_account);
+/// ### Sends the request and parses the JSON it answers with
+/// Failed responses are decoded into the matching [`AuthError`], `service` names the step in errors.
+async fn send_json<T: DeserializeOwned>(request: RequestBuilder, service: &'static str) -> AResult<T> {
+    let response = request.send().await.map_err(|err| AuthError::RequestFailed(service, err))?;
 
-    notifier.set_progress(0, 0);
-    notifier.send_success(&format!("Successfully added account '{username}'"));
-    info!("Successfully added new account.");
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        debug!("The {service} service responded with {status}: {body}");
+        return Err(AuthError::from_response(service, status, &body));
+    }
+
+    response.json().await.map_err(|err| AuthError::RequestFailed(service, err))
 }
 
 
 
-impl MSAResponse2 {
-    async fn from_code(code: &str, client: &Client) -> Self {
+impl DeviceCodeResponse {
+    async fn request(client: &Client, services: &AuthServices) -> AResult<Self> {
         let params = [
             ("client_id", MS_CLIENT_ID),
-            ("code", code),
-            ("grant_type", "authorization_code"),
-            ("redirect_uri", &String::from_iter(["http://127.0.0.1:", &REDIRECT_PORT.to_string()])),
+            ("response_type", "device_code"),
             ("scope", "XboxLive.signin XboxLive.offline_access")
         ];
 
-        let msa_response: MSAResponse = client.post(get_msa_url())
-        .form(&params)
-        .send()
-        .await
-        .unwrap()
-        .json()
-        .await
-        .unwrap();
+        send_json(client.post(services.get_device_code_url()).form(&params), "Microsoft device code").await
+    }
+
+    /// ### Polls the token endpoint until the code was entered
+    /// Fails once the code expires or the login is declined.
+    async fn poll_token(&self, client: &Client, services: &AuthServices) -> AResult<MSAResponse2> {
+        let params = [
+            ("client_id", MS_CLIENT_ID),
+            ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+            ("device_code", &self.device_code)
+        ];
+
+        let expires_at = Instant::now() + Duration::from_secs(self.expires_in.into());
+        let mut interval = Duration::from_secs(self.interval.unwrap_or(5).into());
+
+        while Instant::now() < expires_at {
+            sleep(interval).await;
+
+            // Pending logins are answered with an error status, so the body is read either way
+            let response: DeviceTokenResponse = client.post(services.get_msa_url())
+            .form(&params)
+            .send()
+            .await
+            .map_err(|err| AuthError::RequestFailed("Microsoft login", err))?
+            .json()
+            .await
+            .map_err(|err| AuthError::RequestFailed("Microsoft login", err))?;
+
+            match response {
+                DeviceTokenResponse::Token(msa_response) => {
+                    add_secrets([msa_response.access_token.as_str(), &msa_response.refresh_token]);
+                    return Ok(msa_response.into());
+                },
+                DeviceTokenResponse::Error(err) => match err.error.as_str() {
+                    "authorization_pending" => continue,
+                    "slow_down" => interval += Duration::from_secs(5),
+                    "authorization_declined" => return Err(AuthError::LoginDeclined),
+                    "expired_token" | "bad_verification_code" => break,
+                    _ => return Err(AuthError::from_oauth_error(err))
+                }
+            }
+        }
+
+        Err(AuthError::CodeExpired)
+    }
+}
 
+impl From<MSAResponse> for MSAResponse2 {
+    fn from(msa_response: MSAResponse) -> Self {
         MSAResponse2 {
             token_type: msa_response.token_type,
             expires_at: Utc::now() + TimeDelta::try_seconds(msa_response.expires_in.into()).unwrap_or_default(),
@@ -178,8 +400,26 @@ impl MSAResponse2 {
             user_id: msa_response.user_id,
         }
     }
+}
+
+impl MSAResponse2 {
+    async fn from_code(code: &str, code_verifier: &str, redirect_uri: &str, client: &Client, services: &AuthServices) -> AResult<Self> {
+        let params = [
+            ("client_id", MS_CLIENT_ID),
+            ("code", code),
+            ("code_verifier", code_verifier),
+            ("grant_type", "authorization_code"),
+            ("redirect_uri", redirect_uri),
+            ("scope", "XboxLive.signin XboxLive.offline_access")
+        ];
+
+        let msa_response: MSAResponse = send_json(client.post(services.get_msa_url()).form(&params), "Microsoft login").await?;
+        add_secrets([msa_response.access_token.as_str(), &msa_response.refresh_token]);
+
+        Ok(msa_response.into())
+    }
 
-    async fn refresh(&mut self, client: &Client) {
+    async fn refresh(&mut self, client: &Client, services: &AuthServices) -> AResult<()> {
         let params = [
             ("client_id", MS_CLIENT_ID),
             ("grant_type", "refresh_token"),
@@ -187,28 +427,14 @@ impl MSAResponse2 {
             ("scope", &self.scope)
         ];
 
-        let msa_response: MSAResponse = client.post(get_msa_url())
-        .form(&params)
-        .send()
-        .await
-        .unwrap()
-        .json()
-        .await
-        .unwrap();
-
-        debug!("res {:#?}", msa_response);
+        let msa_response: MSAResponse = send_json(client.post(services.get_msa_url()).form(&params), "Microsoft login").await?;
+        add_secrets([msa_response.access_token.as_str(), &msa_response.refresh_token]);
 
-        *self = MSAResponse2 {
-            token_type: msa_response.token_type,
-            expires_at: Utc::now() + TimeDelta::try_seconds(msa_response.expires_in.into()).unwrap_or_default(),
-            scope: msa_response.scope,
-            access_token: msa_response.access_token,
-            refresh_token: msa_response.refresh_token,
-            user_id: msa_response.user_id,
-        };
+        *self = msa_response.into();
+        Ok(())
     }
 
-    async fn get_xbl_reponse(&self, client: &Client) -> XBLResponse {
+    async fn get_xbl_reponse(&self, client: &Client, services: &AuthServices) -> AResult<XBLResponse> {
         let json = json!({
             "Properties": {
                 "AuthMethod": "RPS",
@@ -219,19 +445,19 @@ impl MSAResponse2 {
             "TokenType": "JWT"
         });
 
-        client.post(get_xbl_url())
-        .json(&json)
-        .send()
-        .await
-        .unwrap()
-        .json()
-        .await
-        .unwrap()
+        let xbl_response: XBLResponse = send_json(client.post(services.get_xbl_url()).json(&json), "Xbox Live").await?;
+        xbl_response.add_secrets();
+
+        Ok(xbl_response)
     }
 }
 
 impl XBLResponse {
-    async fn xbl_to_xsts_response(&self, client: &Client) -> Self {
+    pub fn add_secrets(&self) {
+        add_secrets([self.token.as_str()].into_iter().chain(self.display_claims.xui.iter().map(|claims| claims.uhs.as_str())));
+    }
+
+    async fn xbl_to_xsts_response(&self, client: &Client, services: &AuthServices) -> AResult<Self> {
         let json = json!({
             "Properties": {
                 "SandboxId": "RETAIL",
@@ -243,78 +469,75 @@ impl XBLResponse {
             "TokenType": "JWT"
         });
 
-        client.post(get_xsts_url())
-        .json(&json)
-        .send()
-        .await
-        .unwrap()
-        .json()
-        .await
-        .unwrap()
+        let xsts_response: XBLResponse = send_json(client.post(services.get_xsts_url()).json(&json), "Xsts").await?;
+        xsts_response.add_secrets();
+
+        Ok(xsts_response)
     }
 
-    async fn xsts_to_mc_response(&self, client: &Client) -> MCResponse2 {
+    async fn xsts_to_mc_response(&self, client: &Client, services: &AuthServices) -> AResult<MCResponse2> {
+        let uhs = &self.display_claims.xui.first().ok_or(AuthError::NoUserHash)?.uhs;
         let json = json!({
-            "xtoken": String::from_iter(["XBL3.0 x=", &self.display_claims.xui[0].uhs, ";", &self.token]),
+            "xtoken": String::from_iter(["XBL3.0 x=", uhs, ";", &self.token]),
             "platform": "PC_LAUNCHER"
         });
     
-        let mc_response: MCResponse = client.post(get_mc_url())
-        .json(&json)
-        .send()
-        .await
-        .unwrap()
-        .json()
-        .await
-        .unwrap();
+        let mc_response: MCResponse = send_json(client.post(services.get_mc_url()).json(&json), "Minecraft login").await?;
+        add_secrets([mc_response.access_token.as_str()]);
     
-        MCResponse2 {
+        Ok(MCResponse2 {
             access_token: mc_response.access_token,
             expires_at: Utc::now() + TimeDelta::try_seconds(mc_response.expires_in.into()).unwrap_or_default(),
             username: mc_response.username,
             token_type: mc_response.token_type,
-        }
+        })
     }
 }
 
 impl MCResponse2 {
-    async fn get_mc_profile(&self, client: &Client) -> MCProfile {
-        let mcprofile_response: MCProfile = client.get(get_mc_profile_url())
-        .header("Authorization", String::from_iter(["Bearer ", &self.access_token]))
-        .send()
-        .await
-        .unwrap()
-        .json()
-        .await
-        .unwrap();
-    
-        mcprofile_response
-    }
-
-    async fn has_mc_ownership(&self, client: &Client) -> bool {
-        let entitlements_response: Entitlements = client.get(
-            String::from_iter([&get_entitlements_url(), "?requestId=", &Uuid::new_v4().to_string()])
-        )
-        .header("Authorization", String::from_iter(["Bearer ", &self.access_token]))
-        .header("Content-Type", "application/json")
-        .header("Accept", "application/json")
-        .send()
-        .await
-        .unwrap()
-        .json()
-        .await
-        .unwrap();
+    async fn get_mc_profile(&self, client: &Client, services: &AuthServices) -> AResult<MCProfile> {
+        send_json(
+            client.get(services.get_mc_profile_url())
+            .header("Authorization", String::from_iter(["Bearer ", &self.access_token])),
+            "Minecraft profile"
+        ).await
+    }
+
+    async fn has_mc_ownership(&self, client: &Client, services: &AuthServices) -> AResult<bool> {
+        let entitlements_response: Entitlements = send_json(
+            client.get(String::from_iter([&services.get_entitlements_url(), "?requestId=", &Uuid::new_v4().to_string()]))
+            .header("Authorization", String::from_iter(["Bearer ", &self.access_token]))
+            .header("Content-Type", "application/json")
+            .header("Accept", "application/json"),
+            "Minecraft entitlements"
+        ).await?;
         // trace!("{:#?}", entitlements_response);
 
-        entitlements_response.items.iter().any(|item| 
+        Ok(entitlements_response.items.iter().any(|item| 
             item.name.eq_ignore_ascii_case("product_minecraft") || item.name.eq_ignore_ascii_case("game_minecraft")
-        )
+        ))
     }
 }
 
 
 impl MCAccount {
-    pub async fn refresh(&mut self, client: &Client, force: bool) {
+    /// ### Refreshes the tokens that expired, or all of them with `force`
+    /// If the refresh fails in a way that only a new login can fix, the account is marked with `needs_relogin`, which is cleared again by a successful refresh.
+    pub async fn refresh(&mut self, client: &Client, services: &AuthServices, force: bool) -> AResult<()> {
+        let result = self.refresh_tokens(client, services, force).await;
+
+        match &result {
+            Ok(()) => self.needs_relogin = false,
+            Err(err) => if err.needs_relogin() {
+                warn!("Account '{}' needs to log in again: {err}", self.mc_profile.name);
+                self.needs_relogin = true;
+            }
+        }
+
+        result
+    }
+
+    async fn refresh_tokens(&mut self, client: &Client, services: &AuthServices, force: bool) -> AResult<()> {
         let now = Utc::now();
 
         if self.mc_response.expires_at < now || force {
@@ -322,17 +545,196 @@ impl MCAccount {
                 if self.xbl_response.not_after < now || force {
                     if self.msa_response.expires_at < now || force {
                         info!("Refreshing Microsoft Token...");
-                        self.msa_response.refresh(client).await;
+                        self.msa_response.refresh(client, services).await?;
                     }
                     info!("Refreshing Xbox Live Token...");
-                    self.xbl_response = self.msa_response.get_xbl_reponse(client).await;
+                    self.xbl_response = self.msa_response.get_xbl_reponse(client, services).await?;
                 }
                 info!("Refreshing Xsts Token...");
-                self.xsts_response = self.xbl_response.xbl_to_xsts_response(client).await;
+                self.xsts_response = self.xbl_response.xbl_to_xsts_response(client, services).await?;
             }
             info!("Refreshing Minecraft Token...");
-            self.mc_response = self.xsts_response.xsts_to_mc_response(client).await;
+            self.mc_response = self.xsts_response.xsts_to_mc_response(client, services).await?;
         }
+
+        Ok(())
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::app::{redaction::{capture_logs, captured_logs}, test_server::{TestResponse, TestServer}};
+
+    /// Sends the requests for each service to `<stand-in>/<host>`
+    async fn start_stand_in() -> (TestServer, AuthServices) {
+        capture_logs();
+        let server = TestServer::start().await;
+        let services = AuthServices {
+            microsoft_url: format!("{}/login.live.com", server.url),
+            xbox_live_url: format!("{}/user.auth.xboxlive.com", server.url),
+            xsts_url: format!("{}/xsts.auth.xboxlive.com", server.url),
+            minecraft_url: format!("{}/api.minecraftservices.com", server.url)
+        };
+        (server, services)
+    }
+
+    fn msa_json(access_token: &str, refresh_token: &str) -> Value {
+        json!({
+            "token_type": "bearer", "expires_in": 3600, "scope": "XboxLive.signin XboxLive.offline_access",
+            "access_token": access_token, "refresh_token": refresh_token, "user_id": "user"
+        })
+    }
+
+    fn xbl_json(token: &str, uhs: &str) -> Value {
+        json!({ "IssueInstant": Utc::now(), "NotAfter": Utc::now(), "Token": token, "DisplayClaims": { "xui": [{ "uhs": uhs }] } })
+    }
+
+    fn route_logins(server: &TestServer, msa: Value, xbl: Value, xsts: Value, mc: TestResponse) {
+        server.route("/login.live.com/oauth20_token.srf", TestResponse::ok(msa.to_string()))
+        .route("/user.auth.xboxlive.com/user/authenticate", TestResponse::ok(xbl.to_string()))
+        .route("/xsts.auth.xboxlive.com/xsts/authorize", TestResponse::ok(xsts.to_string()))
+        .route("/api.minecraftservices.com/launcher/login", mc);
+    }
+
+    fn logged_record(start: &str) -> String {
+        captured_logs().into_iter().find(|record| record.starts_with(start)).unwrap()
+    }
+
+    fn assert_redacted(record: &str, tokens: &[&str]) {
+        assert!(record.contains("<redacted>"));
+        for token in tokens {
+            assert!(!record.contains(token), "{token} was logged");
+        }
+    }
+
+    fn expired_account(name: &str) -> MCAccount {
+        let expired = Utc::now() - TimeDelta::try_hours(1).unwrap();
+        let xbl = serde_json::from_value::<XBLResponse>(xbl_json(&format!("{name}-old-xbl-token"), &format!("{name}-old-uhs"))).unwrap();
+
+        MCAccount {
+            msa_response: MSAResponse2 {
+                expires_at: expired,
+                ..serde_json::from_value::<MSAResponse>(msa_json(&format!("{name}-old-msa-access"), &format!("{name}-old-msa-refresh"))).unwrap().into()
+            },
+            xbl_response: XBLResponse { not_after: expired, ..xbl.clone() },
+            xsts_response: XBLResponse { not_after: expired, ..xbl },
+            mc_response: MCResponse2 { access_token: format!("{name}-old-mc-access"), expires_at: expired, username: "player".to_string(), token_type: "Bearer".to_string() },
+            mc_profile: MCProfile { id: "0a1b2c".to_string(), name: name.to_string(), skins: Vec::new(), capes: Vec::new() },
+            needs_relogin: false
+        }
+    }
+
+    #[tokio::test]
+    async fn login_tokens_are_redacted() {
+        let (server, services) = start_stand_in().await;
+        let tokens = ["code-msa-access-8f2d", "code-msa-refresh-8f2d", "code-xbl-token-8f2d", "code-xsts-token-8f2d", "code-uhs-8f2d"];
+        // Error responses are logged with their body, which may echo what was sent
+        route_logins(&server,
+            msa_json(tokens[0], tokens[1]),
+            xbl_json(tokens[2], tokens[4]),
+            xbl_json(tokens[3], tokens[4]),
+            TestResponse::status(500, json!({ "message": "Internal error", "echo": tokens }).to_string())
+        );
+
+        let client = Client::new();
+        let msa = MSAResponse2::from_code("code-1234", "verifier", "http://127.0.0.1/", &client, &services).await.unwrap();
+        let xbl = msa.get_xbl_reponse(&client, &services).await.unwrap();
+        let xsts = xbl.xbl_to_xsts_response(&client, &services).await.unwrap();
+        let result = xsts.xsts_to_mc_response(&client, &services).await;
+        assert!(matches!(result, Err(AuthError::BadResponse("Minecraft login", _))));
+
+        let requests = server.requests();
+        assert!(requests[0].body.contains("grant_type=authorization_code"));
+        assert!(requests[2].body.contains("code-xbl-token-8f2d"));
+        assert!(requests[3].body.contains("XBL3.0 x=code-uhs-8f2d;code-xsts-token-8f2d"));
+
+        let logged = logged_record("The Minecraft login service responded with 500");
+        assert!(logged.contains("Internal error"));
+        assert_redacted(&logged, &tokens);
+    }
+
+    #[tokio::test]
+    async fn refreshed_tokens_are_redacted() {
+        let (server, services) = start_stand_in().await;
+        let tokens = ["refresh-msa-access-3b7a", "refresh-msa-refresh-3b7a", "refresh-xbl-token-3b7a", "refresh-xsts-token-3b7a", "refresh-uhs-3b7a"];
+        route_logins(&server,
+            msa_json(tokens[0], tokens[1]),
+            xbl_json(tokens[2], tokens[4]),
+            xbl_json(tokens[3], tokens[4]),
+            TestResponse::status(503, json!({ "message": "Unavailable", "echo": tokens }).to_string())
+        );
+
+        let mut account = expired_account("RefreshPlayer");
+        let result = account.refresh(&Client::new(), &services, false).await;
+        assert!(matches!(result, Err(AuthError::BadResponse("Minecraft login", _))));
+        assert_eq!(account.xsts_response.token, "refresh-xsts-token-3b7a");
+
+        let token_request = server.requests().into_iter().find(|request| request.path == "/login.live.com/oauth20_token.srf").unwrap();
+        assert!(token_request.body.contains("grant_type=refresh_token") && token_request.body.contains("RefreshPlayer-old-msa-refresh"));
+
+        let logged = logged_record("The Minecraft login service responded with 503");
+        assert!(logged.contains("Unavailable"));
+        assert_redacted(&logged, &tokens);
+    }
+
+    #[tokio::test]
+    async fn rejected_refresh_needs_relogin() {
+        let (server, services) = start_stand_in().await;
+        server.route("/login.live.com/oauth20_token.srf", TestResponse::status(400, json!({
+            "error": "invalid_grant", "error_description": "The refresh token RejectedPlayer-old-msa-refresh has expired"
+        }).to_string()));
+
+        let mut account = expired_account("RejectedPlayer");
+        // Happens when the accounts are read
+        account.add_secrets();
+        let result = account.refresh(&Client::new(), &services, false).await;
+
+        assert!(matches!(result, Err(AuthError::LoginRejected(_))));
+        assert!(account.needs_relogin);
+
+        assert_redacted(&logged_record("The Microsoft login service responded with 400"), &["RejectedPlayer-old-msa-refresh"]);
+        assert_redacted(&logged_record("Account 'RejectedPlayer' needs to log in again"), &["RejectedPlayer-old-msa-refresh"]);
+    }
+
+    #[tokio::test]
+    async fn missing_user_hash_needs_relogin() {
+        let (server, services) = start_stand_in().await;
+        route_logins(&server,
+            msa_json("no-uhs-msa-access-5d9c", "no-uhs-msa-refresh-5d9c"),
+            xbl_json("no-uhs-xbl-token-5d9c", "no-uhs-5d9c"),
+            json!({ "IssueInstant": Utc::now(), "NotAfter": Utc::now(), "Token": "no-uhs-xsts-token-5d9c", "DisplayClaims": { "xui": [] } }),
+            TestResponse::ok(json!({ "access_token": "no-uhs-mc-access-5d9c", "expires_in": 86400, "username": "player", "token_type": "Bearer" }).to_string())
+        );
+
+        let mut account = expired_account("NoHashPlayer");
+        let result = account.refresh(&Client::new(), &services, false).await;
+
+        assert!(matches!(result, Err(AuthError::NoUserHash)));
+        assert!(account.needs_relogin);
+        assert!(!server.requests().iter().any(|request| request.path == "/api.minecraftservices.com/launcher/login"));
+    }
+
+    #[tokio::test]
+    async fn xsts_errors_are_decoded() {
+        let (server, services) = start_stand_in().await;
+        server.route("/xsts.auth.xboxlive.com/xsts/authorize", TestResponse::status(401, json!({
+            "Identity": "0", "XErr": 2148916233u64, "Message": "", "Redirect": "https://start.ui.xboxlive.com/CreateAccount"
+        }).to_string()));
+
+        let xbl: XBLResponse = serde_json::from_value(xbl_json("xsts-error-xbl-token-6c1e", "xsts-error-uhs-6c1e")).unwrap();
+        xbl.add_secrets();
+
+        let result = xbl.xbl_to_xsts_response(&Client::new(), &services).await;
+        assert!(matches!(result, Err(AuthError::NoXboxProfile)));
+
+        let logged = captured_logs().into_iter().find(|record| record.contains("2148916233")).unwrap();
+        assert!(logged.starts_with("The Xsts service responded with 401"));
+
+        let request = server.requests().pop().unwrap();
+        assert!(request.body.contains("xsts-error-xbl-token-6c1e"));
+    }
+}
@@ -1,13 +1,17 @@
-use std::{path::{Path, PathBuf}, str::FromStr, sync::Arc};
+use std::{collections::HashMap, path::{Path, PathBuf}, str::FromStr, sync::Arc};
 
 use log::*;
 use reqwest::Client;
-use serde::{Serialize, Deserialize};
-use tokio::fs;
+use serde::{de::DeserializeOwned, Serialize, Deserialize};
+use serde_json::{json, Value};
+use tokio::{fs, task};
 
-use crate::{app::{consts::META_FILE_NAME, utils::download_file_checked}, YetaLauncher};
+use crate::{app::{consts::META_FILE_NAME, downloader::{Download, Downloader}, notifier::Notifier, utils::{download_file_checked, extract_zip_folder}}, launcher::modloaders::ModLoaders, YetaLauncher};
 
-use super::{errors::InstanceGatherError, IResult, InstanceType};
+use super::{errors::InstanceGatherError, modrinth::is_safe_relative_path, native::NativeConfig, open_archive, read_archive_file, IResult, InstanceType, SimpleInstance};
+
+
+pub const CF_MANIFEST_NAME: &str = "manifest.json";
 
 
 
@@ -58,6 +62,43 @@ impl CFInstance {
         )
     }
 
+    /// ### Writes the last session to `minecraftinstance.json` and the play time to our metadata
+    /// `minecraftinstance.json` is edited as plain JSON, so fields we don't know about are kept.
+    pub async fn save_play_session(instance: &SimpleInstance) -> IResult<()> {
+        let path = instance.instance_path.join("minecraftinstance.json");
+        let pack_file = fs::read(&path).await.map_err(
+            |err| InstanceGatherError::FileReadFailed(path.clone(), err)
+        )?;
+        let mut instance_json: Value = serde_json::from_slice(&pack_file).map_err(
+            |err| InstanceGatherError::ParseFailedJson(InstanceType::CurseForge, path.clone(), err)
+        )?;
+
+        if let Some(instance_json) = instance_json.as_object_mut() {
+            if let Some(last_played) = instance.last_played {
+                instance_json.insert("lastPlayed".to_string(), json!(last_played.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()));
+            }
+            if let Some(play_count) = instance.play_count {
+                instance_json.insert("playedCount".to_string(), json!(play_count));
+            }
+        }
+
+        fs::write(&path, serde_json::to_string_pretty(&instance_json).unwrap(/* this cannot fail */)).await.map_err(
+            |err| InstanceGatherError::FileWriteFailed(path, err)
+        )?;
+
+        let meta_path = instance.instance_path.join(META_FILE_NAME);
+        let meta_file = fs::read(&meta_path).await.map_err(
+            |err| InstanceGatherError::FileReadFailed(meta_path.clone(), err)
+        )?;
+        let mut meta: CFMetadata = serde_json::from_slice(&meta_file).map_err(
+            |err| InstanceGatherError::ParseFailedMeta(meta_path, err)
+        )?;
+
+        meta.last_played_for = instance.last_played_for.map(|time| time.num_seconds());
+        meta.total_time_played = instance.total_time_played.map(|time| time.num_seconds());
+        meta.save(&instance.instance_path).await
+    }
+
     async fn download_icon(instance_path: &Path, app: Arc<YetaLauncher>) -> IResult<Option<String>> {
         let instance = Self::get(instance_path).await?;
 
@@ -142,8 +183,6 @@ impl CFMetadata {
     }
 
     async fn generate(instance_path: &Path, app: Arc<YetaLauncher>) -> IResult<Self> {
-        let path = instance_path.join(META_FILE_NAME);
-
         let meta = CFMetadata {
             instance_id: fastrand::u32(..),
             last_played_for: None,
@@ -157,10 +196,319 @@ impl CFMetadata {
             }
         };
 
-        fs::write(&path, serde_json::to_string_pretty(&meta).unwrap(/* this cannot fail */)).await.map_err(
+        meta.save(instance_path).await?;
+        Ok(meta)
+    }
+
+    pub async fn save(&self, instance_path: &Path) -> IResult<()> {
+        let path = instance_path.join(META_FILE_NAME);
+
+        fs::write(&path, serde_json::to_string_pretty(self).unwrap(/* this cannot fail */)).await.map_err(
             |err| InstanceGatherError::FileWriteFailed(path, err)
+        )
+    }
+}
+
+// Handling the "manifest.json" file of exported CurseForge modpacks
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CFManifest {
+    pub minecraft: CFManifestMinecraft,
+    pub name: String,
+    pub version: Option<String>,
+    pub author: Option<String>,
+    pub files: Vec<CFManifestFile>,
+    pub overrides: Option<String>
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CFManifestMinecraft {
+    pub version: String,
+    pub mod_loaders: Vec<CFManifestLoader>
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CFManifestLoader {
+    pub id: String,
+    pub primary: bool
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CFManifestFile {
+    #[serde(rename = "projectID")]
+    pub project_id: u64,
+    #[serde(rename = "fileID")]
+    pub file_id: u64,
+    pub required: Option<bool>
+}
+
+// Responses of the CurseForge API
+#[derive(Debug, Deserialize)]
+struct CFApiResponse<T> {
+    data: T
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CFApiFile {
+    id: u64,
+    mod_id: u64,
+    file_name: String,
+    download_url: Option<String>,
+    hashes: Vec<CFApiHash>,
+    file_length: u64
+}
+
+#[derive(Debug, Deserialize)]
+struct CFApiHash {
+    value: String,
+    algo: u8
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CFApiMod {
+    id: u64,
+    class_id: Option<u32>
+}
+
+
+impl CFManifest {
+    pub async fn get(pack_path: &Path) -> IResult<Self> {
+        let path = pack_path.to_path_buf();
+
+        task::spawn_blocking(move || {
+            let contents = read_archive_file(&path, CF_MANIFEST_NAME)?;
+
+            serde_json::from_slice(&contents).map_err(
+                |err| InstanceGatherError::ParseFailedJson(InstanceType::CurseForge, path.join(CF_MANIFEST_NAME), err)
+            )
+        }).await.unwrap()
+    }
+
+    /// ### Imports an exported CurseForge modpack zip as a new native instance
+    /// Resolves the listed files through the CurseForge API and CDN set in the settings and unpacks the overrides on top.
+    pub async fn import(pack_path: &Path, app: Arc<YetaLauncher>, notifier: &Notifier) -> IResult<SimpleInstance> {
+        let mut notifier = notifier.make_new();
+        notifier.set_progress(1, 4);
+        notifier.send_msg("Reading modpack manifest...");
+
+        let manifest = Self::get(pack_path).await?;
+        info!("Importing CurseForge pack '{}' ({})", manifest.name, manifest.version.as_deref().unwrap_or("unknown version"));
+
+        let (api_url, api_key, cdn_url) = {
+            let settings = app.settings.read().unwrap();
+            (settings.curseforge_api_url.to_string(), settings.curseforge_api_key.clone(), settings.curseforge_cdn_url.to_string())
+        };
+        let (loader, loader_version) = manifest.get_loader();
+
+        let instance = NativeConfig::new(&manifest.name, &manifest.minecraft.version, loader, &loader_version).create(app).await?;
+
+        if let Err(err) = manifest.install(pack_path, &instance.minecraft_path, &api_url, api_key.as_deref(), &cdn_url, &mut notifier).await {
+            instance.discard().await;
+            notifier.send_remove();
+            Err(err)?;
+        }
+
+        notifier.set_progress(0, 0);
+        notifier.send_success(&format!("Successfully imported '{}'", manifest.name));
+
+        Ok(instance)
+    }
+
+    async fn install(&self, pack_path: &Path, minecraft_path: &Path, api_url: &str, api_key: Option<&str>, cdn_url: &str, notifier: &mut Notifier) -> IResult<()> {
+        let client = Client::new();
+        let required_files: Vec<&CFManifestFile> = self.files.iter().filter(|file| file.required.unwrap_or(true)).collect();
+
+        notifier.send_progress("Resolving modpack files...", 2);
+        let files: Vec<CFApiFile> = cf_api_post(&client, api_url, api_key, "mods/files", json!({
+            "fileIds": required_files.iter().map(|file| file.file_id).collect::<Vec<u64>>()
+        })).await?;
+        let mods: Vec<CFApiMod> = cf_api_post(&client, api_url, api_key, "mods", json!({
+            "modIds": required_files.iter().map(|file| file.project_id).collect::<Vec<u64>>()
+        })).await?;
+
+        let class_ids: HashMap<u64, u32> = mods.into_iter().filter_map(
+            |cf_mod| cf_mod.class_id.map(|class_id| (cf_mod.id, class_id))
+        ).collect();
+
+        if let Some(missing) = required_files.iter().find(|req| !files.iter().any(|file| file.id == req.file_id)) {
+            Err(InstanceGatherError::InvalidPack(pack_path.to_path_buf(), format!("File {} of project {} could not be resolved", missing.file_id, missing.project_id)))?;
+        }
+
+        notifier.send_progress("Downloading modpack files...", 3);
+        let mut downloader = Downloader::new(notifier.make_new(), 8);
+
+        for file in files {
+            let rel_path = format!("{}/{}", get_class_folder(class_ids.get(&file.mod_id)), file.file_name);
+            if !is_safe_relative_path(&rel_path) {
+                Err(InstanceGatherError::InvalidPack(pack_path.to_path_buf(), format!("Unsafe file name {:?}", file.file_name)))?;
+            }
+
+            // Files of projects that opted out of third party distribution have no download url, but are still on the CDN
+            let url = file.download_url.clone().unwrap_or_else(
+                || format!("{}/files/{}/{}/{}", cdn_url.trim_end_matches('/'), file.id / 1000, file.id % 1000, file.file_name)
+            );
+            let sha1 = file.hashes.iter().find(|hash| hash.algo == 1).map(|hash| hash.value.to_string());
+
+            downloader.add(Download::new(minecraft_path.join(rel_path), &url, sha1, file.file_length.try_into().ok()));
+        }
+
+        downloader.download_all(true, "modpack files").await.map_err(
+            |err| InstanceGatherError::DownloadFailed(format!("modpack files: {err:?}"))
         )?;
 
-        Ok(meta)
+        notifier.send_progress("Applying overrides...", 4);
+        let (pack_path, minecraft_path) = (pack_path.to_path_buf(), minecraft_path.to_path_buf());
+        let overrides = self.overrides.clone().unwrap_or("overrides".to_string());
+
+        task::spawn_blocking(move || {
+            let count = extract_zip_folder(&mut open_archive(&pack_path)?, &overrides, &minecraft_path).map_err(
+                |err| InstanceGatherError::ArchiveReadFailed(pack_path.clone(), err)
+            )?;
+            debug!("Extracted {count} file(s) from {overrides}/");
+
+            Ok(())
+        }).await.unwrap()
+    }
+
+    fn get_loader(&self) -> (ModLoaders, String) {
+        let loaders = &self.minecraft.mod_loaders;
+
+        loaders.iter()
+        .find(|loader| loader.primary)
+        .or(loaders.first())
+        .and_then(|loader| {
+            let loader_type = ModLoaders::from_cf(&loader.id)?;
+            let (_, version) = loader.id.split_once('-')?;
+
+            Some((loader_type, version.to_string()))
+        })
+        .unwrap_or((ModLoaders::Vanilla, String::new()))
+    }
+}
+
+async fn cf_api_post<T: DeserializeOwned>(client: &Client, api_url: &str, api_key: Option<&str>, endpoint: &str, body: Value) -> IResult<T> {
+    let url = format!("{}/{endpoint}", api_url.trim_end_matches('/'));
+    debug!("Requesting {url}...");
+
+    let mut request = client.post(&url).json(&body);
+    if let Some(key) = api_key {
+        request = request.header("x-api-key", key);
     }
-}
\ No newline at end of file
+
+    let response: CFApiResponse<T> = request
+    .send()
+    .await
+    .and_then(|res| res.error_for_status())
+    .map_err(|err| InstanceGatherError::DownloadFailed(format!("{url}: {err}")))?
+    .json()
+    .await
+    .map_err(|err| InstanceGatherError::DownloadFailed(format!("{url}: {err}")))?;
+
+    Ok(response.data)
+}
+
+/// Maps CurseForge class ids to the folder their files belong in
+fn get_class_folder(class_id: Option<&u32>) -> &'static str {
+    match class_id {
+        Some(12) => "resourcepacks",
+        Some(6552) => "shaderpacks",
+        _ => "mods"
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::Write};
+
+    use sha1_smol::Sha1;
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    use super::*;
+    use crate::app::{notifier::InternalNotifier, test_server::{TestResponse, TestServer}};
+
+    fn write_pack(path: &Path, manifest: Value, files: &[(&str, &str)]) {
+        let mut writer = ZipWriter::new(File::create(path).unwrap());
+        writer.start_file(CF_MANIFEST_NAME, SimpleFileOptions::default()).unwrap();
+        writer.write_all(manifest.to_string().as_bytes()).unwrap();
+
+        for (name, contents) in files {
+            writer.start_file(*name, SimpleFileOptions::default()).unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    fn api_file(id: u64, mod_id: u64, file_name: &str, contents: &[u8], download_url: Option<String>) -> Value {
+        json!({
+            "id": id, "modId": mod_id, "fileName": file_name, "downloadUrl": download_url,
+            "hashes": [{ "value": Sha1::from(contents).digest().to_string(), "algo": 1 }],
+            "fileLength": contents.len()
+        })
+    }
+
+    #[tokio::test]
+    async fn pack_is_installed_from_stand_in() {
+        let dir = tempfile::tempdir().unwrap();
+        let (pack_path, minecraft_path) = (dir.path().join("pack.zip"), dir.path().join("minecraft"));
+
+        let server = TestServer::start().await;
+        server.route("/api/mods/files", TestResponse::ok(json!({ "data": [
+            api_file(1234, 10, "example.jar", b"mod", Some(format!("{}/example.jar", server.url))),
+            // Opted out of third party downloads, so only on the CDN
+            api_file(4567, 20, "textures.zip", b"pack", None)
+        ] }).to_string()))
+        .route("/api/mods", TestResponse::ok(json!({ "data": [{ "id": 10, "classId": 6 }, { "id": 20, "classId": 12 }] }).to_string()))
+        .route("/example.jar", TestResponse::ok(b"mod".to_vec()))
+        .route("/cdn/files/4/567/textures.zip", TestResponse::ok(b"pack".to_vec()));
+
+        write_pack(&pack_path, json!({
+            "minecraft": { "version": "1.21", "modLoaders": [{ "id": "fabric-0.16.5", "primary": true }] },
+            "name": "Example Pack", "version": "1.0.0", "author": "Someone",
+            "files": [{ "projectID": 10, "fileID": 1234, "required": true }, { "projectID": 20, "fileID": 4567 }],
+            "overrides": "overrides"
+        }), &[("overrides/config/example.txt", "config")]);
+
+        let manifest = CFManifest::get(&pack_path).await.unwrap();
+        assert_eq!(manifest.get_loader(), (ModLoaders::Fabric, "0.16.5".to_string()));
+
+        let mut notifier = InternalNotifier::new().make_notifier();
+        let (api_url, cdn_url) = (format!("{}/api", server.url), format!("{}/cdn/", server.url));
+        manifest.install(&pack_path, &minecraft_path, &api_url, None, &cdn_url, &mut notifier).await.unwrap();
+
+        assert_eq!(std::fs::read(minecraft_path.join("mods/example.jar")).unwrap(), b"mod");
+        assert_eq!(std::fs::read(minecraft_path.join("resourcepacks/textures.zip")).unwrap(), b"pack");
+        assert_eq!(std::fs::read_to_string(minecraft_path.join("config/example.txt")).unwrap(), "config");
+
+        let lookup = server.requests().into_iter().find(|request| request.path == "/api/mods/files").unwrap();
+        assert_eq!(lookup.method, "POST");
+        assert!(lookup.body.contains("1234") && lookup.body.contains("4567"));
+    }
+
+    #[tokio::test]
+    async fn unresolved_files_fail_the_import() {
+        let dir = tempfile::tempdir().unwrap();
+        let (pack_path, minecraft_path) = (dir.path().join("pack.zip"), dir.path().join("minecraft"));
+
+        let server = TestServer::start().await;
+        server.route("/mods/files", TestResponse::ok(r#"{ "data": [] }"#))
+        .route("/mods", TestResponse::ok(r#"{ "data": [] }"#));
+
+        write_pack(&pack_path, json!({
+            "minecraft": { "version": "1.21", "modLoaders": [] },
+            "name": "Example Pack",
+            "files": [{ "projectID": 10, "fileID": 1234 }]
+        }), &[]);
+
+        let manifest = CFManifest::get(&pack_path).await.unwrap();
+        let mut notifier = InternalNotifier::new().make_notifier();
+
+        assert!(matches!(
+            manifest.install(&pack_path, &minecraft_path, &server.url, None, &server.url, &mut notifier).await,
+            Err(InstanceGatherError::InvalidPack(..))
+        ));
+    }
+}
@@ -5,6 +5,8 @@ use serde::Serialize;
 use thiserror::Error;
 use tokio::io;
 
+use crate::launcher::modloaders::ModLoaders;
+
 use super::InstanceType;
 
 
@@ -42,6 +44,26 @@ pub enum InstanceGatherError {
 
     #[error("Minecraft version could not be found in mmc-pack.json of {0}")]
     MinecraftNotFound(PathBuf),
+
+    #[error("{0:?} is not a valid instance name")]
+    InvalidName(String),
+    #[error("No Minecraft version was selected for the new instance")]
+    VersionUnset,
+    #[error("No {0} version was entered for the new instance")]
+    LoaderVersionUnset(ModLoaders),
+
+    #[error("Failed to read archive at {0:?}: {1}")]
+    ArchiveReadFailed(PathBuf, #[source] zip::result::ZipError),
+    #[error("Failed to write archive at {0:?}: {1}")]
+    ArchiveWriteFailed(PathBuf, #[source] zip::result::ZipError),
+    #[error("Invalid modpack at {0:?}: {1}")]
+    InvalidPack(PathBuf, String),
+    #[error("Invalid world at {0:?}: {1}")]
+    InvalidWorld(PathBuf, String),
+    #[error("Failed to download {0}")]
+    DownloadFailed(String),
+    #[error("Renamed {renamed} mod(s), but {} failed: {}", .failed.len(), .failed.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    ModsRenameFailed { renamed: u32, failed: Vec<InstanceGatherError> },
 }
 
 
@@ -1,22 +1,25 @@
-use std::{cmp::Ordering, fs::File, io::BufReader, path::{Path, PathBuf}, sync::Arc};
+use std::{cmp::Ordering, fs::File, io::{BufReader, Read}, path::{Path, PathBuf}, sync::Arc};
 
 use clone_macro::clone;
 use image::RgbaImage;
 use slint::{Image, SharedPixelBuffer};
-use tokio::{fs, runtime::Handle, task::JoinSet, time::Instant};
+use tokio::{fs, runtime::Handle, task::{self, JoinSet}, time::Instant};
+use zip::ZipArchive;
 use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
 use log::{*};
 use serde::{Deserialize, Serialize};
 
-use crate::{app::{notifier::Notifier, slint_utils::SlintOption, utils::format_time_delta}, SlInstanceType, SlSimpleInstance, YetaLauncher};
+use crate::{app::{consts::NATIVE_FILE_NAME, notifier::Notifier, slint_utils::SlintOption, utils::format_time_delta}, SlInstanceType, SlSimpleInstance, YetaLauncher};
 
-use self::{errors::InstanceGatherError, multimc::*, curseforge::*};
+use self::{errors::InstanceGatherError, multimc::*, curseforge::*, native::*, modrinth::*};
 
 use super::modloaders::ModLoaders;
 
 pub mod errors;
 pub mod curseforge;
 pub mod multimc;
+pub mod native;
+pub mod modrinth;
 pub mod instance;
 
 // Instance Gather Result
@@ -48,7 +51,8 @@ pub struct ModLoader {
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum InstanceType {
     CurseForge,
-    MultiMC
+    MultiMC,
+    Native
 }
 
 
@@ -70,7 +74,10 @@ pub async fn get_instances(app: Arc<YetaLauncher>, notifier: &Notifier) -> IResu
                 let p = &path.path();
                 trace!("Scanning folder {p:?}");
 
-                if p.join("minecraftinstance.json").is_file() {
+                if p.join(NATIVE_FILE_NAME).is_file() {
+                    trace!("Found {NATIVE_FILE_NAME} in {p:?}");
+                    Some(SimpleInstance::get_from_native(&path.path(), app).await)
+                } else if p.join("minecraftinstance.json").is_file() {
                     trace!("Found minecraftinstance.json in {p:?}");
                     Some(SimpleInstance::get_from_cf(&path.path(), app).await)
                 } else if p.join("instance.cfg").is_file() {
@@ -112,6 +119,67 @@ pub async fn get_instances(app: Arc<YetaLauncher>, notifier: &Notifier) -> IResu
     Ok(instances)
 }
 
+/// Imports a modpack or exported instance file into the instance directory, detecting its format
+pub async fn import_instance(path: &Path, app: Arc<YetaLauncher>, notifier: &Notifier) -> IResult<SimpleInstance> {
+    info!("Importing instance from {path:?}");
+
+    let file_names = {
+        let path = path.to_path_buf();
+        task::spawn_blocking(move || {
+            open_archive(&path).map(|archive| archive.file_names().map(String::from).collect::<Vec<String>>())
+        }).await.unwrap()?
+    };
+
+    if file_names.iter().any(|name| name == MRPACK_INDEX_NAME) {
+        MRPackIndex::import(path, app, notifier).await
+    } else if file_names.iter().any(|name| name == CF_MANIFEST_NAME) {
+        CFManifest::import(path, app, notifier).await
+    } else if let Some(prefix) = MMCConfig::find_in_archive(&file_names) {
+        MMCConfig::import(path, &prefix, app, notifier).await
+    } else {
+        Err(InstanceGatherError::InvalidPack(path.to_path_buf(), "Unrecognized modpack format".to_string()))
+    }
+}
+
+/// Turns an instance name into a folder name in `dir` that isn't taken yet
+pub fn get_free_instance_path(dir: &Path, name: &str) -> PathBuf {
+    let folder_name: String = name.trim().chars().map(
+        |c| if c.is_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.') { c } else { '_' }
+    ).collect();
+
+    let mut path = dir.join(&folder_name);
+    let mut i = 1;
+
+    while path.exists() {
+        i += 1;
+        path = dir.join(format!("{folder_name} ({i})"));
+    }
+
+    path
+}
+
+pub fn open_archive(path: &Path) -> IResult<ZipArchive<BufReader<File>>> {
+    let file = File::open(path).map_err(
+        |err| InstanceGatherError::FileReadFailed(path.to_path_buf(), err)
+    )?;
+
+    ZipArchive::new(BufReader::new(file)).map_err(
+        |err| InstanceGatherError::ArchiveReadFailed(path.to_path_buf(), err)
+    )
+}
+
+pub fn read_archive_file(path: &Path, name: &str) -> IResult<Vec<u8>> {
+    let mut contents = Vec::new();
+
+    open_archive(path)?.by_name(name).map_err(
+        |err| InstanceGatherError::ArchiveReadFailed(path.to_path_buf(), err)
+    )?.read_to_end(&mut contents).map_err(
+        |err| InstanceGatherError::FileReadFailed(path.join(name), err)
+    )?;
+
+    Ok(contents)
+}
+
 
 impl SimpleInstance {
     pub async fn get_from_mmc(path: &Path, app: Arc<YetaLauncher>) -> IResult<Self> {
@@ -193,7 +261,7 @@ impl SimpleInstance {
 
                 if time.timestamp() > 10 { Some(time) } else { None }
             },
-            last_played_for: None,
+            last_played_for: meta.last_played_for.map(TimeDelta::seconds),
             modloader: {
                 let vanilla = ModLoader {
                     name: "Vanilla".into(),
@@ -213,6 +281,54 @@ impl SimpleInstance {
         })
     }
 
+    pub async fn get_from_native(path: &Path, app: Arc<YetaLauncher>) -> IResult<Self> {
+        let config = NativeConfig::get(path).await?;
+
+        Ok(SimpleInstance {
+            instance_type: InstanceType::Native,
+            minecraft_path: path.join("minecraft"),
+            instance_path: path.to_path_buf(),
+
+            icon: if let Some(path) = config.get_icon(app) {
+                Self::load_image(path).await
+            } else { None },
+            id: config.instance_id,
+            name: config.name,
+            mc_version: config.mc_version,
+            modloader: config.modloader,
+            last_played: config.last_played.and_then(DateTime::from_timestamp_millis),
+            last_played_for: config.last_played_for.map(TimeDelta::seconds),
+            total_time_played: config.total_time_played.map(TimeDelta::seconds),
+            play_count: config.play_count
+        })
+    }
+
+    /// ### Records a finished play session in this instance and in its files
+    /// The session counts as the last time the instance was played and is added to the total play time.
+    pub async fn save_play_session(&mut self, started: DateTime<Utc>, ended: DateTime<Utc>) -> IResult<()> {
+        let played_for = ended - started;
+
+        self.last_played = Some(started);
+        self.last_played_for = Some(played_for);
+        self.total_time_played = Some(self.total_time_played.unwrap_or(TimeDelta::zero()) + played_for);
+        self.play_count = Some(self.play_count.unwrap_or(0) + 1);
+
+        debug!("Saving play session of {} for {:?}", format_time_delta(played_for), self.name);
+        match self.instance_type {
+            InstanceType::MultiMC => MMCConfig::save_play_session(self).await,
+            InstanceType::CurseForge => CFInstance::save_play_session(self).await,
+            InstanceType::Native => NativeConfig::save_play_session(self).await
+        }
+    }
+
+    /// Removes the files of an instance whose import failed halfway through
+    pub async fn discard(&self) {
+        warn!("Removing partially imported instance at {:?}", self.instance_path);
+        fs::remove_dir_all(&self.instance_path).await.unwrap_or_else(
+            |err| error!("Failed to remove {:?}: {err}", self.instance_path)
+        );
+    }
+
     pub async fn to_slint(&self) -> SlSimpleInstance {
         SlSimpleInstance {
             icon: if let Some(icon) = &self.icon {
@@ -241,7 +357,7 @@ impl SimpleInstance {
         }
     }
 
-    async fn load_image(path: String) -> Option<RgbaImage> {
+    pub async fn load_image(path: String) -> Option<RgbaImage> {
         Handle::current().spawn(async move {
             let reader = BufReader::new(
                 File::open(&path).or_else(
@@ -278,6 +394,7 @@ impl InstanceType {
         match self { // this sucks even more, but is necessary
             InstanceType::CurseForge => SlInstanceType::CurseForge,
             InstanceType::MultiMC => SlInstanceType::MultiMC,
+            InstanceType::Native => SlInstanceType::Native,
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,374 @@
+use std::{collections::HashMap, fs::File, io::Write, path::{Component, Path, PathBuf}, sync::Arc};
+
+use chrono::Utc;
+use log::*;
+use reqwest::Client;
+use serde::{Serialize, Deserialize};
+use sha1_smol::Sha1;
+use sha2::{Digest, Sha512};
+use tokio::task;
+use zip::{result::ZipResult, write::SimpleFileOptions, ZipWriter};
+
+use crate::{app::{consts::{META_FILE_NAME, MOD_BACKUP_FOLDER, NATIVE_FILE_NAME, WORLD_BACKUP_FOLDER}, downloader::{Download, Downloader}, notifier::Notifier, utils::{extract_zip_folder, list_files_relative, zip_files}}, launcher::{modloaders::ModLoaders, modrinth_api::MRVersion}, YetaLauncher};
+
+use super::{errors::InstanceGatherError, native::NativeConfig, open_archive, read_archive_file, IResult, SimpleInstance};
+
+
+pub const MRPACK_INDEX_NAME: &str = "modrinth.index.json";
+
+/// Top-level entries of the minecraft folder that never get exported
+const EXPORT_EXCLUDES: [&str; 11] = [
+    "saves", "logs", "crash-reports", "screenshots", "natives", "backups",
+    "minecraftinstance.json", META_FILE_NAME, NATIVE_FILE_NAME, MOD_BACKUP_FOLDER, WORLD_BACKUP_FOLDER
+];
+/// Folders whose files are looked up on Modrinth, so they can be referenced instead of bundled
+const LOOKUP_FOLDERS: [&str; 3] = ["mods", "resourcepacks", "shaderpacks"];
+
+// Handling the "modrinth.index.json" file of .mrpack files
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MRPackIndex {
+    pub format_version: u32,
+    pub game: String,
+    pub version_id: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    pub files: Vec<MRPackFile>,
+    pub dependencies: HashMap<String, String>
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MRPackFile {
+    pub path: String,
+    pub hashes: MRPackHashes,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<MRPackEnv>,
+    pub downloads: Vec<String>,
+    pub file_size: u64
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MRPackHashes {
+    pub sha1: String,
+    pub sha512: String
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MRPackEnv {
+    pub client: MRPackEnvSupport,
+    pub server: MRPackEnvSupport
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MRPackEnvSupport {
+    Required,
+    Optional,
+    Unsupported
+}
+
+
+impl MRPackIndex {
+    pub async fn get(pack_path: &Path) -> IResult<Self> {
+        let path = pack_path.to_path_buf();
+
+        task::spawn_blocking(move || {
+            let contents = read_archive_file(&path, MRPACK_INDEX_NAME)?;
+
+            serde_json::from_slice(&contents).map_err(
+                |err| InstanceGatherError::ParseFailedMeta(path.join(MRPACK_INDEX_NAME), err)
+            )
+        }).await.unwrap()
+    }
+
+    /// ### Imports a .mrpack file as a new native instance
+    /// Downloads all client files listed in the index and applies `overrides/` and `client-overrides/` on top.
+    pub async fn import(pack_path: &Path, app: Arc<YetaLauncher>, notifier: &Notifier) -> IResult<SimpleInstance> {
+        let mut notifier = notifier.make_new();
+        notifier.set_progress(1, 3);
+        notifier.send_msg("Reading modpack index...");
+
+        let index = Self::get(pack_path).await?;
+        info!("Importing Modrinth pack '{}' ({})", index.name, index.version_id);
+
+        if index.game != "minecraft" {
+            Err(InstanceGatherError::InvalidPack(pack_path.to_path_buf(), format!("Unsupported game {:?}", index.game)))?;
+        }
+
+        let mc_version = index.dependencies.get("minecraft").ok_or_else(
+            || InstanceGatherError::InvalidPack(pack_path.to_path_buf(), "No Minecraft version found in dependencies".to_string())
+        )?;
+        let (loader, loader_version) = index.get_loader();
+
+        let instance = NativeConfig::new(&index.name, mc_version, loader, &loader_version).create(app).await?;
+
+        if let Err(err) = index.install(pack_path, &instance.minecraft_path, &mut notifier).await {
+            instance.discard().await;
+            notifier.send_remove();
+            Err(err)?;
+        }
+
+        notifier.set_progress(0, 0);
+        notifier.send_success(&format!("Successfully imported '{}'", index.name));
+
+        Ok(instance)
+    }
+
+    async fn install(&self, pack_path: &Path, minecraft_path: &Path, notifier: &mut Notifier) -> IResult<()> {
+        notifier.send_progress("Downloading modpack files...", 2);
+        let mut downloader = Downloader::new(notifier.make_new(), 8);
+
+        for file in self.files.iter().filter(|file| file.is_client_file()) {
+            if !is_safe_relative_path(&file.path) {
+                Err(InstanceGatherError::InvalidPack(pack_path.to_path_buf(), format!("Unsafe file path {:?}", file.path)))?;
+            }
+
+            let url = file.downloads.first().ok_or_else(
+                || InstanceGatherError::InvalidPack(pack_path.to_path_buf(), format!("No download for {:?}", file.path))
+            )?;
+
+            downloader.add(
+                Download::new(
+                    minecraft_path.join(&file.path),
+                    url,
+                    Some(file.hashes.sha1.to_string()),
+                    file.file_size.try_into().ok()
+                ).with_sha512(Some(file.hashes.sha512.to_string()))
+            );
+        }
+
+        downloader.download_all(true, "modpack files").await.map_err(
+            |err| InstanceGatherError::DownloadFailed(format!("modpack files: {err:?}"))
+        )?;
+
+        notifier.send_progress("Applying overrides...", 3);
+        let (pack_path, minecraft_path) = (pack_path.to_path_buf(), minecraft_path.to_path_buf());
+
+        task::spawn_blocking(move || {
+            let mut archive = open_archive(&pack_path)?;
+
+            // Client overrides are applied last so they take precedence
+            for prefix in ["overrides", "client-overrides"] {
+                let count = extract_zip_folder(&mut archive, prefix, &minecraft_path).map_err(
+                    |err| InstanceGatherError::ArchiveReadFailed(pack_path.clone(), err)
+                )?;
+                debug!("Extracted {count} file(s) from {prefix}/");
+            }
+
+            Ok(())
+        }).await.unwrap()
+    }
+
+    /// ### Exports an instance as a .mrpack file at `target`
+    /// Files that Modrinth knows by their hash are referenced in the index, everything else is put into `overrides/`.
+    pub async fn export(instance: &SimpleInstance, target: &Path, app: Arc<YetaLauncher>, notifier: &Notifier) -> IResult<()> {
+        let mut notifier = notifier.make_new();
+        notifier.set_progress(1, 3);
+        notifier.send_msg(&format!("Hashing files of '{}'...", instance.name));
+
+        let minecraft_path = instance.minecraft_path.clone();
+        let mut files = task::spawn_blocking(move || {
+            list_files_relative(&minecraft_path, &EXPORT_EXCLUDES).map_err(
+                |err| InstanceGatherError::FileReadFailed(minecraft_path.clone(), err)
+            )?
+            .into_iter()
+            .map(|rel_path| MRPackFile::from_local(&minecraft_path, rel_path))
+            .collect::<IResult<Vec<MRPackFile>>>()
+        }).await.unwrap()?;
+
+        notifier.send_progress("Looking up files on Modrinth...", 2);
+        let lookup_hashes: Vec<String> = files.iter()
+        .filter(|file| LOOKUP_FOLDERS.iter().any(|&folder| file.path.starts_with(&format!("{folder}/"))))
+        .map(|file| file.hashes.sha1.to_string())
+        .collect();
+
+        let api_url = app.settings.read().unwrap().modrinth_api_url.to_string();
+        let versions = MRVersion::from_hashes(&lookup_hashes, &api_url, &Client::new()).await.unwrap_or_else(|| {
+            warn!("Modrinth lookup failed, bundling all files as overrides");
+            HashMap::new()
+        });
+
+        for file in &mut files {
+            if let Some(version_file) = versions.get(&file.hashes.sha1).and_then(|ver| ver.get_file(&file.hashes.sha1)) {
+                file.downloads.push(version_file.url.to_string());
+            }
+        }
+
+        let (files, overrides): (Vec<MRPackFile>, Vec<MRPackFile>) = files.into_iter().partition(|file| !file.downloads.is_empty());
+        info!("Exporting '{}' with {} referenced file(s) and {} override(s)", instance.name, files.len(), overrides.len());
+
+        let mut dependencies = HashMap::from([("minecraft".to_string(), instance.mc_version.to_string())]);
+        if let Some(loader) = instance.modloader.typ.to_mrpack() {
+            dependencies.insert(loader.to_string(), instance.modloader.version.to_string());
+        }
+
+        let index = MRPackIndex {
+            format_version: 1,
+            game: "minecraft".to_string(),
+            version_id: Utc::now().format("%Y.%m.%d").to_string(),
+            name: instance.name.to_string(),
+            summary: None,
+            files,
+            dependencies
+        };
+
+        notifier.send_progress("Writing modpack file...", 3);
+        let (minecraft_path, target_path) = (instance.minecraft_path.clone(), target.to_path_buf());
+        let override_paths: Vec<PathBuf> = overrides.into_iter().map(|file| PathBuf::from(file.path)).collect();
+
+        task::spawn_blocking(move || {
+            let file = File::create(&target_path).map_err(
+                |err| InstanceGatherError::FileWriteFailed(target_path.clone(), err)
+            )?;
+
+            index.write_pack(ZipWriter::new(file), &minecraft_path, &override_paths).map_err(
+                |err| InstanceGatherError::ArchiveWriteFailed(target_path, err)
+            )
+        }).await.unwrap()?;
+
+        notifier.set_progress(0, 0);
+        notifier.send_success(&format!("Exported '{}' to {}", instance.name, target.to_string_lossy()));
+
+        Ok(())
+    }
+
+    fn write_pack(&self, mut writer: ZipWriter<File>, minecraft_path: &Path, overrides: &[PathBuf]) -> ZipResult<()> {
+        writer.start_file(MRPACK_INDEX_NAME, SimpleFileOptions::default())?;
+        writer.write_all(serde_json::to_string_pretty(self).unwrap(/* this cannot fail */).as_bytes())?;
+
+        zip_files(&mut writer, minecraft_path, overrides, "overrides")?;
+        writer.finish()?;
+
+        Ok(())
+    }
+
+    fn get_loader(&self) -> (ModLoaders, String) {
+        self.dependencies.iter()
+        .find_map(|(dependency, version)| {
+            ModLoaders::from_mrpack(dependency).map(|loader| (loader, version.to_string()))
+        })
+        .unwrap_or((ModLoaders::Vanilla, String::new()))
+    }
+}
+
+impl MRPackFile {
+    fn from_local(base: &Path, rel_path: PathBuf) -> IResult<Self> {
+        let path = base.join(&rel_path);
+        let contents = std::fs::read(&path).map_err(
+            |err| InstanceGatherError::FileReadFailed(path, err)
+        )?;
+
+        Ok(Self {
+            path: rel_path.to_string_lossy().replace('\\', "/"),
+            hashes: MRPackHashes {
+                sha1: Sha1::from(&contents).digest().to_string(),
+                sha512: format!("{:x}", Sha512::digest(&contents))
+            },
+            env: None,
+            downloads: Vec::new(),
+            file_size: contents.len() as u64
+        })
+    }
+
+    fn is_client_file(&self) -> bool {
+        self.env.as_ref().is_none_or(|env| env.client != MRPackEnvSupport::Unsupported)
+    }
+}
+
+/// Makes sure a path from a pack index can't escape the folder it gets joined onto
+pub fn is_safe_relative_path(path: &str) -> bool {
+    let path = PathBuf::from(path);
+
+    path.components().next().is_some()
+    &&
+    path.components().all(|comp| matches!(comp, Component::Normal(_)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::app::{notifier::InternalNotifier, test_server::{TestResponse, TestServer}};
+
+    fn write_mrpack(path: &Path, index: serde_json::Value, files: &[(&str, &str)]) {
+        let mut writer = ZipWriter::new(File::create(path).unwrap());
+        writer.start_file(MRPACK_INDEX_NAME, SimpleFileOptions::default()).unwrap();
+        writer.write_all(index.to_string().as_bytes()).unwrap();
+
+        for (name, contents) in files {
+            writer.start_file(*name, SimpleFileOptions::default()).unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    fn pack_file(path: &str, contents: &[u8], url: &str, client: &str) -> serde_json::Value {
+        json!({
+            "path": path,
+            "hashes": { "sha1": Sha1::from(contents).digest().to_string(), "sha512": format!("{:x}", Sha512::digest(contents)) },
+            "env": { "client": client, "server": "required" },
+            "downloads": [url],
+            "fileSize": contents.len()
+        })
+    }
+
+    #[tokio::test]
+    async fn mrpack_is_installed_from_stand_in() {
+        let dir = tempfile::tempdir().unwrap();
+        let (pack_path, minecraft_path) = (dir.path().join("pack.mrpack"), dir.path().join("minecraft"));
+
+        let server = TestServer::start().await;
+        server.route("/example.jar", TestResponse::ok(b"mod".to_vec()))
+        .route("/server-only.jar", TestResponse::ok(b"server".to_vec()));
+
+        write_mrpack(&pack_path, json!({
+            "formatVersion": 1, "game": "minecraft", "versionId": "1.0.0", "name": "Example Pack",
+            "files": [
+                pack_file("mods/example.jar", b"mod", &format!("{}/example.jar", server.url), "required"),
+                pack_file("mods/server-only.jar", b"server", &format!("{}/server-only.jar", server.url), "unsupported")
+            ],
+            "dependencies": { "minecraft": "1.21", "fabric-loader": "0.16.5" }
+        }), &[
+            ("overrides/config/example.txt", "common"),
+            ("overrides/options.txt", "lang:en_us"),
+            ("client-overrides/config/example.txt", "client")
+        ]);
+
+        let index = MRPackIndex::get(&pack_path).await.unwrap();
+        assert_eq!(index.get_loader(), (ModLoaders::Fabric, "0.16.5".to_string()));
+
+        let mut notifier = InternalNotifier::new().make_notifier();
+        index.install(&pack_path, &minecraft_path, &mut notifier).await.unwrap();
+
+        assert_eq!(std::fs::read(minecraft_path.join("mods/example.jar")).unwrap(), b"mod");
+        assert!(!minecraft_path.join("mods/server-only.jar").exists());
+        assert_eq!(std::fs::read_to_string(minecraft_path.join("config/example.txt")).unwrap(), "client");
+        assert_eq!(std::fs::read_to_string(minecraft_path.join("options.txt")).unwrap(), "lang:en_us");
+        assert!(server.requests().iter().all(|request| request.path != "/server-only.jar"));
+    }
+
+    #[tokio::test]
+    async fn failed_mrpack_download_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let (pack_path, minecraft_path) = (dir.path().join("pack.mrpack"), dir.path().join("minecraft"));
+        let server = TestServer::start().await;
+
+        write_mrpack(&pack_path, json!({
+            "formatVersion": 1, "game": "minecraft", "versionId": "1.0.0", "name": "Example Pack",
+            "files": [pack_file("mods/missing.jar", b"mod", &format!("{}/missing.jar", server.url), "required")],
+            "dependencies": { "minecraft": "1.21" }
+        }), &[]);
+
+        let index = MRPackIndex::get(&pack_path).await.unwrap();
+        let mut notifier = InternalNotifier::new().make_notifier();
+
+        assert!(matches!(
+            index.install(&pack_path, &minecraft_path, &mut notifier).await,
+            Err(InstanceGatherError::DownloadFailed(_))
+        ));
+    }
+}
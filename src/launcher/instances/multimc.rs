@@ -1,12 +1,13 @@
-use std::{path::{Path, PathBuf}, str::FromStr, sync::Arc};
+use std::{fs::File, path::{Path, PathBuf}, str::FromStr, sync::Arc};
 
-use log::warn;
+use log::*;
 use serde::{Serialize, Deserialize};
-use tokio::fs;
+use tokio::{fs, task};
+use zip::ZipWriter;
 
-use crate::{app::consts::META_FILE_NAME, YetaLauncher};
+use crate::{app::{consts::{META_FILE_NAME, MOD_BACKUP_FOLDER, WORLD_BACKUP_FOLDER}, notifier::Notifier, utils::{extract_zip_folder, list_files_relative, zip_files}}, YetaLauncher};
 
-use super::{errors::InstanceGatherError, IResult, InstanceType};
+use super::{errors::InstanceGatherError, get_free_instance_path, open_archive, read_archive_file, IResult, InstanceType, SimpleInstance};
 
 
 // Handling the "instance.cfg" file
@@ -26,13 +27,162 @@ impl MMCConfig {
     pub async fn get(path: &Path) -> IResult<Self> {
         let instance_file = fs::read_to_string(path.join("instance.cfg")).await.map_err(
             |err| InstanceGatherError::FileReadFailed(path.join("instance.cfg").to_path_buf(), err)
-        )?.replace("[General]", ""); // Remove the section if there is one
+        )?;
+
+        Self::parse(&instance_file, path)
+    }
+
+    fn parse(instance_file: &str, path: &Path) -> IResult<Self> {
+        let instance_file = instance_file.replace("[General]", ""); // Remove the section if there is one
 
         serde_ini::from_str(&instance_file).map_err(
             |err| InstanceGatherError::ParseFailedIni(InstanceType::MultiMC, path.to_path_buf(), err)
         )
     }
 
+    /// Finds the folder containing `instance.cfg` in an archive, which is either the root or a single nested folder
+    pub fn find_in_archive(file_names: &[String]) -> Option<String> {
+        file_names.iter().find_map(|name| {
+            match name.split('/').collect::<Vec<&str>>()[..] {
+                ["instance.cfg"] => Some(String::new()),
+                [folder, "instance.cfg"] => Some(folder.to_string()),
+                _ => None
+            }
+        })
+    }
+
+    /// ### Imports a zipped MultiMC / Prism Launcher instance into the instance directory
+    /// `prefix` is the folder inside the archive that contains `instance.cfg`. The instance gets a fresh id.
+    pub async fn import(pack_path: &Path, prefix: &str, app: Arc<YetaLauncher>, notifier: &Notifier) -> IResult<SimpleInstance> {
+        let mut notifier = notifier.make_new();
+        notifier.set_progress(1, 2);
+        notifier.send_msg("Reading instance...");
+
+        let dir = app.settings.read().unwrap().instance_path.clone().ok_or(InstanceGatherError::PathUnset)?;
+        let (pack_path, prefix) = (pack_path.to_path_buf(), prefix.to_string());
+
+        let instance_path = task::spawn_blocking(move || {
+            let in_archive = |name: &str| if prefix.is_empty() { name.to_string() } else { format!("{prefix}/{name}") };
+
+            // Validate the instance before extracting anything
+            let config = Self::parse(
+                &String::from_utf8_lossy(&read_archive_file(&pack_path, &in_archive("instance.cfg"))?),
+                &pack_path
+            )?;
+            MMCPack::parse(&read_archive_file(&pack_path, &in_archive("mmc-pack.json"))?, &pack_path)?;
+
+            let instance_path = get_free_instance_path(Path::new(&dir), &config.name);
+            info!("Importing MultiMC instance '{}' to {instance_path:?}", config.name);
+
+            let count = extract_zip_folder(&mut open_archive(&pack_path)?, &prefix, &instance_path).map_err(
+                |err| InstanceGatherError::ArchiveReadFailed(pack_path.clone(), err)
+            )?;
+            debug!("Extracted {count} file(s)");
+
+            Ok::<PathBuf, InstanceGatherError>(instance_path)
+        }).await.unwrap()?;
+
+        notifier.send_progress("Generating metadata...", 2);
+        let result = match MMCMetadata::generate(&instance_path).await {
+            Ok(_) => SimpleInstance::get_from_mmc(&instance_path, app).await,
+            Err(err) => Err(err)
+        };
+
+        match result {
+            Ok(instance) => {
+                notifier.set_progress(0, 0);
+                notifier.send_success(&format!("Successfully imported '{}'", instance.name));
+                Ok(instance)
+            },
+            Err(err) => {
+                warn!("Removing partially imported instance at {instance_path:?}");
+                fs::remove_dir_all(&instance_path).await.unwrap_or_else(
+                    |err| error!("Failed to remove {instance_path:?}: {err}")
+                );
+                notifier.send_remove();
+                Err(err)
+            }
+        }
+    }
+
+    /// ### Exports a MultiMC instance as a zip that MultiMC and Prism Launcher can import
+    /// `excludes` are names of folders in the minecraft directory that get left out, like `saves` or `logs`.
+    pub async fn export(instance: &SimpleInstance, target: &Path, excludes: &[&str], notifier: &Notifier) -> IResult<()> {
+        if instance.instance_type != InstanceType::MultiMC {
+            Err(InstanceGatherError::InvalidPack(instance.instance_path.clone(), "Only MultiMC instances can be exported as MultiMC zips".to_string()))?;
+        }
+
+        let notifier = notifier.make_new();
+        notifier.send_msg(&format!("Exporting '{}'...", instance.name));
+
+        let (instance_path, target_path) = (instance.instance_path.clone(), target.to_path_buf());
+        let excluded_paths: Vec<PathBuf> = excludes.iter().chain(&[MOD_BACKUP_FOLDER, WORLD_BACKUP_FOLDER]).map(|ex| {
+            instance.minecraft_path.strip_prefix(&instance.instance_path).unwrap_or(Path::new(".minecraft")).join(ex)
+        }).collect();
+
+        task::spawn_blocking(move || {
+            let files: Vec<PathBuf> = list_files_relative(&instance_path, &[META_FILE_NAME]).map_err(
+                |err| InstanceGatherError::FileReadFailed(instance_path.clone(), err)
+            )?
+            .into_iter()
+            .filter(|file| !excluded_paths.iter().any(|ex| file.starts_with(ex)))
+            .collect();
+
+            let file = File::create(&target_path).map_err(
+                |err| InstanceGatherError::FileWriteFailed(target_path.clone(), err)
+            )?;
+            let mut writer = ZipWriter::new(file);
+
+            zip_files(&mut writer, &instance_path, &files, "")
+            .and_then(|_| writer.finish().map(|_| ()))
+            .map_err(|err| InstanceGatherError::ArchiveWriteFailed(target_path, err))
+        }).await.unwrap()?;
+
+        notifier.send_success(&format!("Exported '{}' to {}", instance.name, target.to_string_lossy()));
+        Ok(())
+    }
+
+    /// ### Writes the play time of the last session to `instance.cfg` and the play count to our metadata
+    /// The config is edited line by line, so keys we don't know about are kept.
+    pub async fn save_play_session(instance: &SimpleInstance) -> IResult<()> {
+        let path = instance.instance_path.join("instance.cfg");
+        let instance_file = fs::read_to_string(&path).await.map_err(
+            |err| InstanceGatherError::FileReadFailed(path.clone(), err)
+        )?;
+
+        let mut values: Vec<(&str, String)> = [
+            ("lastLaunchTime", instance.last_played.map(|time| time.timestamp_millis())),
+            ("lastTimePlayed", instance.last_played_for.map(|time| time.num_seconds())),
+            ("totalTimePlayed", instance.total_time_played.map(|time| time.num_seconds()))
+        ].into_iter().filter_map(|(key, value)| Some((key, value?.to_string()))).collect();
+
+        let mut lines: Vec<String> = instance_file.lines().map(|line| {
+            let index = line.split_once('=').and_then(
+                |(key, _)| values.iter().position(|(name, _)| *name == key.trim())
+            );
+
+            match index {
+                Some(index) => {
+                    let (key, value) = values.remove(index);
+                    format!("{key}={value}")
+                },
+                None => line.to_string()
+            }
+        }).collect();
+
+        // Keys that didn't exist yet go right below the section header, if there is one
+        let insert_at = lines.iter().position(|line| line.trim() == "[General]").map_or(lines.len(), |index| index + 1);
+        lines.splice(insert_at..insert_at, values.into_iter().map(|(key, value)| format!("{key}={value}")));
+
+        fs::write(&path, lines.join("\n") + "\n").await.map_err(
+            |err| InstanceGatherError::FileWriteFailed(path, err)
+        )?;
+
+        let mut meta = MMCMetadata::get(&instance.instance_path).await?;
+        meta.play_count = instance.play_count;
+        meta.save(&instance.instance_path).await
+    }
+
     pub fn get_icon(&self, settings: Arc<YetaLauncher>) -> Option<String> {
         let internal_icons = [
             "default", "bee", "brick", "chicken", "creeper", "diamond", "dirt", "enderman", "enderpearl", "flame", "fox", "gear", "herobrine",
@@ -79,8 +229,12 @@ impl MMCPack {
             |err| InstanceGatherError::FileReadFailed(path.clone(), err)
         )?;
 
-        serde_json::from_slice(&pack_file).map_err(
-            |err| InstanceGatherError::ParseFailedJson(InstanceType::MultiMC, path, err)
+        Self::parse(&pack_file, &path)
+    }
+
+    fn parse(pack_file: &[u8], path: &Path) -> IResult<Self> {
+        serde_json::from_slice(pack_file).map_err(
+            |err| InstanceGatherError::ParseFailedJson(InstanceType::MultiMC, path.to_path_buf(), err)
         )
     }
 }
@@ -113,18 +267,21 @@ impl MMCMetadata {
         }
     }
 
-    async fn generate(instance_path: &Path) -> IResult<Self> {
-        let path = instance_path.join(META_FILE_NAME);
-
+    pub async fn generate(instance_path: &Path) -> IResult<Self> {
         let meta = MMCMetadata {
             instance_id: fastrand::u32(..),
             play_count: None
         };
 
-        fs::write(&path, serde_json::to_string_pretty(&meta).unwrap(/* this cannot fail */)).await.map_err(
-            |err| InstanceGatherError::FileWriteFailed(path, err)
-        )?;
-
+        meta.save(instance_path).await?;
         Ok(meta)
     }
+
+    pub async fn save(&self, instance_path: &Path) -> IResult<()> {
+        let path = instance_path.join(META_FILE_NAME);
+
+        fs::write(&path, serde_json::to_string_pretty(self).unwrap(/* this cannot fail */)).await.map_err(
+            |err| InstanceGatherError::FileWriteFailed(path, err)
+        )
+    }
 }
\ No newline at end of file
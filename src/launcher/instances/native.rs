@@ -0,0 +1,111 @@
+use std::{path::{Path, PathBuf}, sync::Arc};
+
+use log::*;
+use serde::{Serialize, Deserialize};
+use tokio::fs;
+
+use crate::{app::consts::NATIVE_FILE_NAME, launcher::modloaders::ModLoaders, YetaLauncher};
+
+use super::{errors::InstanceGatherError, get_free_instance_path, IResult, InstanceType, ModLoader, SimpleInstance};
+
+
+// Handling our own instance format ("yeta-instance.json" file)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NativeConfig {
+    pub instance_id: u32,
+    pub name: String,
+    pub icon: Option<String>,
+    pub mc_version: String,
+    pub modloader: ModLoader,
+    pub last_played: Option<i64>,
+    pub last_played_for: Option<i64>,
+    pub total_time_played: Option<i64>,
+    pub play_count: Option<i32>
+}
+
+impl NativeConfig {
+    pub fn new(name: &str, mc_version: &str, loader: ModLoaders, loader_version: &str) -> Self {
+        Self {
+            instance_id: fastrand::u32(..),
+            name: name.to_string(),
+            icon: None,
+            mc_version: mc_version.to_string(),
+            modloader: ModLoader {
+                name: loader.to_string(),
+                typ: loader,
+                version: if let ModLoaders::Vanilla = loader { String::new() } else { loader_version.trim().to_string() }
+            },
+            last_played: None,
+            last_played_for: None,
+            total_time_played: None,
+            play_count: None
+        }
+    }
+
+    pub async fn get(instance_path: &Path) -> IResult<Self> {
+        let path = instance_path.join(NATIVE_FILE_NAME);
+        let config_file = fs::read(&path).await.map_err(
+            |err| InstanceGatherError::FileReadFailed(path.clone(), err)
+        )?;
+
+        serde_json::from_slice(&config_file).map_err(
+            |err| InstanceGatherError::ParseFailedJson(InstanceType::Native, path, err)
+        )
+    }
+
+    pub async fn save(&self, instance_path: &Path) -> IResult<()> {
+        let path = instance_path.join(NATIVE_FILE_NAME);
+
+        fs::write(&path, serde_json::to_string_pretty(self).unwrap(/* this cannot fail */)).await.map_err(
+            |err| InstanceGatherError::FileWriteFailed(path, err)
+        )
+    }
+
+    /// ### Creates a new instance folder in the instance directory and writes the config to it
+    /// The folder name is derived from the instance name and made unique if it's already taken.
+    pub async fn create(self, app: Arc<YetaLauncher>) -> IResult<SimpleInstance> {
+        if self.name.trim().is_empty() {
+            Err(InstanceGatherError::InvalidName(self.name.to_string()))?;
+        }
+        if self.mc_version.is_empty() {
+            Err(InstanceGatherError::VersionUnset)?;
+        }
+        if self.modloader.typ != ModLoaders::Vanilla && self.modloader.version.trim().is_empty() {
+            Err(InstanceGatherError::LoaderVersionUnset(self.modloader.typ))?;
+        }
+
+        let dir = app.settings.read().unwrap().instance_path.clone().ok_or(InstanceGatherError::PathUnset)?;
+        let instance_path = get_free_instance_path(&PathBuf::from(dir), &self.name);
+        info!("Creating new instance '{}' at {instance_path:?}", self.name);
+
+        fs::create_dir_all(instance_path.join("minecraft")).await.map_err(
+            |err| InstanceGatherError::FileWriteFailed(instance_path.join("minecraft"), err)
+        )?;
+        self.save(&instance_path).await?;
+
+        SimpleInstance::get_from_native(&instance_path, app).await
+    }
+
+    /// Writes the play time of the last session to the config
+    pub async fn save_play_session(instance: &SimpleInstance) -> IResult<()> {
+        let mut config = Self::get(&instance.instance_path).await?;
+
+        config.last_played = instance.last_played.map(|time| time.timestamp_millis());
+        config.last_played_for = instance.last_played_for.map(|time| time.num_seconds());
+        config.total_time_played = instance.total_time_played.map(|time| time.num_seconds());
+        config.play_count = instance.play_count;
+
+        config.save(&instance.instance_path).await
+    }
+
+    pub fn get_icon(&self, app: Arc<YetaLauncher>) -> Option<String> {
+        let icon = self.icon.as_ref()?;
+
+        if Path::new(icon).is_absolute() {
+            Some(icon.to_string())
+        } else {
+            let icon_path = app.settings.read().unwrap().icon_path.clone()?;
+            Some(Path::new(&icon_path).join(icon).to_string_lossy().to_string())
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::{path::{Path, PathBuf}, time::SystemTime};
+
+use log::*;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::app::{consts::CRASH_RULES_FILE_NAME, utils::get_config_dir};
+
+
+/// Written to the config folder on first use, so the rules can be extended there
+const DEFAULT_CRASH_RULES: &str = include_str!("../../../resources/crash_rules.json");
+
+/// A known cause of crashes, recognized by any of its patterns appearing in a crash report or log
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CrashRule {
+    pub name: String,
+    pub patterns: Vec<String>,
+    pub diagnosis: String
+}
+
+#[derive(Debug, Clone)]
+pub struct CrashReport {
+    /// The file that is opened to show the full report
+    pub path: PathBuf,
+    pub description: Option<String>,
+    pub suspected_mods: Vec<String>,
+    pub matched_rules: Vec<CrashRule>
+}
+
+
+impl CrashRule {
+    /// ### Reads the crash rules from the config folder
+    /// The default rules are written there first if the file doesn't exist yet.
+    pub async fn get_all() -> Vec<Self> {
+        let path = get_config_dir().join(CRASH_RULES_FILE_NAME);
+
+        if !path.is_file() {
+            debug!("Generating crash rules at {path:?}");
+            fs::write(&path, DEFAULT_CRASH_RULES).await.unwrap_or_else(
+                |err| warn!("Failed to write crash rules to {path:?}: {err}")
+            );
+        }
+
+        let rules = fs::read_to_string(&path).await.unwrap_or_else(|_| DEFAULT_CRASH_RULES.to_string());
+        serde_json::from_str(&rules).unwrap_or_else(|err| {
+            warn!("Failed to parse crash rules at {path:?}: {err}, using the defaults");
+            serde_json::from_str(DEFAULT_CRASH_RULES).unwrap(/* the defaults are valid */)
+        })
+    }
+
+    fn matches(&self, text: &str) -> bool {
+        self.patterns.iter().any(|pattern| text.contains(pattern.as_str()))
+    }
+}
+
+impl CrashReport {
+    /// ### Looks for a crash report and logs that were written since the launch started
+    /// The newest file in `crash-reports/` is preferred for display, followed by `logs/latest.log` and the launcher's own log of the launch.
+    /// All of them are searched for known crash causes, as some crashes (like a wrong Java version) happen before Minecraft writes anything.
+    pub async fn find(minecraft_path: &Path, since: SystemTime, launch_log: Option<&Path>) -> Option<Self> {
+        let crash_report = get_newest_since(&minecraft_path.join("crash-reports"), since).await;
+        let latest_log = Some(minecraft_path.join("logs").join("latest.log"))
+            .filter(|path| is_modified_since(path, since));
+
+        let files: Vec<PathBuf> = [crash_report.clone(), latest_log, launch_log.map(Path::to_path_buf)].into_iter().flatten().collect();
+        let path = files.first()?.clone();
+
+        let mut text = String::new();
+        for file in &files {
+            match fs::read(file).await {
+                Ok(contents) => text.push_str(&String::from_utf8_lossy(&contents)),
+                Err(err) => warn!("Failed to read {file:?} for crash analysis: {err}")
+            }
+        }
+
+        // Only a real crash report has a trustworthy description and list of suspects
+        let report_text = if crash_report.is_some() { text.as_str() } else { "" };
+        let report = Self {
+            description: parse_description(report_text),
+            suspected_mods: parse_suspected_mods(report_text),
+            matched_rules: CrashRule::get_all().await.into_iter().filter(|rule| rule.matches(&text)).collect(),
+            path
+        };
+
+        info!("Analyzed crash at {:?}: {report:?}", report.path);
+        Some(report)
+    }
+
+    /// Summarizes the report in a few sentences for the crash notification
+    pub fn get_summary(&self) -> String {
+        let mut parts = Vec::new();
+
+        if let Some(description) = &self.description {
+            parts.push(format!("\"{description}\""));
+        }
+        if !self.suspected_mods.is_empty() {
+            parts.push(format!("Suspected mods: {}", self.suspected_mods.join(", ")));
+        }
+        for rule in &self.matched_rules {
+            parts.push(format!("{}: {}", rule.name, rule.diagnosis));
+        }
+
+        if parts.is_empty() {
+            "No known cause was found.".to_string()
+        } else {
+            parts.join("\n")
+        }
+    }
+}
+
+
+async fn get_newest_since(folder: &Path, since: SystemTime) -> Option<PathBuf> {
+    let mut entries = fs::read_dir(folder).await.ok()?;
+    let mut newest: Option<(SystemTime, PathBuf)> = None;
+
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let Some(modified) = entry.metadata().await.ok().and_then(|meta| meta.modified().ok()) else {
+            continue;
+        };
+
+        if modified >= since && newest.as_ref().is_none_or(|(time, _)| modified > *time) {
+            newest = Some((modified, entry.path()));
+        }
+    }
+
+    newest.map(|(_, path)| path)
+}
+
+fn is_modified_since(path: &Path, since: SystemTime) -> bool {
+    path.metadata().and_then(|meta| meta.modified()).is_ok_and(|modified| modified >= since)
+}
+
+fn parse_description(report: &str) -> Option<String> {
+    report.lines()
+    .find_map(|line| line.trim().strip_prefix("Description:"))
+    .map(|description| description.trim().to_string())
+    .filter(|description| !description.is_empty())
+}
+
+/// ### Reads the "Suspected Mods" section that Forge and NeoForge add to crash reports
+/// The mods are either listed after the colon or on the following lines, indented by a single tab.
+fn parse_suspected_mods(report: &str) -> Vec<String> {
+    let mut lines = report.lines().skip_while(|line| !line.trim_start().starts_with("Suspected Mod"));
+    let Some((_, inline)) = lines.next().and_then(|line| line.split_once(':')) else {
+        return Vec::new();
+    };
+
+    let inline = inline.trim();
+    if inline.eq_ignore_ascii_case("NONE") {
+        return Vec::new();
+    }
+
+    let get_name = |entry: &str| entry.split(", Version").next().unwrap_or(entry).trim().to_string();
+    if !inline.is_empty() {
+        return vec![get_name(inline)];
+    }
+
+    lines
+    .take_while(|line| line.starts_with('\t') || line.starts_with(' '))
+    // Deeper indented lines are details of the mod above them
+    .filter(|line| !line.starts_with("\t\t") && !line.starts_with("    "))
+    .map(get_name)
+    .filter(|name| !name.is_empty())
+    .collect()
+}
@@ -0,0 +1,260 @@
+use std::path::PathBuf;
+
+use chrono::{DateTime, Local};
+use log::*;
+use tokio::{fs::{self, File}, io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader, BufWriter}, sync::mpsc::{UnboundedReceiver, UnboundedSender}};
+
+use crate::SlLogRecord;
+
+
+/// The folder inside `logs` that holds one file per launch
+const LAUNCH_LOG_FOLDER: &str = "launcher";
+/// Older records are dropped from memory, but stay in the log file
+pub const MAX_LOG_RECORDS: usize = 50_000;
+/// Only the newest matching records are shown in the logs tab
+pub const MAX_SHOWN_LOG_RECORDS: usize = 2_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal
+}
+
+/// A single line (or multi-line event) of game output
+#[derive(Debug, Clone)]
+pub struct LogRecord {
+    pub time: DateTime<Local>,
+    pub level: LogLevel,
+    pub logger: String,
+    pub thread: String,
+    pub message: String
+}
+
+/// Collects the lines of Log4j XML events, which span multiple lines, and turns them into records
+#[derive(Debug, Default)]
+pub struct LogParser {
+    event: Option<String>
+}
+
+
+impl LogLevel {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_uppercase().as_str() {
+            "TRACE" => Some(LogLevel::Trace),
+            "DEBUG" => Some(LogLevel::Debug),
+            "INFO" => Some(LogLevel::Info),
+            "WARN" | "WARNING" => Some(LogLevel::Warn),
+            "ERROR" | "SEVERE" => Some(LogLevel::Error),
+            "FATAL" => Some(LogLevel::Fatal),
+            _ => None
+        }
+    }
+
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            LogLevel::Trace => "TRACE",
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Info => "INFO",
+            LogLevel::Warn => "WARN",
+            LogLevel::Error => "ERROR",
+            LogLevel::Fatal => "FATAL"
+        }
+    }
+
+    /// The minimum level of each entry of the level filter in the logs tab
+    pub fn from_filter_index(index: i32) -> Self {
+        match index {
+            1 => LogLevel::Debug,
+            2 => LogLevel::Info,
+            3 => LogLevel::Warn,
+            4 => LogLevel::Error,
+            _ => LogLevel::Trace
+        }
+    }
+}
+
+impl LogRecord {
+    /// ### Turns a line of output that isn't part of an XML event into a record
+    /// Lines in the usual `[time] [thread/LEVEL]: message` format keep their thread and level.
+    fn from_plain(line: &str, default_level: LogLevel) -> Self {
+        let mut record = Self {
+            time: Local::now(),
+            level: default_level,
+            logger: String::new(),
+            thread: String::new(),
+            message: line.to_string()
+        };
+
+        let parsed = line.split_once("]: ").and_then(|(head, message)| {
+            let (thread, level) = head.rsplit_once('[')?.1.rsplit_once('/')?;
+            Some((thread, LogLevel::from_name(level)?, message))
+        });
+
+        if let Some((thread, level, message)) = parsed {
+            record.thread = thread.to_string();
+            record.level = level;
+            record.message = message.to_string();
+        }
+
+        record
+    }
+
+    /// Parses a complete `<log4j:Event>` element as written by Log4j's XML layout
+    fn from_event(xml: &str) -> Option<Self> {
+        // The "log4j" prefix is never declared in the output, so it has to be declared around it
+        let wrapped = format!("<events xmlns:log4j=\"http://logging.apache.org/log4j/\">{xml}</events>");
+        let doc = roxmltree::Document::parse(&wrapped).map_err(
+            |err| debug!("Failed to parse log event: {err}")
+        ).ok()?;
+
+        let event = doc.root_element().children().find(|node| node.has_tag_name("Event"))?;
+        let get_text = |name: &str| {
+            event.children().find(|node| node.has_tag_name(name)).map(|node| {
+                node.descendants().filter_map(|desc| desc.text()).collect::<String>()
+            })
+        };
+
+        let mut message = get_text("Message").unwrap_or_default();
+        if let Some(throwable) = get_text("Throwable") {
+            message = format!("{message}\n{}", throwable.trim_end());
+        }
+
+        Some(Self {
+            time: event.attribute("timestamp")
+                .and_then(|time| time.parse().ok())
+                .and_then(DateTime::from_timestamp_millis)
+                .map_or(Local::now(), |time| time.with_timezone(&Local)),
+            level: event.attribute("level").and_then(LogLevel::from_name).unwrap_or(LogLevel::Info),
+            logger: event.attribute("logger").unwrap_or_default().to_string(),
+            thread: event.attribute("thread").unwrap_or_default().to_string(),
+            message
+        })
+    }
+
+    /// Checks the record against the filter of the logs tab. `search` has to be lowercase.
+    pub fn matches(&self, min_level: LogLevel, search: &str) -> bool {
+        self.level >= min_level && (
+            search.is_empty()
+            || self.message.to_lowercase().contains(search)
+            || self.logger.to_lowercase().contains(search)
+            || self.thread.to_lowercase().contains(search)
+        )
+    }
+
+    /// Formats the record like Minecraft's own `latest.log`
+    pub fn to_line(&self) -> String {
+        let source = if self.thread.is_empty() { self.level.to_str().to_string() } else { format!("{}/{}", self.thread, self.level.to_str()) };
+        let logger = if self.logger.is_empty() { String::new() } else { format!(" [{}]", self.logger) };
+
+        format!("[{}] [{source}]{logger}: {}", self.time.format("%H:%M:%S"), self.message)
+    }
+
+    pub fn to_slint(&self) -> SlLogRecord {
+        SlLogRecord {
+            time: self.time.format("%H:%M:%S").to_string().into(),
+            level: self.level.to_str().into(),
+            logger: self.logger.to_string().into(),
+            thread: self.thread.to_string().into(),
+            message: self.message.to_string().into()
+        }
+    }
+}
+
+impl LogParser {
+    /// ### Feeds a line of output into the parser
+    /// Returns a record once an event is complete. Lines outside of events are returned right away.
+    pub fn push_line(&mut self, line: &str, default_level: LogLevel) -> Option<LogRecord> {
+        if let Some(event) = &mut self.event {
+            event.push_str(line);
+            event.push('\n');
+
+            if line.contains("</log4j:Event>") {
+                let event = self.event.take().unwrap_or_default();
+                return LogRecord::from_event(&event).or_else(|| Some(LogRecord::from_plain(event.trim_end(), default_level)));
+            }
+            return None;
+        }
+
+        if line.trim_start().starts_with("<log4j:Event") {
+            self.event = Some(String::new());
+            return self.push_line(line, default_level);
+        }
+
+        if line.trim().is_empty() {
+            None
+        } else {
+            Some(LogRecord::from_plain(line, default_level))
+        }
+    }
+}
+
+
+/// ### Reads the output of the game line by line and sends every parsed record
+/// Returns once the stream is closed, which happens when the game exits.
+pub async fn read_output<R: AsyncRead + Unpin>(output: R, default_level: LogLevel, sender: UnboundedSender<LogRecord>) {
+    let mut reader = BufReader::new(output);
+    let mut parser = LogParser::default();
+    let mut buf = Vec::new();
+
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf).await {
+            Ok(0) => break,
+            Ok(_) => {
+                // Java doesn't always write UTF-8, especially on Windows
+                let line = String::from_utf8_lossy(&buf);
+                if let Some(record) = parser.push_line(line.trim_end_matches(['\r', '\n']), default_level) {
+                    sender.send(record).ok();
+                }
+            },
+            Err(err) => {
+                warn!("Failed to read game output: {err}");
+                break;
+            }
+        }
+    }
+}
+
+/// ### Writes all received records into a log file and forwards them to `forward`
+/// Returns the path of the file once all senders are gone.
+pub async fn write_launch_log(minecraft_path: PathBuf, mut receiver: UnboundedReceiver<LogRecord>, forward: UnboundedSender<LogRecord>) -> Option<PathBuf> {
+    let folder = minecraft_path.join("logs").join(LAUNCH_LOG_FOLDER);
+    let path = folder.join(format!("{}.log", Local::now().format("%Y-%m-%d_%H-%M-%S")));
+
+    let file = match fs::create_dir_all(&folder).await {
+        Ok(()) => File::create(&path).await.map_err(
+            |err| error!("Failed to create launch log at {path:?}: {err}")
+        ).ok(),
+        Err(err) => {
+            error!("Failed to create log folder at {folder:?}: {err}");
+            None
+        }
+    };
+    let mut writer = file.map(BufWriter::new);
+
+    while let Some(record) = receiver.recv().await {
+        if let Some(file) = &mut writer {
+            let mut result = file.write_all(format!("{}\n", record.to_line()).as_bytes()).await;
+
+            // Only flush once the game is quiet, as it can log thousands of lines at once
+            if result.is_ok() && receiver.is_empty() {
+                result = file.flush().await;
+            }
+            if let Err(err) = result {
+                error!("Failed to write to launch log at {path:?}: {err}");
+                writer = None;
+            }
+        }
+
+        forward.send(record).ok();
+    }
+
+    if let Some(file) = &mut writer {
+        file.flush().await.ok();
+    }
+    writer.map(|_| path)
+}
@@ -4,7 +4,7 @@ use chrono::DateTime;
 use log::{*};
 use reqwest::Client;
 
-use crate::{app::{consts::MINECRAFT_VERSION_URL, downloader::Downloader, notifier::Notifier, utils::{download_file_checked, get_assets_dir, get_classpath_separator, get_client_jar_dir, get_log4j_dir}}, launcher::modloaders::{fabric::FabricLibrary, LoaderManifests}, slint_generatedMainWindow::SlMCVersionDetails};
+use crate::{app::{consts::MINECRAFT_VERSION_URL, downloader::{DownloadErr, Downloader}, notifier::Notifier, utils::{download_file_checked, get_assets_dir, get_classpath_separator, get_client_jar_dir, get_log4j_dir}}, launcher::modloaders::{fabric::FabricLibrary, forge::ForgeLibrary, launchwrapper::LAUNCHWRAPPER_MAIN_CLASS, LoaderManifests}, slint_generatedMainWindow::SlMCVersionDetails};
 
 use super::mc_structs::*;
 
@@ -133,7 +133,7 @@ impl MCVersionManifest {
         final_args
     }
 
-    pub async fn get_classpath(&self, natives_path: &Path, client: &Client, notifier: Notifier) -> String {
+    pub async fn get_classpath(&self, natives_path: &Path, client: &Client, notifier: Notifier) -> Result<String, DownloadErr> {
         let separator = get_classpath_separator();
         let mut downloader = Downloader::new(notifier, 8);
 
@@ -150,9 +150,9 @@ impl MCVersionManifest {
             }
         }
 
-        downloader.download_all(true, "Minecraft libraries").await;
+        downloader.download_all(true, "Minecraft libraries").await?;
 
-        libraries.iter()
+        Ok(libraries.iter()
             .inspect(|&lib| if lib.extract.is_some() {
                 lib.extract_natives(natives_path).unwrap();
             })
@@ -162,7 +162,7 @@ impl MCVersionManifest {
                 self.get_client_jar(client).await.to_string_lossy().to_string()
             ))
             .collect::<Vec<String>>()
-            .join(&separator)
+            .join(&separator))
     }
 
     pub fn get_main_class(&self) -> String {
@@ -193,7 +193,7 @@ impl MCVersionManifest {
         } else { None }
     }
 
-    pub async fn get_client_assets(&self, client: &Client, notifier: Notifier) -> String {
+    pub async fn get_client_assets(&self, client: &Client, notifier: Notifier) -> Result<String, DownloadErr> {
         let assets_dir = get_assets_dir();
         let index_path = &assets_dir.join("indexes").join(format!("{}.json", &self.asset_index.id));
 
@@ -217,10 +217,14 @@ impl MCVersionManifest {
                 downloader.add_from(path, url, None, Some(asset.1.size));
             }
 
-            downloader.download_all(false, "Minecraft assets").await;
+            if let Err(err) = downloader.download_all(false, "Minecraft assets").await {
+                // The assets are only checked while the index is missing, so they are checked again on the next launch
+                fs::remove_file(index_path).ok();
+                return Err(err);
+            }
         }
 
-        assets_dir.to_string_lossy().to_string()
+        Ok(assets_dir.to_string_lossy().to_string())
     }
 
     pub fn merge_with(&mut self, other: LoaderManifests) {
@@ -241,6 +245,26 @@ impl MCVersionManifest {
                     .collect()
                 )
             },
+            LoaderManifests::LaunchWrapper(wrapper) => {
+                let tweak_args = wrapper.get_tweak_args();
+                self.main_class = wrapper.main_class.unwrap_or(LAUNCHWRAPPER_MAIN_CLASS.to_string());
+
+                if let Some(args) = &mut self.arguments {
+                    args.game.extend(tweak_args.into_iter().map(MCGameArg::GameArg));
+                } else if let Some(mc_args) = &mut self.minecraft_arguments {
+                    mc_args.push(' ');
+                    mc_args.push_str(&tweak_args.join(" "));
+                }
+
+                // Versions that already run on the launchwrapper have it and its dependencies in their libraries
+                let get_artifact = |name: &str| name.rsplit_once(':').map_or(name.to_string(), |(artifact, _)| artifact.to_string());
+                for lib in wrapper.libraries.into_iter().map(ForgeLibrary::to_vanilla) {
+                    let artifact = get_artifact(&lib.name);
+                    if !self.libraries.iter().any(|existing| get_artifact(&existing.name) == artifact) {
+                        self.libraries.push(lib);
+                    }
+                }
+            },
             LoaderManifests::Forge(mut forge) => {
                 self.id = forge.id;
                 self.main_class = forge.main_class;
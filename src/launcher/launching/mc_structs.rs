@@ -63,6 +63,8 @@ pub struct MCVersionManifest {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MCArguments {
     pub game: Vec<MCGameArg>,
+    /// Missing in Quilt's profiles
+    #[serde(default)]
     pub jvm: Vec<MCJvmArg>
 }
 
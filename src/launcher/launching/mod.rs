@@ -1,16 +1,21 @@
-use std::{path::Path, sync::Arc};
+use std::{io, path::Path, process::{ExitStatus, Stdio}, sync::Arc, time::{Duration, SystemTime}};
 
+use chrono::Utc;
 use log::{*};
 use reqwest::Client;
-use tokio::{fs, process::Command};
+use tokio::{fs, process::{Child, Command}, sync::mpsc::{unbounded_channel, UnboundedSender}, time::{interval_at, Instant}};
+use tokio_util::sync::CancellationToken;
 
-use crate::{app::{notifier::Notifier, utils::{get_classpath_separator, get_library_dir}}, launcher::{authentication::auth_structs::Accounts, launching::mc_structs::*}, YetaLauncher};
+use crate::{app::{notifier::Notifier, utils::{get_classpath_separator, get_library_dir}}, launcher::{authentication::auth_structs::Accounts, launching::{crashes::CrashReport, logs::{read_output, write_launch_log, LogLevel, LogRecord}, mc_structs::*, processes::{terminate, RunningInstance}}, worlds::backups::{BackupSettings, BackupTarget, WorldBackup}}, YetaLauncher};
 
-use super::{authentication::auth_structs::MCAccount, instances::SimpleInstance, java::JavaDetails};
+use super::{authentication::auth_structs::Account, instances::SimpleInstance, java::JavaDetails};
 
+pub mod crashes;
 pub mod libraries;
+pub mod logs;
 pub mod manifests;
 pub mod mc_structs;
+pub mod processes;
 
 #[derive(Debug)]
 struct Args {
@@ -20,8 +25,17 @@ struct Args {
 }
 
 
+impl Args {
+    /// The access token in the arguments is redacted by the logger, as it is registered as a secret once received
+    fn log(&self, custom_args: &str) {
+        debug!("Args: {:#?}\nCustom Args: {}", self, custom_args);
+    }
+}
+
 impl SimpleInstance {
-    pub async fn launch(app: Arc<YetaLauncher>, instance_id: i32, notifier: &mut Notifier) -> Result<(), String> {
+    /// ### Launches the instance and waits for the game to exit
+    /// The output of the game is parsed into records, which are written to a log file in the instance and sent to `log_sender`.
+    pub async fn launch(app: Arc<YetaLauncher>, instance_id: i32, notifier: &mut Notifier, log_sender: UnboundedSender<LogRecord>) -> Result<(), String> {
         let instance = app.instances.read().unwrap().as_ref().map(|instances| {
             instances.iter().find(|&inst| inst.id == instance_id as u32).unwrap().clone()
         }).unwrap();
@@ -43,13 +57,35 @@ impl SimpleInstance {
         notifier.send_msg(&format!("Launching {name}..."));
 
 
+        let stop = app.running.read().unwrap().get(id).map(RunningInstance::get_stop_token).unwrap_or_default();
+
         let client = Client::new();
         let java = instance.get_java(app.clone(), &client).await?;
         let args = instance.get_arguments(&java, app.clone(), &client, notifier).await?;
         let additional_args = java.get_args();
     
-        debug!("Args: {:#?}\nCustom Args: {}", args, additional_args);
+        args.log(&additional_args);
+
+        let backup_settings = app.settings.read().unwrap().backup_settings.clone();
+        if backup_settings.before_launch && minecraft_path.join("saves").is_dir() {
+            notifier.send_msg("Backing up worlds...");
+            if let Err(err) = WorldBackup::create(minecraft_path, BackupTarget::Saves, &backup_settings).await {
+                warn!("Pre-launch world backup failed: {err}");
+                notifier.make_new().send_error(&format!("Failed to back up the worlds of {name}: {err}"));
+            }
+        }
+
+        // Killed while the game was still being prepared
+        if stop.is_cancelled() {
+            info!("Launch of {minecraft_path:?} was stopped before the game started");
+            notifier.set_progress(0, 0);
+            notifier.send_success(&format!("{name} was stopped."));
+            return Ok(());
+        }
+
         info!("Launching NOW!");
+        let launch_time = SystemTime::now();
+        let started = Utc::now();
 
         let mut process = Command::new(&java.path)
         .current_dir(minecraft_path)
@@ -57,29 +93,111 @@ impl SimpleInstance {
         .args(args.jvm)
         .arg(args.main_class)
         .args(args.game)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
         .spawn()
         .map_err(|err| format!("Failed to run Minecraft command: {err}"))?;
-    
+
+        if let Some(running) = app.running.write().unwrap().get_mut(id) {
+            running.pid = process.id();
+        }
+
+        let (record_sender, record_receiver) = unbounded_channel();
+        if let Some(stdout) = process.stdout.take() {
+            tokio::spawn(read_output(stdout, LogLevel::Info, record_sender.clone()));
+        }
+        if let Some(stderr) = process.stderr.take() {
+            tokio::spawn(read_output(stderr, LogLevel::Error, record_sender));
+        }
+        let log_task = tokio::spawn(write_launch_log(minecraft_path.to_path_buf(), record_receiver, log_sender));
 
         notifier.set_progress(0, 0);
         notifier.send_success("Instance launched successfully!");
     
-        let exit_status = process.wait().await.expect("Failed to wait on Java process! How did this happen?");
-        info!("Exited with status: {}", exit_status);
+        // Also fails if a kill request couldn't stop the game, which must not skip saving the session below
+        let exit_status = Self::wait_for_exit(&mut process, minecraft_path, &backup_settings, notifier, &stop).await;
+        match &exit_status {
+            Ok(status) => info!("Exited with status: {status}"),
+            Err(err) => error!("Failed to wait on the game process of {name}: {err}")
+        }
+
+        let mut played = instance.clone();
+        match played.save_play_session(started, Utc::now()).await {
+            Ok(()) => if let Some(instances) = app.instances.write().unwrap().as_mut() {
+                if let Some(existing) = instances.iter_mut().find(|inst| inst.id == played.id) {
+                    *existing = played;
+                }
+            },
+            Err(err) => error!("Failed to save play time of {name}: {err}")
+        }
+
+        let log_path = log_task.await.ok().flatten();
+        if let Some(log_path) = &log_path {
+            info!("Game output was written to {log_path:?}");
+        }
     
-        if exit_status.success() {
+        let exit_status = match exit_status {
+            Ok(status) => status,
+            Err(err) if stop.is_cancelled() => {
+                notifier.make_new().send_error(&format!("Failed to stop {name}: {err}"));
+                return Ok(());
+            },
+            Err(err) => {
+                notifier.make_new().send_error(&format!("Lost track of the game process of {name}: {err}"));
+                return Ok(());
+            }
+        };
+
+        if stop.is_cancelled() {
+            info!("{minecraft_path:?} was stopped with status {}", exit_status);
+            notifier.make_new().send_success(&format!("{name} was stopped."));
+        } else if exit_status.success() {
             info!("{minecraft_path:?} exited successfully.");
             notifier.make_new().send_success(&format!("{name} exited successfully."));
         } else {
             warn!("{minecraft_path:?} exited (crashed) with status {}", exit_status);
-            notifier.make_new().send_error(
-                &format!("{name} crashed with code {}", exit_status.code().map_or("None".to_string(), |c| c.to_string())
-            ));
+            let message = format!("{name} crashed with code {}", exit_status.code().map_or("None".to_string(), |c| c.to_string()));
+
+            match CrashReport::find(minecraft_path, launch_time, log_path.as_deref()).await {
+                Some(report) => notifier.make_new().send_error_with_link(
+                    &format!("{message}.\n{}", report.get_summary()),
+                    &report.path.to_string_lossy()
+                ),
+                None => notifier.make_new().send_error(&message)
+            }
         }
 
         Ok(())
     }
     
+    /// ### Waits for the game to exit, or stops it once `stop` is cancelled
+    /// If a backup interval is set, all worlds are backed up periodically in the meantime.
+    /// Minecraft keeps writing to the worlds while they are copied, so these backups are taken on a best effort basis.
+    async fn wait_for_exit(process: &mut Child, minecraft_path: &Path, settings: &BackupSettings, notifier: &Notifier, stop: &CancellationToken) -> io::Result<ExitStatus> {
+        let mut interval = settings.interval_minutes.map(|minutes| {
+            let period = Duration::from_secs(minutes as u64 * 60);
+            interval_at(Instant::now() + period, period)
+        });
+
+        loop {
+            tokio::select! {
+                status = process.wait() => return status,
+                _ = stop.cancelled() => return terminate(process).await,
+                Some(_) = async { Some(interval.as_mut()?.tick().await) } => {
+                    if !minecraft_path.join("saves").is_dir() {
+                        continue;
+                    }
+
+                    debug!("Taking scheduled world backup of {minecraft_path:?}");
+                    if let Err(err) = WorldBackup::create(minecraft_path, BackupTarget::Saves, settings).await {
+                        warn!("Scheduled world backup failed: {err}");
+                        notifier.make_new().send_error(&format!("Scheduled world backup failed: {err}"));
+                    }
+                }
+            }
+        }
+    }
+
     async fn get_arguments(&self, java: &JavaDetails, app: Arc<YetaLauncher>, client: &Client, notifier: &mut Notifier) -> Result<Args, String> {
         let loader = self.modloader.typ;
     
@@ -87,7 +205,7 @@ impl SimpleInstance {
         info!("Preparing account...");
         let account = Accounts::get_account_from_app(app, client, false)
         .await
-        .ok_or("Could not get the selected account!".to_string())?;
+        .map_err(|err| format!("Could not prepare the selected account: {err}"))?;
 
 
         notifier.send_progress(&format!("Getting version details for {}...", self.mc_version), 3);
@@ -112,7 +230,7 @@ impl SimpleInstance {
     
 
         notifier.send_progress("Getting the modloader manifest...", 6);
-        if let Some(mf) = loader.get_manifest(&self.mc_version, &self.modloader.version, client, notifier.make_new()).await {
+        if let Some(mf) = loader.get_manifest(&self.mc_version, &self.modloader.version, &self.instance_path, client, notifier.make_new()).await? {
             info!("Merging with manifest of {loader} Loader...");
             version.merge_with(mf)
         }
@@ -121,46 +239,52 @@ impl SimpleInstance {
 
 
         notifier.send_progress("Preparing the modloader...", 7);
-        loader.prepare_launch(&self.mc_version, &self.modloader.version, client, &java.path, notifier.make_new()).await;
+        loader.prepare_launch(&self.mc_version, &self.modloader.version, client, &java.path, notifier.make_new()).await?;
     
         info!("Beginning argument parsing...");
         notifier.send_progress("Preparing the game...", 8);
-        Ok(
-            Self::parse_arguments(
-                Args {
-                    jvm: version.get_jvm_args(client).await,
-                    game: version.get_game_args(),
-                    main_class: version.get_main_class()
-                },
-                &account,
-                version,
-                &self.minecraft_path,
-                client,
-                notifier
-            ).await
-        )
+        let natives_path = self.minecraft_path.join("natives");
+        let classpath = version.get_classpath(&natives_path, client, notifier.clone()).await.map_err(
+            |err| format!("Failed to download the Minecraft libraries: {err:?}")
+        )?;
+        let assets_root = version.get_client_assets(client, notifier.clone()).await.map_err(
+            |err| format!("Failed to download the Minecraft assets: {err:?}")
+        )?;
+
+        Ok(Self::parse_arguments(
+            Args {
+                jvm: version.get_jvm_args(client).await,
+                game: version.get_game_args(),
+                main_class: version.get_main_class()
+            },
+            &account,
+            &version,
+            &self.minecraft_path,
+            classpath,
+            assets_root
+        ))
     }
     
-    async fn parse_arguments(args_struct: Args, account: &MCAccount, version: MCVersionManifest, minecraft_path: &Path, client: &Client, notifier: &mut Notifier) -> Args {
+    fn parse_arguments(args_struct: Args, account: &Account, version: &MCVersionManifest, minecraft_path: &Path, classpath: String, assets_root: String) -> Args {
         let natives_path = minecraft_path.join("natives");
 
         let replacements = [
-            ("${auth_player_name}", account.mc_profile.name.to_string()),
-            ("${auth_uuid}", account.mc_profile.id.to_string()),
-            ("${auth_access_token}", account.mc_response.access_token.to_string()),
+            ("${auth_player_name}", account.get_name().to_string()),
+            ("${auth_uuid}", account.get_uuid().to_string()),
+            ("${auth_access_token}", account.get_access_token().to_string()),
             ("${user_properties}", stringify!({"": []}).to_string()),
     
-            ("${classpath}", version.get_classpath(&natives_path, client, notifier.clone()).await),
-            ("${assets_root}", version.get_client_assets(client, notifier.clone()).await),
+            ("${classpath}", classpath),
+            ("${assets_root}", assets_root),
             ("${version_name}", version.id.replace([' ', ':'], "_")),
-            ("${assets_index_name}", version.asset_index.id),
-            ("${version_type}", version.typ),
+            ("${assets_index_name}", version.asset_index.id.to_string()),
+            ("${version_type}", version.typ.to_string()),
     
             ("${natives_directory}", natives_path.to_string_lossy().to_string()),
             ("${launcher_name}", "yetalauncher".to_string()),
             ("${launcher_version}", "323".to_string()),
             ("${game_directory}", minecraft_path.to_string_lossy().to_string()),
-            ("${user_type}", "msa".to_string()),
+            ("${user_type}", account.get_user_type().to_string()),
             ("${resolution_width}", 1200.to_string()),
             ("${resolution_height}", 800.to_string()),
     
@@ -214,4 +338,79 @@ impl SimpleInstance {
         .cloned()
         .ok_or_else(|| "Could not find Java to use for this version in the settings!".to_string())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{app::redaction::{capture_logs, captured_logs}, launcher::authentication::auth_structs::{MCAccount, MCProfile, MCResponse2, MSAResponse2, XBLDisplayClaims, XBLResponse, XBLXUIClaims}};
+
+    fn xbl_response(token: &str, uhs: &str) -> XBLResponse {
+        XBLResponse {
+            issue_instant: Utc::now(),
+            not_after: Utc::now(),
+            token: token.to_string(),
+            display_claims: XBLDisplayClaims { xui: vec![XBLXUIClaims { uhs: uhs.to_string() }] }
+        }
+    }
+
+    #[test]
+    fn logged_arguments_hide_the_access_token() {
+        capture_logs();
+
+        let account = MCAccount {
+            msa_response: MSAResponse2 {
+                token_type: "bearer".to_string(),
+                expires_at: Utc::now(),
+                scope: "XboxLive.signin".to_string(),
+                access_token: "args-msa-access-5e1c".to_string(),
+                refresh_token: "args-msa-refresh-5e1c".to_string(),
+                user_id: "user".to_string()
+            },
+            xbl_response: xbl_response("args-xbl-token-5e1c", "args-uhs-5e1c"),
+            xsts_response: xbl_response("args-xsts-token-5e1c", "args-uhs-5e1c"),
+            mc_response: MCResponse2 {
+                access_token: "args-mc-access-5e1c".to_string(),
+                expires_at: Utc::now(),
+                username: "args-player".to_string(),
+                token_type: "Bearer".to_string()
+            },
+            mc_profile: MCProfile { id: "0a1b2c".to_string(), name: "ArgsPlayer".to_string(), skins: Vec::new(), capes: Vec::new() },
+            needs_relogin: false
+        };
+        // Happens when the accounts are read or a token is received
+        account.add_secrets();
+
+        let version: MCVersionManifest = serde_json::from_value(json!({
+            "assetIndex": { "id": "17", "url": "", "size": 0, "totalSize": 0, "sha1": "" },
+            "assets": "17",
+            "complianceLevel": 1,
+            "downloads": { "client": { "url": "", "size": 0, "sha1": "" }, "server": { "url": "", "size": 0, "sha1": "" } },
+            "id": "1.21",
+            "javaVersion": { "component": "java-runtime-delta", "majorVersion": 21 },
+            "libraries": [],
+            "mainClass": "net.minecraft.client.main.Main",
+            "minimumLauncherVersion": 21,
+            "releaseTime": "2024-06-13T08:24:03+00:00",
+            "time": "2024-06-13T08:24:03+00:00",
+            "type": "release"
+        })).unwrap();
+
+        let args = Args {
+            jvm: vec!["-cp".to_string(), "${classpath}".to_string()],
+            game: ["--username", "${auth_player_name}", "--accessToken", "${auth_access_token}", "--version", "${version_name}"].map(String::from).to_vec(),
+            main_class: version.get_main_class()
+        };
+        let args = SimpleInstance::parse_arguments(args, &Account::Microsoft(account), &version, Path::new("instance"), "client.jar".to_string(), "assets".to_string());
+        assert!(args.game.contains(&"args-mc-access-5e1c".to_string()));
+
+        args.log("");
+
+        let logged = captured_logs().into_iter().find(|record| record.starts_with("Args:") && record.contains("ArgsPlayer")).unwrap();
+        assert!(logged.contains("<redacted>"));
+        assert!(!logged.contains("args-mc-access-5e1c"));
+    }
+}
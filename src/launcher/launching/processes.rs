@@ -0,0 +1,134 @@
+use std::{io, process::ExitStatus, time::Duration};
+
+use chrono::{DateTime, Utc};
+use log::*;
+use tokio::process::{Child, Command};
+use tokio_util::sync::CancellationToken;
+
+
+/// How long the game gets to save and exit after being asked to, before it is killed
+#[cfg(unix)]
+const KILL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// ### A game process that was started by the launcher
+/// The child handle itself stays with the launch task, which waits on it and stops it once `stop` is cancelled.
+#[derive(Debug, Clone)]
+pub struct RunningInstance {
+    /// Only known once the process was spawned
+    pub pid: Option<u32>,
+    pub started: DateTime<Utc>,
+    /// The name of the account the game was launched with
+    pub account: Option<String>,
+    stop: CancellationToken
+}
+
+
+impl RunningInstance {
+    pub fn new(account: Option<String>) -> Self {
+        Self {
+            pid: None,
+            started: Utc::now(),
+            account,
+            stop: CancellationToken::new()
+        }
+    }
+
+    /// Asks the launch task to stop the game
+    pub fn kill(&self) {
+        info!("Stopping game process {:?} (started at {}, account {:?})", self.pid, self.started, self.account);
+        self.stop.cancel();
+    }
+
+    pub fn get_stop_token(&self) -> CancellationToken {
+        self.stop.clone()
+    }
+}
+
+/// ### Removes an instance from the running ones once it is dropped
+/// Also runs when the launch task panics, so an instance can't stay marked as running.
+pub struct RunningGuard<F: FnOnce()> {
+    on_drop: Option<F>
+}
+
+impl<F: FnOnce()> RunningGuard<F> {
+    pub fn new(on_drop: F) -> Self {
+        Self { on_drop: Some(on_drop) }
+    }
+}
+
+impl<F: FnOnce()> Drop for RunningGuard<F> {
+    fn drop(&mut self) {
+        if let Some(on_drop) = self.on_drop.take() {
+            on_drop();
+        }
+    }
+}
+
+
+/// ### Brings the window of the game process to the front
+/// Uses the tools each system ships with, except for Linux where `xdotool` has to be installed. Wayland doesn't allow this at all.
+pub async fn focus_window(pid: u32) -> io::Result<()> {
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("powershell");
+        command.args(["-NoProfile", "-Command", &format!("(New-Object -ComObject WScript.Shell).AppActivate({pid})")]);
+        command
+    } else if cfg!(target_os = "macos") {
+        let mut command = Command::new("osascript");
+        command.args(["-e", &format!("tell application \"System Events\" to set frontmost of (first process whose unix id is {pid}) to true")]);
+        command
+    } else {
+        let mut command = Command::new("xdotool");
+        command.args(["search", "--pid", &pid.to_string(), "windowactivate"]);
+        command
+    };
+
+    debug!("Focusing the window of game process {pid}");
+    let status = command.status().await?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("The focus command exited with {status}")))
+    }
+}
+
+/// ### Asks the game to exit and kills it if it doesn't within [`KILL_TIMEOUT`]
+/// Windows has no way of asking a process to exit, so the game is killed right away there.
+pub async fn terminate(process: &mut Child) -> io::Result<ExitStatus> {
+    #[cfg(unix)]
+    if let Some(pid) = process.id() {
+        use nix::{sys::signal::{kill, Signal}, unistd::Pid};
+
+        debug!("Sending SIGTERM to game process {pid}");
+        match kill(Pid::from_raw(pid as i32), Signal::SIGTERM) {
+            Ok(()) => if let Ok(status) = tokio::time::timeout(KILL_TIMEOUT, process.wait()).await {
+                return status;
+            },
+            Err(err) => warn!("Failed to send SIGTERM to game process {pid}: {err}")
+        }
+    }
+
+    warn!("Killing game process {:?}", process.id());
+    process.kill().await?;
+    process.wait().await
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::{panic, sync::atomic::{AtomicBool, Ordering}};
+
+    use super::*;
+
+    #[test]
+    fn guard_runs_when_the_launch_panics() {
+        static REMOVED: AtomicBool = AtomicBool::new(false);
+
+        let result = panic::catch_unwind(|| {
+            let _guard = RunningGuard::new(|| REMOVED.store(true, Ordering::SeqCst));
+            panic!("Launch failed");
+        });
+
+        assert!(result.is_err());
+        assert!(REMOVED.load(Ordering::SeqCst));
+    }
+}
@@ -2,4 +2,8 @@ pub mod java;
 pub mod launching;
 pub mod authentication;
 pub mod instances;
-pub mod modloaders;
\ No newline at end of file
+pub mod modloaders;
+pub mod mods;
+pub mod packs;
+pub mod worlds;
+pub mod modrinth_api;
\ No newline at end of file
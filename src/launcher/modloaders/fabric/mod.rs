@@ -2,7 +2,7 @@ use log::{info, error};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 
-use crate::{app::utils::maven_identifier_to_path, launcher::launching::mc_structs::{MCArguments, MCLibrary}};
+use crate::{app::utils::maven_identifier_to_path, launcher::{launching::mc_structs::{MCArguments, MCLibrary}, modloaders::ModLoaders}};
 
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -28,18 +28,32 @@ pub struct FabricLibrary {
 }
 
 impl FabricVersionManifest {
-    pub async fn get(mc_ver: &str, fabric_loader_ver: &str, client: &Client) -> Option<Self> {
-        let url = format!("https://meta.fabricmc.net/v2/versions/loader/{mc_ver}/{fabric_loader_ver}/profile/json");
-        info!("Getting Fabric version manifest from {url}...");
+    /// ### Gets the launch profile of a Fabric or Quilt loader version
+    /// Quilt's meta serves its profiles in the same format as Fabric's, including the libraries.
+    pub async fn get(loader: ModLoaders, mc_ver: &str, loader_ver: &str, client: &Client) -> Option<Self> {
+        let url = format!("{}/versions/loader/{mc_ver}/{loader_ver}/profile/json", Self::get_meta_url(loader));
+        info!("Getting {loader} version manifest from {url}...");
 
-        match client.get(url).send().await.unwrap().json::<Self>().await {
+        let response = match client.get(url).send().await {
+            Ok(response) => response.json::<Self>().await,
+            Err(e) => Err(e)
+        };
+
+        match response {
             Ok(manifest) => Some(manifest),
             Err(e) => {
-                error!("Failed to get fabric version manifest: {}", e);
+                error!("Failed to get {loader} version manifest: {}", e);
                 None
             }
         }
     }
+
+    fn get_meta_url(loader: ModLoaders) -> &'static str {
+        match loader {
+            ModLoaders::Quilt => "https://meta.quiltmc.org/v3",
+            _ => "https://meta.fabricmc.net/v2"
+        }
+    }
 }
 
 impl FabricLibrary {
@@ -5,9 +5,9 @@ use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use tokio::fs;
 
-use crate::{app::{downloader::Downloader, notifier::Notifier, utils::{get_installer_extracts_dir, get_library_dir, maven_identifier_to_path}}, launcher::launching::mc_structs::MCLibrary};
+use crate::{app::{downloader::{DResult, Downloader}, notifier::Notifier, utils::{get_library_dir, maven_identifier_to_path}}, launcher::launching::mc_structs::MCLibrary};
 
-use super::{installer::{ForgeInstaller, ForgeProcessor, Side}, legacy_installer::LegacyInstallProfile};
+use super::{installer::{ForgeInstaller, ForgeProcessor, Side}, legacy_installer::LegacyInstallProfile, ForgeBuild};
 
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -37,24 +37,30 @@ pub struct ForgeMappings {
 
 
 impl ForgeInstallProfile {
-    pub async fn get(mc_ver: &str, forge_ver: &str, client: &Client, notifier: &mut Notifier) -> Option<Self> {
-        let path = Self::get_path(mc_ver, forge_ver);
+    /// ### Reads the install profile, extracting it from the installer first if needed
+    /// Returns `None` if the installer has no install profile.
+    pub async fn get(build: &ForgeBuild, client: &Client, notifier: &mut Notifier) -> Result<Option<Self>, String> {
+        let path = Self::get_path(build);
         if !path.exists() {
-            ForgeInstaller::extract_needed(mc_ver, forge_ver, client, notifier).await;
+            ForgeInstaller::extract_needed(build, client, notifier).await?;
         }
 
-        let install_profile = fs::read_to_string(path).await.ok()?;
-        Some(serde_json::from_str(&install_profile).unwrap())
+        let Ok(install_profile) = fs::read_to_string(path).await else {
+            return Ok(None);
+        };
+        serde_json::from_str(&install_profile)
+        .map(Some)
+        .map_err(|err| format!("Failed to parse the {} install profile: {err}", build.loader))
     }
 
 
-    pub fn get_path(mc_ver: &str, forge_ver: &str) -> PathBuf {
-        get_installer_extracts_dir(mc_ver, forge_ver).join("install_profile.json")
+    pub fn get_path(build: &ForgeBuild) -> PathBuf {
+        build.get_extracts_dir().join("install_profile.json")
     }
 }
 
 impl ModernInstallProfile {
-    pub async fn process(&self, side: Side, java_path: &str, notifier: &mut Notifier) {
+    pub async fn process(&self, build: &ForgeBuild, side: Side, java_path: &str, notifier: &mut Notifier) -> Result<(), String> {
         let length = self.processors.len();
 
         let mut notifier = notifier.make_new();
@@ -64,14 +70,15 @@ impl ModernInstallProfile {
             notifier.set_progress((i + 1) as u32, length as u32);
             notifier.send_msg(&format!("Running task: {}", proc.get_task()));
 
-            proc.run(&side, self, java_path).await;
+            proc.run(&side, self, build, java_path).await?;
         }
 
         notifier.set_progress(0, 0);
-        notifier.send_success("Finished running processors")
+        notifier.send_success("Finished running processors");
+        Ok(())
     }
 
-    pub async fn download_libraries(&mut self, notifier: Notifier) {
+    pub async fn download_libraries(&mut self, notifier: Notifier) -> DResult {
         info!("Downloading installer libraries...");
 
         let mut downloader = Downloader::new(notifier, 8);
@@ -91,7 +98,7 @@ impl ModernInstallProfile {
             }
         }
 
-        downloader.download_all(true, "Forge libraries").await;
+        downloader.download_all(true, "Forge libraries").await
     }
 }
 
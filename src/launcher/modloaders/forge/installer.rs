@@ -7,79 +7,83 @@ use reqwest::{Client, StatusCode};
 use serde::{Serialize, Deserialize};
 use tokio::process::Command;
 
-use crate::{app::{downloader::{Download, DownloadErr}, notifier::Notifier, utils::*}, launcher::modloaders::forge::{legacy_installer::LegacyInstallerManifest, ForgeVersionManifest}};
+use crate::{app::{downloader::{Download, DownloadErr}, notifier::Notifier, utils::*}, launcher::modloaders::forge::{legacy_installer::LegacyInstallerManifest, ForgeBuild, ForgeVersionManifest}};
 
 use super::install_profile::{ForgeInstallProfile, ModernInstallProfile};
 
 pub struct ForgeInstaller;
 
 impl ForgeInstaller {
-    async fn download(mc_ver: &str, forge_ver: &str, client: &Client, notifier: &mut Notifier) -> Result<PathBuf, DownloadErr> {
-        info!("Downloading Forge installer for {mc_ver}-{forge_ver}...");
-        let path = get_forge_cache_dir().join(format!("forge-{mc_ver}-{forge_ver}-installer.jar"));
+    async fn download(build: &ForgeBuild, client: &Client, notifier: &mut Notifier) -> Result<PathBuf, DownloadErr> {
+        info!("Downloading {} installer for {}-{}...", build.loader, build.mc_ver, build.loader_ver);
+        let path = build.get_installer_path();
 
         let download = Download::new(
             path.clone(),
-            &format!("https://maven.minecraftforge.net/net/minecraftforge/forge/{mc_ver}-{forge_ver}/forge-{mc_ver}-{forge_ver}-installer.jar"),
+            &build.get_installer_url(),
             None,
             None
         ).download(client, notifier).await.map(|_| path.clone());
 
-        if let Err(DownloadErr::Response(StatusCode::NOT_FOUND)) = download {
-            let fallback_url = format!("https://maven.minecraftforge.net/net/minecraftforge/forge/{mc_ver}-{forge_ver}-{mc_ver}/forge-{mc_ver}-{forge_ver}-{mc_ver}-installer.jar");
-            debug!("Falling back to {fallback_url}");
-            
-            Download::new(
-                path.clone(),
-                &fallback_url,
-                None,
-                None
-            ).download(client, notifier).await.map(|_| path)
-        } else {
-            download
+        match (download, build.get_fallback_installer_url()) {
+            (Err(DownloadErr::Response(StatusCode::NOT_FOUND)), Some(fallback_url)) => {
+                debug!("Falling back to {fallback_url}");
+
+                Download::new(
+                    path.clone(),
+                    &fallback_url,
+                    None,
+                    None
+                ).download(client, notifier).await.map(|_| path)
+            },
+            (download, _) => download
         }
     }
 
-    pub async fn prepare_jar(mc_ver: &str, forge_ver: &str, client: &Client, java_path: &str, notifier: &mut Notifier) {
-        let path = get_library_dir()
-        .join("net/minecraftforge/forge")
-        .join(format!("{mc_ver}-{forge_ver}"))
-        .join(format!("forge-{mc_ver}-{forge_ver}-client.jar"));
+    pub async fn prepare_jar(build: &ForgeBuild, client: &Client, java_path: &str, notifier: &mut Notifier) -> Result<(), String> {
+        let path = build.get_client_jar_path();
 
         if !path.is_file() {
             notifier.set_progress(1, 5);
 
-            if let Some(install_profile) = ForgeInstallProfile::get(mc_ver, forge_ver, client, notifier).await {
+            if let Some(install_profile) = ForgeInstallProfile::get(build, client, notifier).await? {
                 match install_profile {
                     ForgeInstallProfile::Modern(mut profile) => {
                         notifier.send_progress("Downloading installer libraries...", 3);
-                        profile.download_libraries(notifier.make_new()).await;
+                        profile.download_libraries(notifier.make_new()).await.map_err(
+                            |err| format!("Failed to download the {} installer libraries: {err:?}", build.loader)
+                        )?;
             
                         notifier.send_progress("Running installer processors...", 4);
-                        profile.process(Side::Client, java_path, notifier).await;
+                        profile.process(build, Side::Client, java_path, notifier).await?;
             
                         notifier.set_progress(0, 0);
-                        notifier.send_success("Successfully installed forge");
+                        notifier.send_success(&format!("Successfully installed {}", build.loader));
                     },
                     ForgeInstallProfile::Legacy(_) => {
                         debug!("Legacy install profile found, no further actions required");
 
                         notifier.set_progress(0, 0);
-                        notifier.send_success("Successfully installed forge");
+                        notifier.send_success(&format!("Successfully installed {}", build.loader));
                     },
                 }
 
             }
         }
+
+        Ok(())
     }
 
-    /// ### Downloads the Forge installer and extracts the manifest and the install_profile from it
-    /// Target location: `forge-{mc_ver}-{forge_ver}-[installer.jar/manifest.json/install_profile.json]` in the forge cache dir
-    pub async fn extract_needed(mc_ver: &str, forge_ver: &str, client: &Client, notifier: &mut Notifier) {
-        notifier.send_progress("Downloading Forge installer...", 1);
-        let installer = Self::download(mc_ver, forge_ver, client, &mut notifier.make_new()).await.expect("Failed to download Forge installer!");
+    /// ### Downloads the installer and extracts the manifest, the install_profile and the processor data from it
+    /// Target location: the extracts dir of the build in the forge cache dir
+    pub async fn extract_needed(build: &ForgeBuild, client: &Client, notifier: &mut Notifier) -> Result<(), String> {
+        notifier.send_progress(&format!("Downloading {} installer...", build.loader), 1);
+        let installer = Self::download(build, client, &mut notifier.make_new()).await.map_err(|err| match err {
+            DownloadErr::Response(StatusCode::NOT_FOUND) => format!("{} {} does not exist for Minecraft {}", build.loader, build.loader_ver, build.mc_ver),
+            err => format!("Failed to download the {} installer: {err:?}", build.loader)
+        })?;
 
-        notifier.send_progress("Extracting Forge installer...", 2);
+        notifier.send_progress(&format!("Extracting {} installer...", build.loader), 2);
         debug!("Extracting installer jar...");
         let jar = jars::jar(
             installer, 
@@ -87,21 +91,24 @@ impl ForgeInstaller {
             .targets(&vec!["version.json", "install_profile.json", "data"])
             .ext("jar")
             .build()
-        ).expect("Failed to extract Forge installer jar!");
+        ).map_err(|err| format!("Failed to extract the {} installer: {err}", build.loader))?;
 
         let legacy_installer = !jar.files.iter().any(|(name, _)| *name == "version.json"); // For versions 1.6 to 1.9
+        let extracts_dir = build.get_extracts_dir();
 
         for (f_path, f_contents) in jar.files.iter().filter(|(_, contents)| !contents.is_empty()) {
             let is_legacy = legacy_installer && f_path == "install_profile.json";
 
             if is_legacy {
                 info!("Detected legacy Forge installer! Running workarounds...");
-                let legacy_manifest: LegacyInstallerManifest = serde_json::from_slice(f_contents).expect("Failed to parse legacy installer manifest!");
+                let legacy_manifest: LegacyInstallerManifest = serde_json::from_slice(f_contents)
+                .map_err(|err| format!("Failed to parse the legacy installer manifest: {err}"))?;
 
-                let version_manifest_path = ForgeVersionManifest::get_path(mc_ver, forge_ver);
-                let install_profile_path = ForgeInstallProfile::get_path(mc_ver, forge_ver);
+                let version_manifest_path = ForgeVersionManifest::get_path(build);
+                let install_profile_path = ForgeInstallProfile::get_path(build);
 
-                legacy_manifest.install_profile.copy_libraries(&jar.files).await;
+                legacy_manifest.install_profile.copy_libraries(&jar.files).await
+                .map_err(|err| format!("Failed to extract the {} library: {err}", build.loader))?;
 
                 create_dir_parents(&version_manifest_path).await;
                 create_dir_parents(&install_profile_path).await;
@@ -109,16 +116,21 @@ impl ForgeInstaller {
                 fs::write(
                     version_manifest_path,
                     serde_json::to_string_pretty(&legacy_manifest.version_manifest).unwrap()
-                ).await.expect("Failed to write manifest to file!");
+                ).await.map_err(|err| format!("Failed to write the {} version manifest: {err}", build.loader))?;
                 fs::write(
                     install_profile_path,
                     serde_json::to_string_pretty(&legacy_manifest.install_profile).unwrap()
-                ).await.expect("Failed to write install profile to file!");
+                ).await.map_err(|err| format!("Failed to write the {} install profile: {err}", build.loader))?;
 
             } else {
-                fs::write(f_path, f_contents).await.expect("Failed to write manifest to file!");
+                let output_path = extracts_dir.join(f_path);
+
+                create_dir_parents(&output_path).await;
+                fs::write(output_path, f_contents).await.map_err(|err| format!("Failed to extract {f_path} from the {} installer: {err}", build.loader))?;
             }
         }
+
+        Ok(())
     }
 }
 
@@ -134,10 +146,10 @@ pub struct ForgeProcessor {
 
 
 impl ForgeProcessor {
-    pub async fn run(&self, side: &Side, install_profile: &ModernInstallProfile, java_path: &str) {
+    pub async fn run(&self, side: &Side, install_profile: &ModernInstallProfile, build: &ForgeBuild, java_path: &str) -> Result<(), String> {
         let shouldrun = self.sides.is_none() || self.sides.as_ref().is_some_and(|s| s.contains(side));
 
-        if !shouldrun { return; }
+        if !shouldrun { return Ok(()); }
 
         info!("Starting processor {}...", self.jar);
 
@@ -148,7 +160,7 @@ impl ForgeProcessor {
         .collect::<Vec<String>>()
         .join(&get_classpath_separator());
 
-        let args = self.parse_args(install_profile, side);
+        let args = self.parse_args(install_profile, build, side)?;
 
         let main_class = get_jar_main_class(get_library_dir().join(maven_identifier_to_path(&self.jar)));
 
@@ -160,19 +172,20 @@ impl ForgeProcessor {
         //.arg("-jar").arg(get_library_dir().join(maven_identifier_to_path(&self.jar)))
         .args(args)
         .spawn()
-        .expect(&format!("Failed to run Processor {}", self.jar))
+        .map_err(|err| format!("Failed to run processor {}: {err}", self.jar))?
         .wait()
         .await
-        .expect(&format!("Failed to wait on Processor {} process", self.jar));
+        .map_err(|err| format!("Failed to wait on processor {}: {err}", self.jar))?;
 
         if process.success() {
-            info!("Processor exited successfully.")
+            info!("Processor exited successfully.");
+            Ok(())
         } else {
-            panic!("Processor crashed with code: {:?}", process.code())
+            Err(format!("Processor {} crashed with code: {:?}", self.jar, process.code()))
         }
     }
 
-    fn parse_args(&self, install_profile: &ModernInstallProfile, side: &Side) -> Vec<String> {
+    fn parse_args(&self, install_profile: &ModernInstallProfile, build: &ForgeBuild, side: &Side) -> Result<Vec<String>, String> {
         self.args.iter().map(|arg| {
             let mut final_arg = arg.to_string();
             if arg.starts_with("{") && arg.ends_with("}") {
@@ -181,7 +194,11 @@ impl ForgeProcessor {
                 final_arg = match key {
                     "SIDE" => format!("{:?}", side).to_lowercase(),
                     "MINECRAFT_JAR" => get_client_jar_dir().join(format!("{}.jar", &install_profile.minecraft)).to_string_lossy().to_string(),
-                    _ => install_profile.data.get(key).expect(&format!("Key {key} was not found in data!")).get_value(side)
+                    "MINECRAFT_VERSION" => install_profile.minecraft.to_string(),
+                    "ROOT" => get_data_dir().to_string_lossy().to_string(),
+                    "INSTALLER" => build.get_installer_path().to_string_lossy().to_string(),
+                    "LIBRARY_DIR" => get_library_dir().to_string_lossy().to_string(),
+                    _ => install_profile.data.get(key).ok_or(format!("Key {key} of processor {} was not found in data", self.jar))?.get_value(side)
                 };
             } else if arg.starts_with("[") && arg.ends_with("]") {
                 let identifier = &arg[1..arg.len()-1];
@@ -189,7 +206,7 @@ impl ForgeProcessor {
                 let path = get_library_dir().join(maven_identifier_to_path(identifier));
                 
                 if !path.is_file() {
-                    panic!("File at {:?} could not be found!", path)
+                    return Err(format!("File at {path:?} needed by processor {} could not be found", self.jar));
                 } else {
                     final_arg = path.to_string_lossy().to_string()
                 }
@@ -197,21 +214,17 @@ impl ForgeProcessor {
 
             let data_dir = get_data_dir().to_string_lossy().to_string();
             if final_arg.starts_with("/") && !final_arg.contains(data_dir.as_str()) {
-                let (mc_ver, forge_ver) = install_profile.version.split_once("-forge-").unwrap();
-                final_arg = get_installer_extracts_dir(mc_ver, forge_ver).join(&final_arg[1..]).to_string_lossy().to_string()
+                final_arg = build.get_extracts_dir().join(&final_arg[1..]).to_string_lossy().to_string()
             }
 
-            final_arg
+            Ok(final_arg)
         }).collect()
     }
 
     pub fn get_task(&self) -> &str {
-        let arg = &self.args[1];
-
-        if !arg.starts_with("{") && !arg.starts_with("[") && arg == &arg.to_uppercase() {
-            arg
-        } else {
-            &self.jar
+        match self.args.get(1) {
+            Some(arg) if !arg.starts_with("{") && !arg.starts_with("[") && arg == &arg.to_uppercase() => arg,
+            _ => &self.jar
         }
     }
 }
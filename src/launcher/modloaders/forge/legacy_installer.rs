@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, io};
 
 use log::info;
 use serde::{Deserialize, Serialize};
@@ -29,15 +29,17 @@ pub struct LegacyInstallProfile {
 }
 
 impl LegacyInstallProfile {
-    pub async fn copy_libraries(&self, files: &HashMap<String, Vec<u8>>) {
+    pub async fn copy_libraries(&self, files: &HashMap<String, Vec<u8>>) -> io::Result<()> {
         for (f_name, f_contents) in files {
             if f_name == &self.file_path {
                 let output_path = get_library_dir().join(maven_identifier_to_path(&self.path));
                 info!("Extracting Forge jar {f_name} to {output_path:?}...");
 
                 create_dir_parents(&output_path).await;
-                fs::write(output_path, f_contents).await.expect("Failed to write Forge library file!");
+                fs::write(output_path, f_contents).await?;
             }
         }
+
+        Ok(())
     }
 }
\ No newline at end of file
@@ -4,7 +4,7 @@ use tokio::fs;
 use reqwest::Client;
 use serde::{Serialize, Deserialize};
 
-use crate::{app::{consts::MINECRAFT_LIBRARY_URL, notifier::Notifier, utils::{get_installer_extracts_dir, maven_identifier_to_path}}, launcher::launching::mc_structs::{MCArguments, MCLibrary}};
+use crate::{app::{consts::MINECRAFT_LIBRARY_URL, notifier::Notifier, utils::{get_forge_cache_dir, get_installer_extracts_dir, get_library_dir, maven_identifier_to_path}}, launcher::{launching::mc_structs::{MCArguments, MCLibrary}, modloaders::ModLoaders}};
 
 use self::installer::ForgeInstaller;
 
@@ -13,6 +13,15 @@ pub mod install_profile;
 pub mod legacy_installer;
 
 
+/// ### A version of a loader that is installed with Forge's installer
+/// NeoForge forked Forge and kept its installer, so both only differ in where their files are hosted and how they are named.
+#[derive(Debug, Clone)]
+pub struct ForgeBuild {
+    pub loader: ModLoaders,
+    pub mc_ver: String,
+    pub loader_ver: String
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ForgeVersionManifest {
@@ -45,21 +54,84 @@ pub struct LegacyForgeLibrary {
     pub clientreq: Option<bool>
 }
 
+impl ForgeBuild {
+    pub fn new(loader: ModLoaders, mc_ver: &str, loader_ver: &str) -> Self {
+        Self {
+            loader,
+            mc_ver: mc_ver.to_string(),
+            loader_ver: loader_ver.to_string()
+        }
+    }
+
+    fn get_maven_url(&self) -> &'static str {
+        match self.loader {
+            ModLoaders::NeoForge => "https://maven.neoforged.net/releases",
+            _ => "https://maven.minecraftforge.net"
+        }
+    }
+
+    /// ### The maven group (as a path), artifact and version of the loader
+    /// NeoForge's builds for 1.20.1 are still named like Forge's, but versions after that drop the Minecraft version.
+    fn get_coordinates(&self) -> (&'static str, &'static str, String) {
+        let Self { mc_ver, loader_ver, .. } = self;
+
+        match self.loader {
+            ModLoaders::NeoForge if mc_ver == "1.20.1" => ("net/neoforged/forge", "forge", format!("{mc_ver}-{loader_ver}")),
+            ModLoaders::NeoForge => ("net/neoforged/neoforge", "neoforge", loader_ver.to_string()),
+            _ => ("net/minecraftforge/forge", "forge", format!("{mc_ver}-{loader_ver}"))
+        }
+    }
+
+    pub fn get_installer_url(&self) -> String {
+        let (group, artifact, version) = self.get_coordinates();
+        format!("{}/{group}/{version}/{artifact}-{version}-installer.jar", self.get_maven_url())
+    }
+
+    /// Some old Forge versions have the Minecraft version appended to their version
+    pub fn get_fallback_installer_url(&self) -> Option<String> {
+        if self.loader == ModLoaders::NeoForge {
+            return None;
+        }
+
+        let Self { mc_ver, loader_ver, .. } = self;
+        Some(format!("{}/net/minecraftforge/forge/{mc_ver}-{loader_ver}-{mc_ver}/forge-{mc_ver}-{loader_ver}-{mc_ver}-installer.jar", self.get_maven_url()))
+    }
+
+    /// The patched client jar, which only exists once the installer processors ran
+    pub fn get_client_jar_path(&self) -> PathBuf {
+        let (group, artifact, version) = self.get_coordinates();
+        get_library_dir().join(group).join(&version).join(format!("{artifact}-{version}-client.jar"))
+    }
+
+    /// Identifies the build in the forge cache dir, which is shared by both loaders
+    pub fn get_name(&self) -> String {
+        format!("{}-{}-{}", self.loader.to_string().to_lowercase(), self.mc_ver, self.loader_ver)
+    }
+
+    pub fn get_installer_path(&self) -> PathBuf {
+        get_forge_cache_dir().join(format!("{}-installer.jar", self.get_name()))
+    }
+
+    pub fn get_extracts_dir(&self) -> PathBuf {
+        get_installer_extracts_dir(&self.get_name())
+    }
+}
+
 impl ForgeVersionManifest {
-    pub async fn get(mc_ver: &str, forge_ver: &str, client: &Client, notifier: &mut Notifier) -> Option<Self> {
-        let path = Self::get_path(mc_ver, forge_ver);
+    pub async fn get(build: &ForgeBuild, client: &Client, notifier: &mut Notifier) -> Result<Self, String> {
+        let path = Self::get_path(build);
         if !path.exists() {
             notifier.set_progress(1, 2);
-            ForgeInstaller::extract_needed(mc_ver, forge_ver, client, notifier).await;
-            notifier.send_success("Got Forge version manifest");
+            ForgeInstaller::extract_needed(build, client, notifier).await?;
+            notifier.send_success(&format!("Got {} version manifest", build.loader));
         }
 
-        let manifest = fs::read_to_string(path).await.expect("Failed to read manifest file!?");
-        Some(serde_json::from_str(&manifest).expect("Failed to parse manifest file!?"))
+        let manifest = fs::read_to_string(path).await.map_err(|err| format!("Failed to read the {} version manifest: {err}", build.loader))?;
+        serde_json::from_str(&manifest).map_err(|err| format!("Failed to parse the {} version manifest: {err}", build.loader))
     }
 
-    pub fn get_path(mc_ver: &str, forge_ver: &str) -> PathBuf {
-        get_installer_extracts_dir(mc_ver, forge_ver).join("version.json")
+    pub fn get_path(build: &ForgeBuild) -> PathBuf {
+        build.get_extracts_dir().join("version.json")
     }
 }
 
@@ -0,0 +1,64 @@
+use std::path::Path;
+
+use log::{info, error, debug};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::launcher::modloaders::{forge::ForgeLibrary, ModLoaders};
+
+
+const MMC_META_URL: &str = "https://meta.multimc.org/v1";
+pub const LAUNCHWRAPPER_MAIN_CLASS: &str = "net.minecraft.launchwrapper.Launch";
+
+/// ### A component patch of a loader that runs on top of Mojang's launchwrapper, like LiteLoader and Rift
+/// Uses MultiMC's format, as these loaders never had a profile format of their own.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LaunchWrapperManifest {
+    pub main_class: Option<String>,
+    /// Older patches prefix lists with a `+` to add to those of the base version
+    #[serde(default, alias = "+tweakers")]
+    pub tweakers: Vec<String>,
+    #[serde(default, alias = "+libraries")]
+    pub libraries: Vec<ForgeLibrary>
+}
+
+impl LaunchWrapperManifest {
+    /// ### Gets the patch of the loader from the instance or from MultiMC's meta server
+    /// Loaders that were never on the meta server, like Rift, were added to instances as a patch in the `patches` folder.
+    pub async fn get(loader: ModLoaders, loader_ver: &str, instance_path: &Path, client: &Client) -> Option<Self> {
+        let uid = loader.to_uid()?;
+
+        let patch_path = instance_path.join("patches").join(format!("{uid}.json"));
+        if let Ok(patch) = fs::read_to_string(&patch_path).await {
+            debug!("Using {loader} patch of the instance at {patch_path:?}");
+
+            match serde_json::from_str(&patch) {
+                Ok(manifest) => return Some(manifest),
+                Err(e) => error!("Failed to parse {loader} patch at {patch_path:?}: {}", e)
+            }
+        }
+
+        let url = format!("{MMC_META_URL}/{uid}/{loader_ver}.json");
+        info!("Getting {loader} version manifest from {url}...");
+
+        let response = match client.get(url).send().await {
+            Ok(response) => response.json::<Self>().await,
+            Err(e) => Err(e)
+        };
+
+        match response {
+            Ok(manifest) => Some(manifest),
+            Err(e) => {
+                error!("Failed to get {loader} version manifest: {}", e);
+                None
+            }
+        }
+    }
+
+    /// The launch arguments that load the tweakers of the loader
+    pub fn get_tweak_args(&self) -> Vec<String> {
+        self.tweakers.iter().flat_map(|tweaker| ["--tweakClass".to_string(), tweaker.to_string()]).collect()
+    }
+}
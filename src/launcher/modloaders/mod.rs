@@ -1,4 +1,4 @@
-use std::fmt;
+use std::{fmt, path::Path};
 
 use log::*;
 use reqwest::Client;
@@ -6,49 +6,55 @@ use serde::{Deserialize, Serialize};
 
 use crate::app::notifier::Notifier;
 
-use self::{fabric::FabricVersionManifest, forge::ForgeVersionManifest, forge::installer::ForgeInstaller};
+use self::{fabric::FabricVersionManifest, forge::{ForgeBuild, ForgeVersionManifest}, forge::installer::ForgeInstaller, launchwrapper::LaunchWrapperManifest};
 
 pub mod fabric;
 pub mod forge;
+pub mod launchwrapper;
 
 pub enum LoaderManifests {
     Fabric(FabricVersionManifest),
-    Forge(ForgeVersionManifest)
+    Forge(ForgeVersionManifest),
+    LaunchWrapper(LaunchWrapperManifest)
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum ModLoaders {
     Vanilla,
     Forge,
     NeoForge,
     Fabric,
     Quilt,
-    LiteLoader, // not that important for now
-    Rift // neither is this one
+    LiteLoader,
+    Rift
 }
 
 impl ModLoaders {
-    pub async fn prepare_launch(&self, mc_ver: &str, loader_ver: &str, client: &Client, java_path: &str, mut notifier: Notifier) {
-        #[allow(clippy::single_match)]
+    pub async fn prepare_launch(&self, mc_ver: &str, loader_ver: &str, client: &Client, java_path: &str, mut notifier: Notifier) -> Result<(), String> {
         match self {
-            ModLoaders::Forge => {
-                info!("Preparing launch with Forge...");
-                ForgeInstaller::prepare_jar(mc_ver, loader_ver, client, java_path, &mut notifier).await;
+            ModLoaders::Forge | ModLoaders::NeoForge => {
+                info!("Preparing launch with {self}...");
+                ForgeInstaller::prepare_jar(&ForgeBuild::new(*self, mc_ver, loader_ver), client, java_path, &mut notifier).await
             },
-            _ => {}
+            _ => Ok(())
         }
     }
 
-    pub async fn get_manifest(&self, mc_ver: &str, loader_ver: &str, client: &Client, mut notifier: Notifier) -> Option<LoaderManifests> {
-        match self {
-            ModLoaders::Forge => ForgeVersionManifest::get(mc_ver, loader_ver, client, &mut notifier).await.map(LoaderManifests::Forge),
-            ModLoaders::Fabric => FabricVersionManifest::get(mc_ver, loader_ver, client).await.map(LoaderManifests::Fabric),
-            ModLoaders::Vanilla => None,
-            _ => {
-                error!("Currently unsupported modloader found: {self:?}, this will probably result in just Vanilla being launched!");
-                None
-            },
-        }
+    /// ### Gets the manifest the loader adds to the Minecraft version manifest
+    /// Vanilla has none, every other loader fails the launch without one.
+    pub async fn get_manifest(&self, mc_ver: &str, loader_ver: &str, instance_path: &Path, client: &Client, mut notifier: Notifier) -> Result<Option<LoaderManifests>, String> {
+        let manifest = match self {
+            ModLoaders::Forge | ModLoaders::NeoForge => LoaderManifests::Forge(ForgeVersionManifest::get(&ForgeBuild::new(*self, mc_ver, loader_ver), client, &mut notifier).await?),
+            ModLoaders::Fabric | ModLoaders::Quilt => FabricVersionManifest::get(*self, mc_ver, loader_ver, client).await.map(LoaderManifests::Fabric).ok_or(self.missing_manifest(mc_ver, loader_ver))?,
+            ModLoaders::LiteLoader | ModLoaders::Rift => LaunchWrapperManifest::get(*self, loader_ver, instance_path, client).await.map(LoaderManifests::LaunchWrapper).ok_or(self.missing_manifest(mc_ver, loader_ver))?,
+            ModLoaders::Vanilla => return Ok(None)
+        };
+
+        Ok(Some(manifest))
+    }
+
+    fn missing_manifest(&self, mc_ver: &str, loader_ver: &str) -> String {
+        format!("Could not get the {self} {loader_ver} manifest for Minecraft {mc_ver}!")
     }
 
     pub fn from_uid(mmc_uid: &str) -> Option<Self> {
@@ -57,6 +63,43 @@ impl ModLoaders {
         }).map(|v| v.1)
     }
     
+    pub fn to_uid(&self) -> Option<&'static str> {
+        MMC_LOADERS.iter().find(|&loader| {
+            loader.1 == *self
+        }).map(|v| v.0)
+    }
+    
+    pub fn from_name(name: &str) -> Option<Self> {
+        ALL_LOADERS.iter().find(|&loader| {
+            loader.to_string() == name
+        }).copied()
+    }
+
+    pub fn from_mrpack(dependency: &str) -> Option<Self> {
+        MRPACK_LOADERS.iter().find(|&loader| {
+            loader.0 == dependency
+        }).map(|v| v.1)
+    }
+
+    pub fn to_mrpack(&self) -> Option<&'static str> {
+        MRPACK_LOADERS.iter().find(|&loader| {
+            loader.1 == *self
+        }).map(|v| v.0)
+    }
+
+    /// The loader names Modrinth uses for filtering versions, Quilt can also load Fabric mods
+    pub fn to_modrinth(&self) -> Vec<&'static str> {
+        match self {
+            ModLoaders::Forge => vec!["forge"],
+            ModLoaders::NeoForge => vec!["neoforge"],
+            ModLoaders::Fabric => vec!["fabric"],
+            ModLoaders::Quilt => vec!["quilt", "fabric"],
+            ModLoaders::LiteLoader => vec!["liteloader"],
+            ModLoaders::Rift => vec!["rift"],
+            ModLoaders::Vanilla => Vec::new()
+        }
+    }
+
     pub fn from_cf(cf_name: &str) -> Option<Self> {
         if let Some(name) = cf_name.split('-').nth(0) {
             STRING_LOADERS.iter().find(|&loader| {
@@ -74,15 +117,33 @@ impl fmt::Display for ModLoaders {
     }
 }
 
-const MMC_LOADERS: [(&str, ModLoaders); 5] = [
+const ALL_LOADERS: [ModLoaders; 7] = [
+    ModLoaders::Vanilla,
+    ModLoaders::Forge,
+    ModLoaders::NeoForge,
+    ModLoaders::Fabric,
+    ModLoaders::Quilt,
+    ModLoaders::LiteLoader,
+    ModLoaders::Rift,
+];
+const MMC_LOADERS: [(&str, ModLoaders); 6] = [
     ("net.minecraftforge", ModLoaders::Forge),
     ("net.neoforged", ModLoaders::NeoForge),
     ("net.fabricmc.fabric-loader", ModLoaders::Fabric),
-    ("something", ModLoaders::Quilt),
-    ("something2", ModLoaders::LiteLoader),
+    ("org.quiltmc.quilt-loader", ModLoaders::Quilt),
+    ("com.mumfrey.liteloader", ModLoaders::LiteLoader),
+    ("org.dimdev.rift", ModLoaders::Rift),
+];
+const MRPACK_LOADERS: [(&str, ModLoaders); 4] = [
+    ("forge", ModLoaders::Forge),
+    ("neoforge", ModLoaders::NeoForge),
+    ("fabric-loader", ModLoaders::Fabric),
+    ("quilt-loader", ModLoaders::Quilt),
 ];
-const STRING_LOADERS: [(&str, ModLoaders); 2] = [
+const STRING_LOADERS: [(&str, ModLoaders); 4] = [
     ("forge", ModLoaders::Forge),
+    ("neoforge", ModLoaders::NeoForge),
     ("fabric", ModLoaders::Fabric),
+    ("quilt", ModLoaders::Quilt),
 ];
 
@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+
+use log::*;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MRVersion {
+    pub id: String,
+    pub project_id: String,
+    pub name: String,
+    pub version_number: String,
+    pub game_versions: Vec<String>,
+    pub loaders: Vec<String>,
+    pub files: Vec<MRVersionFile>
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MRVersionFile {
+    pub hashes: HashMap<String, String>,
+    pub url: String,
+    pub filename: String,
+    pub primary: bool,
+    pub size: u64
+}
+
+
+impl MRVersion {
+    /// ### Looks up the Modrinth versions the given sha1 hashes belong to
+    /// Hashes that are unknown to Modrinth are missing from the resulting map.
+    pub async fn from_hashes(hashes: &[String], api_url: &str, client: &Client) -> Option<HashMap<String, Self>> {
+        Self::post_hashes(&format!("{api_url}/version_files"), json!({
+            "hashes": hashes,
+            "algorithm": "sha1"
+        }), hashes.len(), client).await
+    }
+
+    /// ### Looks up the newest versions of the projects the given sha1 hashes belong to
+    /// Only versions that support one of the loaders and game versions are considered.
+    pub async fn get_updates(hashes: &[String], loaders: &[&str], game_versions: &[&str], api_url: &str, client: &Client) -> Option<HashMap<String, Self>> {
+        Self::post_hashes(&format!("{api_url}/version_files/update"), json!({
+            "hashes": hashes,
+            "algorithm": "sha1",
+            "loaders": loaders,
+            "game_versions": game_versions
+        }), hashes.len(), client).await
+    }
+
+    async fn post_hashes(url: &str, body: Value, count: usize, client: &Client) -> Option<HashMap<String, Self>> {
+        if count == 0 {
+            return Some(HashMap::new());
+        }
+
+        debug!("Looking up {count} file hash(es) at {url}...");
+
+        let response = client.post(url)
+        .json(&body)
+        .send()
+        .await;
+
+        match response.and_then(|res| res.error_for_status()) {
+            Ok(res) => res.json().await.map_err(
+                |err| error!("Failed to parse Modrinth version lookup: {err}")
+            ).ok(),
+            Err(err) => {
+                error!("Failed to look up file hashes on Modrinth: {err}");
+                None
+            }
+        }
+    }
+
+    pub fn get_primary_file(&self) -> Option<&MRVersionFile> {
+        self.files.iter().find(|file| file.primary).or(self.files.first())
+    }
+
+    pub fn get_file(&self, sha1: &str) -> Option<&MRVersionFile> {
+        self.files.iter().find(|file| file.hashes.get("sha1").is_some_and(|hash| hash == sha1))
+    }
+}
@@ -0,0 +1,411 @@
+use std::{cmp::Ordering, collections::HashMap, fmt::Display};
+
+use log::*;
+
+use crate::launcher::{instances::SimpleInstance, modloaders::ModLoaders};
+
+use super::ModInfo;
+
+
+/// Ids that mods depend on but that are never provided by a jar, and can't be checked without Java details
+const IGNORED_IDS: [&str; 5] = ["java", "javafml", "lowcodefml", "mclanguage", "fml"];
+
+#[derive(Debug, Clone)]
+pub struct ModDependency {
+    pub id: String,
+    pub kind: DependencyKind,
+    pub versions: VersionReq
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DependencyKind {
+    Required,
+    Breaks
+}
+
+#[derive(Debug, Clone)]
+pub enum VersionReq {
+    Any,
+    /// Fabric/Quilt style predicates like `>=1.2.3`, `~1.20` or `1.20.x`, any of which may match
+    Fabric(Vec<String>),
+    /// Maven style ranges like `[47,)` used by Forge and NeoForge
+    Maven(String)
+}
+
+#[derive(Debug, Clone)]
+pub enum ModIssue {
+    Missing { mod_name: String, dependency: String, versions: String },
+    WrongVersion { mod_name: String, dependency: String, versions: String, found: String },
+    Breaks { mod_name: String, other: String, found: String },
+    Duplicate { id: String, files: Vec<String> }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DependencyReport {
+    pub issues: Vec<ModIssue>
+}
+
+
+impl ModDependency {
+    pub fn new(id: String, kind: DependencyKind, versions: VersionReq) -> Self {
+        Self { id, kind, versions }
+    }
+}
+
+impl VersionReq {
+    pub fn matches(&self, version: &str) -> bool {
+        match self {
+            VersionReq::Any => true,
+            VersionReq::Fabric(predicates) => predicates.is_empty() || predicates.iter().any(
+                |predicate| predicate.split_whitespace().all(|term| matches_fabric_term(term, version))
+            ),
+            VersionReq::Maven(spec) => matches_maven_spec(spec, version)
+        }
+    }
+}
+
+impl Display for VersionReq {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VersionReq::Any => write!(f, "any version"),
+            VersionReq::Fabric(predicates) if predicates.is_empty() => write!(f, "any version"),
+            VersionReq::Fabric(predicates) => write!(f, "{}", predicates.join(" or ")),
+            VersionReq::Maven(spec) if spec.trim().is_empty() => write!(f, "any version"),
+            VersionReq::Maven(spec) => write!(f, "{spec}")
+        }
+    }
+}
+
+impl Display for ModIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModIssue::Missing { mod_name, dependency, versions } => write!(f, "{mod_name} requires {dependency} ({versions}), which is missing"),
+            ModIssue::WrongVersion { mod_name, dependency, versions, found } => write!(f, "{mod_name} requires {dependency} ({versions}), but {found} is installed"),
+            ModIssue::Breaks { mod_name, other, found } => write!(f, "{mod_name} is incompatible with {other} {found}"),
+            ModIssue::Duplicate { id, files } => write!(f, "{id} is provided by multiple jars: {}", files.join(", "))
+        }
+    }
+}
+
+impl Display for DependencyReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Found {} mod issue(s):", self.issues.len())?;
+        for issue in &self.issues {
+            write!(f, "\n- {issue}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl DependencyReport {
+    /// ### Checks the enabled mods of an instance against each other, the Minecraft version and the loader version
+    /// Reports missing dependencies, unsatisfied version ranges, incompatibilities and duplicate mod ids.
+    pub async fn check(instance: &SimpleInstance) -> Self {
+        let mods = ModInfo::get_all(&instance.minecraft_path).await;
+        let report = Self::from_mods(&mods, &instance.mc_version, instance.modloader.typ, &instance.modloader.version);
+
+        if report.is_ok() {
+            debug!("No mod issues found in {:?}", instance.name);
+        } else {
+            warn!("{} mod issue(s) found in {:?}", report.issues.len(), instance.name);
+        }
+
+        report
+    }
+
+    pub fn from_mods(mods: &[ModInfo], mc_version: &str, loader: ModLoaders, loader_version: &str) -> Self {
+        let mods: Vec<&ModInfo> = mods.iter().filter(|mod_info| mod_info.enabled && mod_info.metadata.is_some()).collect();
+        let mut issues = Vec::new();
+
+        // Every id that is present, with its version if it is known
+        let mut provided: HashMap<&str, Option<&str>> = HashMap::from([("minecraft", Some(mc_version))]);
+        match loader {
+            ModLoaders::Fabric => { provided.insert("fabricloader", Some(loader_version)); },
+            ModLoaders::Quilt => {
+                provided.insert("quilt_loader", Some(loader_version));
+                provided.insert("fabricloader", None);
+            },
+            ModLoaders::Forge => { provided.insert("forge", Some(loader_version)); },
+            ModLoaders::NeoForge => { provided.insert("neoforge", Some(loader_version)); },
+            _ => ()
+        }
+
+        let mut files_by_id: HashMap<&str, Vec<&str>> = HashMap::new();
+        for mod_info in &mods {
+            let meta = mod_info.metadata.as_ref().unwrap();
+            files_by_id.entry(&meta.id).or_default().push(&mod_info.file_name);
+
+            for id in std::iter::once(&meta.id).chain(&meta.provides) {
+                provided.insert(id, Some(&meta.version));
+            }
+        }
+
+        // Nested jars are only loaded if no other jar has a newer version of the same mod, so they never replace one
+        for nested in mods.iter().flat_map(|mod_info| mod_info.metadata.as_ref().unwrap().get_all_nested()) {
+            for id in std::iter::once(&nested.id).chain(&nested.provides) {
+                let newer_present = provided.get(id.as_str()).is_some_and(
+                    |version| version.is_none_or(|version| compare_versions(version, &nested.version) != Ordering::Less)
+                );
+
+                if !newer_present {
+                    provided.insert(id, Some(&nested.version));
+                }
+            }
+        }
+
+        let mut duplicates: Vec<(&str, Vec<&str>)> = files_by_id.into_iter().filter(|(_, files)| files.len() > 1).collect();
+        duplicates.sort();
+        issues.extend(duplicates.into_iter().map(|(id, files)| ModIssue::Duplicate {
+            id: id.to_string(),
+            files: files.into_iter().map(str::to_string).collect()
+        }));
+
+        for mod_info in &mods {
+            let meta = mod_info.metadata.as_ref().unwrap();
+
+            for dep in meta.dependencies.iter().filter(|dep| !IGNORED_IDS.contains(&dep.id.as_str())) {
+                let found = provided.get(dep.id.as_str());
+
+                let issue = match (dep.kind, found) {
+                    (DependencyKind::Required, None) => Some(ModIssue::Missing {
+                        mod_name: meta.name.to_string(),
+                        dependency: dep.id.to_string(),
+                        versions: dep.versions.to_string()
+                    }),
+                    (DependencyKind::Required, Some(Some(version))) if !dep.versions.matches(version) => Some(ModIssue::WrongVersion {
+                        mod_name: meta.name.to_string(),
+                        dependency: dep.id.to_string(),
+                        versions: dep.versions.to_string(),
+                        found: version.to_string()
+                    }),
+                    (DependencyKind::Breaks, Some(version)) if version.is_none_or(|version| dep.versions.matches(version)) => Some(ModIssue::Breaks {
+                        mod_name: meta.name.to_string(),
+                        other: dep.id.to_string(),
+                        found: version.unwrap_or_default().to_string()
+                    }),
+                    _ => None
+                };
+
+                issues.extend(issue);
+            }
+        }
+
+        Self { issues }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+
+/// ### Compares two versions component by component
+/// Build metadata after `+` is ignored and pre-releases (`-beta`) are lower than their release.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (a, b) = (a.split('+').next().unwrap_or_default(), b.split('+').next().unwrap_or_default());
+    let (a_core, a_pre) = a.split_once('-').map_or((a, None), |(core, pre)| (core, Some(pre)));
+    let (b_core, b_pre) = b.split_once('-').map_or((b, None), |(core, pre)| (core, Some(pre)));
+
+    let (a_parts, b_parts): (Vec<&str>, Vec<&str>) = (a_core.split('.').collect(), b_core.split('.').collect());
+
+    for i in 0..a_parts.len().max(b_parts.len()) {
+        let (a_part, b_part) = (a_parts.get(i).copied().unwrap_or("0"), b_parts.get(i).copied().unwrap_or("0"));
+
+        let ordering = match (a_part.parse::<u64>(), b_part.parse::<u64>()) {
+            (Ok(a_num), Ok(b_num)) => a_num.cmp(&b_num),
+            _ => a_part.cmp(b_part)
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    match (a_pre, b_pre) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a_pre), Some(b_pre)) => compare_versions(a_pre, b_pre)
+    }
+}
+
+fn matches_fabric_term(term: &str, version: &str) -> bool {
+    if term == "*" {
+        return true;
+    }
+
+    let op_len = term.find(|c: char| c.is_ascii_alphanumeric()).unwrap_or(term.len());
+    let (op, req) = term.split_at(op_len);
+
+    // `1.20.x` stands for every 1.20 version, so `>=1.20.x` starts at 1.20 and `<=1.20.x` ends before 1.21
+    if let Some(wildcard) = req.split('.').position(|part| matches!(part, "x" | "X" | "*")) {
+        let base: Vec<&str> = req.split('.').take(wildcard).collect();
+        let lower = base.join(".");
+        let upper = base.split_last().and_then(|(last, rest)| {
+            let last = last.parse::<u64>().ok()?;
+            Some(rest.iter().map(|part| part.to_string()).chain([(last + 1).to_string()]).collect::<Vec<_>>().join("."))
+        });
+
+        let from_lower = base.is_empty() || compare_versions(version, &lower) != Ordering::Less;
+        let below_upper = upper.is_none_or(|upper| compare_versions(version, &upper) == Ordering::Less);
+
+        return match op {
+            ">=" => from_lower,
+            "<" => !from_lower,
+            "<=" => below_upper,
+            ">" => !below_upper,
+            "~" | "^" => matches_fabric_term(&format!("{op}{lower}"), version),
+            _ => {
+                let version_parts: Vec<&str> = version.split(['.', '-', '+']).collect();
+                base.iter().enumerate().all(|(i, part)| version_parts.get(i) == Some(part))
+            }
+        };
+    }
+
+    let ordering = compare_versions(version, req);
+
+    match op {
+        ">=" => ordering != Ordering::Less,
+        "<=" => ordering != Ordering::Greater,
+        ">" => ordering == Ordering::Greater,
+        "<" => ordering == Ordering::Less,
+        // Same minor version for `~`, same major version for `^`
+        "~" | "^" => {
+            let parts: Vec<u64> = req.split(['.', '-', '+']).map_while(|part| part.parse().ok()).collect();
+            let upper = match (op, parts.as_slice()) {
+                ("~", [major, minor, ..]) => format!("{major}.{}", minor + 1),
+                (_, [major, ..]) => format!("{}", major + 1),
+                _ => return ordering != Ordering::Less
+            };
+
+            ordering != Ordering::Less && compare_versions(version, &upper) == Ordering::Less
+        },
+        _ => ordering == Ordering::Equal
+    }
+}
+
+fn matches_maven_spec(spec: &str, version: &str) -> bool {
+    let spec = spec.trim();
+
+    // A plain version is only a recommendation in Maven, so anything goes
+    if !spec.starts_with(['[', '(']) {
+        return true;
+    }
+
+    let mut ranges = Vec::new();
+    let mut start = None;
+    for (i, c) in spec.char_indices() {
+        match c {
+            '[' | '(' if start.is_none() => start = Some(i),
+            ']' | ')' => if let Some(s) = start.take() { ranges.push(&spec[s..=i]) },
+            _ => ()
+        }
+    }
+
+    ranges.into_iter().any(|range| {
+        let inner = &range[1..range.len() - 1];
+        let (lower_incl, upper_incl) = (range.starts_with('['), range.ends_with(']'));
+
+        match inner.split_once(',') {
+            None => compare_versions(version, inner.trim()) == Ordering::Equal,
+            Some((lower, upper)) => {
+                let (lower, upper) = (lower.trim(), upper.trim());
+
+                (lower.is_empty() || match compare_versions(version, lower) {
+                    Ordering::Greater => true,
+                    Ordering::Equal => lower_incl,
+                    Ordering::Less => false
+                })
+                &&
+                (upper.is_empty() || match compare_versions(version, upper) {
+                    Ordering::Less => true,
+                    Ordering::Equal => upper_incl,
+                    Ordering::Greater => false
+                })
+            }
+        }
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::launcher::mods::metadata::{ModFormat, ModMetadata};
+
+    fn fabric_mod(id: &str, version: &str, dependencies: Vec<ModDependency>, nested: Vec<ModMetadata>) -> ModMetadata {
+        ModMetadata {
+            format: ModFormat::Fabric,
+            id: id.to_string(),
+            name: id.to_string(),
+            version: version.to_string(),
+            authors: Vec::new(),
+            description: None,
+            icon_path: None,
+            provides: Vec::new(),
+            dependencies,
+            nested
+        }
+    }
+
+    fn requires(id: &str, versions: &str) -> ModDependency {
+        ModDependency::new(id.to_string(), DependencyKind::Required, VersionReq::Fabric(vec![versions.to_string()]))
+    }
+
+    fn installed(metadata: ModMetadata) -> ModInfo {
+        ModInfo {
+            file_name: format!("{}.jar", metadata.id),
+            path: PathBuf::from(format!("mods/{}.jar", metadata.id)),
+            enabled: true,
+            metadata: Some(metadata),
+            icon: None
+        }
+    }
+
+    #[test]
+    fn wildcards_in_ranges() {
+        assert!(matches_fabric_term(">=0.16.x", "0.16.5"));
+        assert!(matches_fabric_term(">=0.16.x", "0.17.0"));
+        assert!(!matches_fabric_term(">=0.16.x", "0.15.11"));
+        assert!(matches_fabric_term("<=1.20.x", "1.20.6"));
+        assert!(!matches_fabric_term("<=1.20.x", "1.21"));
+        assert!(matches_fabric_term("<1.21.x", "1.20.6"));
+        assert!(!matches_fabric_term("<1.21.x", "1.21.1"));
+        assert!(matches_fabric_term(">1.20.x", "1.21"));
+        assert!(!matches_fabric_term(">1.20.x", "1.20.6"));
+        assert!(matches_fabric_term("~1.20.x", "1.20.4"));
+        assert!(matches_fabric_term("1.20.x", "1.20.1"));
+        assert!(!matches_fabric_term("1.20.x", "1.21"));
+    }
+
+    #[test]
+    fn nested_jars_satisfy_dependencies() {
+        let fabric_api = fabric_mod("fabric-api", "0.100.0", Vec::new(), vec![
+            fabric_mod("fabric-api-base", "0.4.42", Vec::new(), Vec::new()),
+            fabric_mod("fabric-rendering-v1", "5.0.0", Vec::new(), vec![
+                fabric_mod("fabric-renderer-api-v1", "3.4.0", Vec::new(), Vec::new())
+            ])
+        ]);
+        let sodium = fabric_mod("sodium", "0.5.11", vec![
+            requires("fabric-api-base", ">=0.4.0"),
+            requires("fabric-renderer-api-v1", ">=3.0.0"),
+            requires("fabricloader", ">=0.16.x")
+        ], Vec::new());
+
+        let report = DependencyReport::from_mods(&[installed(fabric_api), installed(sodium)], "1.21", ModLoaders::Fabric, "0.16.5");
+        assert!(report.is_ok(), "{report}");
+    }
+
+    #[test]
+    fn nested_jars_do_not_replace_newer_mods() {
+        let bundling = fabric_mod("bundling", "1.0.0", Vec::new(), vec![fabric_mod("library", "1.0.0", Vec::new(), Vec::new())]);
+        let library = fabric_mod("library", "2.0.0", Vec::new(), Vec::new());
+        let dependent = fabric_mod("dependent", "1.0.0", vec![requires("library", ">=2.0.0")], Vec::new());
+
+        let report = DependencyReport::from_mods(&[installed(bundling), installed(library), installed(dependent)], "1.21", ModLoaders::Fabric, "0.16.5");
+        assert!(report.is_ok(), "{report}");
+    }
+}
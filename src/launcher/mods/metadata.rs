@@ -0,0 +1,505 @@
+use std::{collections::HashMap, io::{Cursor, Read, Seek}};
+
+use indexmap::IndexMap;
+use log::*;
+use serde::Deserialize;
+use serde_json::Value;
+use zip::ZipArchive;
+
+use super::dependencies::{DependencyKind, ModDependency, VersionReq};
+
+
+/// Jars nested deeper than this are not read, as no real mod goes this deep
+const MAX_NESTING_DEPTH: u32 = 4;
+
+
+/// The metadata of a mod jar, independent of the format it was read from
+#[derive(Debug, Clone)]
+pub struct ModMetadata {
+    pub format: ModFormat,
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    pub authors: Vec<String>,
+    pub description: Option<String>,
+    pub icon_path: Option<String>,
+    /// Other mod ids this jar provides, e.g. through aliases or multiple mods in one jar
+    pub provides: Vec<String>,
+    pub dependencies: Vec<ModDependency>,
+    /// Mods bundled inside the jar (jar-in-jar), which the loader loads as if they were in the mods folder
+    pub nested: Vec<ModMetadata>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModFormat {
+    Fabric,
+    Quilt,
+    Forge,
+    NeoForge,
+    LegacyForge
+}
+
+
+// Handling the "fabric.mod.json" file
+#[derive(Debug, Deserialize)]
+pub struct FabricModJson {
+    pub id: String,
+    /// Required by the spec, but some jars leave it out anyway
+    #[serde(default)]
+    pub version: String,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub authors: Vec<FabricPerson>,
+    pub icon: Option<ModIcon>,
+    #[serde(default)]
+    pub provides: Vec<String>,
+    #[serde(default)]
+    pub depends: HashMap<String, StringOrList>,
+    #[serde(default)]
+    pub breaks: HashMap<String, StringOrList>,
+    #[serde(default)]
+    pub jars: Vec<FabricNestedJar>
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FabricNestedJar {
+    pub file: String
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum FabricPerson {
+    Name(String),
+    Detailed { name: String }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ModIcon {
+    Single(String),
+    Sizes(HashMap<String, String>)
+}
+
+// Handling the "quilt.mod.json" file
+#[derive(Debug, Deserialize)]
+pub struct QuiltModJson {
+    pub quilt_loader: QuiltLoaderSection
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QuiltLoaderSection {
+    pub id: String,
+    pub version: String,
+    pub metadata: Option<QuiltMetadata>,
+    // These have a lot of possible shapes, so they're interpreted by hand
+    #[serde(default)]
+    pub provides: Vec<Value>,
+    #[serde(default)]
+    pub depends: Vec<Value>,
+    #[serde(default)]
+    pub breaks: Vec<Value>,
+    #[serde(default)]
+    pub jars: Vec<String>
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QuiltMetadata {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    /// Kept in the order of the file, which is usually by importance
+    #[serde(default)]
+    pub contributors: IndexMap<String, String>,
+    pub icon: Option<ModIcon>
+}
+
+// Handling the "META-INF/mods.toml" and "META-INF/neoforge.mods.toml" files
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForgeModsToml {
+    #[serde(default)]
+    pub mods: Vec<ForgeModEntry>,
+    pub logo_file: Option<String>,
+    #[serde(default)]
+    pub dependencies: HashMap<String, Vec<ForgeDependency>>
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForgeModEntry {
+    pub mod_id: String,
+    pub version: Option<String>,
+    pub display_name: Option<String>,
+    pub description: Option<String>,
+    pub authors: Option<StringOrList>,
+    pub logo_file: Option<String>
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForgeDependency {
+    pub mod_id: String,
+    /// Used by Forge
+    pub mandatory: Option<bool>,
+    /// Used by NeoForge, replaces `mandatory`
+    #[serde(rename = "type")]
+    pub typ: Option<String>,
+    pub version_range: Option<String>,
+    pub side: Option<String>
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum StringOrList {
+    String(String),
+    List(Vec<String>)
+}
+
+// Handling the legacy "mcmod.info" file
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum McModInfo {
+    List(Vec<McModInfoEntry>),
+    Versioned {
+        #[serde(rename = "modList")]
+        mod_list: Vec<McModInfoEntry>
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McModInfoEntry {
+    pub modid: String,
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub description: Option<String>,
+    #[serde(default, alias = "authors")]
+    pub author_list: Vec<String>,
+    pub logo_file: Option<String>
+}
+
+
+impl ModMetadata {
+    /// Reads the metadata of the first supported format found in the jar, including the mods nested in it
+    pub fn from_jar<R: Read + Seek>(jar: &mut ZipArchive<R>) -> Option<Self> {
+        Self::from_jar_at_depth(jar, 0)
+    }
+
+    fn from_jar_at_depth<R: Read + Seek>(jar: &mut ZipArchive<R>, depth: u32) -> Option<Self> {
+        let (mut metadata, mut nested_paths) = if let Some(quilt) = read_file(jar, "quilt.mod.json").and_then(|file| serde_json::from_str::<QuiltModJson>(&file).ok()) {
+            let paths = quilt.quilt_loader.jars.clone();
+            (quilt.into(), paths)
+        } else if let Some(fabric) = read_file(jar, "fabric.mod.json").and_then(|file| serde_json::from_str::<FabricModJson>(&file).ok()) {
+            let paths = fabric.jars.iter().map(|nested| nested.file.to_string()).collect();
+            (fabric.into(), paths)
+        } else if let Some(neoforge) = read_file(jar, "META-INF/neoforge.mods.toml").and_then(|file| toml::from_str::<ForgeModsToml>(&file).ok()) {
+            (Self::from_forge(neoforge, ModFormat::NeoForge, jar)?, Vec::new())
+        } else if let Some(forge) = read_file(jar, "META-INF/mods.toml").and_then(|file| toml::from_str::<ForgeModsToml>(&file).ok()) {
+            (Self::from_forge(forge, ModFormat::Forge, jar)?, Vec::new())
+        } else if let Some(mcmod) = read_file(jar, "mcmod.info").and_then(|file| serde_json::from_str::<McModInfo>(&file).ok()) {
+            (mcmod.into_entries().into_iter().next().map(Self::from)?, Vec::new())
+        } else {
+            return None;
+        };
+
+        // Forge and NeoForge list their nested jars in META-INF/jarjar/metadata.json, but they are always in these folders
+        nested_paths.extend(jar.file_names()
+            .filter(|name| (name.starts_with("META-INF/jars/") || name.starts_with("META-INF/jarjar/")) && name.ends_with(".jar"))
+            .map(str::to_string)
+        );
+        nested_paths.sort();
+        nested_paths.dedup();
+
+        if depth < MAX_NESTING_DEPTH {
+            metadata.nested = nested_paths.iter().filter_map(|path| {
+                let mut nested_jar = ZipArchive::new(Cursor::new(read_bytes(jar, path)?)).map_err(
+                    |err| warn!("Failed to open nested jar {path:?} of {}: {err}", metadata.id)
+                ).ok()?;
+
+                Self::from_jar_at_depth(&mut nested_jar, depth + 1)
+            }).collect();
+        }
+
+        Some(metadata)
+    }
+
+    /// All mods bundled in this jar, including those nested deeper
+    pub fn get_all_nested(&self) -> Vec<&Self> {
+        self.nested.iter().flat_map(|nested| std::iter::once(nested).chain(nested.get_all_nested())).collect()
+    }
+
+    fn from_forge<R: Read + Seek>(mut toml: ForgeModsToml, format: ModFormat, jar: &mut ZipArchive<R>) -> Option<Self> {
+        let mod_ids: Vec<String> = toml.mods.iter().map(|entry| entry.mod_id.to_string()).collect();
+        let dependencies = mod_ids.iter()
+        .filter_map(|mod_id| toml.dependencies.remove(mod_id))
+        .flatten()
+        .filter_map(ForgeDependency::into_dependency)
+        .collect();
+
+        let entry = toml.mods.into_iter().next()?;
+
+        // Forge fills this in from the jar manifest at runtime
+        let version = match entry.version {
+            Some(version) if version.contains("${file.jarVersion}") => {
+                get_manifest_version(jar).map_or(version.to_string(), |jar_ver| version.replace("${file.jarVersion}", &jar_ver))
+            },
+            Some(version) => version,
+            None => String::new()
+        };
+
+        Some(Self {
+            format,
+            name: entry.display_name.unwrap_or(entry.mod_id.to_string()),
+            id: entry.mod_id,
+            version,
+            authors: match entry.authors {
+                Some(StringOrList::String(authors)) => authors.split(',').map(|a| a.trim().to_string()).filter(|a| !a.is_empty()).collect(),
+                Some(StringOrList::List(authors)) => authors,
+                None => Vec::new()
+            },
+            description: entry.description.map(|desc| desc.trim().to_string()),
+            icon_path: entry.logo_file.or(toml.logo_file),
+            provides: mod_ids.into_iter().skip(1).collect(),
+            dependencies,
+            nested: Vec::new()
+        })
+    }
+}
+
+impl From<FabricModJson> for ModMetadata {
+    fn from(fabric: FabricModJson) -> Self {
+        Self {
+            format: ModFormat::Fabric,
+            name: fabric.name.unwrap_or(fabric.id.to_string()),
+            id: fabric.id,
+            version: fabric.version,
+            authors: fabric.authors.into_iter().map(|author| match author {
+                FabricPerson::Name(name) | FabricPerson::Detailed { name } => name
+            }).collect(),
+            description: fabric.description,
+            icon_path: fabric.icon.and_then(ModIcon::get_largest),
+            provides: fabric.provides,
+            dependencies: fabric.depends.into_iter()
+            .map(|(id, versions)| ModDependency::new(id, DependencyKind::Required, VersionReq::Fabric(versions.into_vec())))
+            .chain(fabric.breaks.into_iter().map(
+                |(id, versions)| ModDependency::new(id, DependencyKind::Breaks, VersionReq::Fabric(versions.into_vec()))
+            ))
+            .collect(),
+            nested: Vec::new()
+        }
+    }
+}
+
+impl From<QuiltModJson> for ModMetadata {
+    fn from(quilt: QuiltModJson) -> Self {
+        let loader = quilt.quilt_loader;
+        let metadata = loader.metadata.unwrap_or(QuiltMetadata { name: None, description: None, contributors: IndexMap::new(), icon: None });
+
+        Self {
+            format: ModFormat::Quilt,
+            name: metadata.name.unwrap_or(loader.id.to_string()),
+            id: loader.id,
+            version: loader.version,
+            authors: metadata.contributors.into_keys().collect(),
+            description: metadata.description,
+            icon_path: metadata.icon.and_then(ModIcon::get_largest),
+            provides: loader.provides.iter().filter_map(|provided| match provided {
+                Value::String(id) => Some(id.to_string()),
+                Value::Object(obj) => obj.get("id").and_then(Value::as_str).map(str::to_string),
+                _ => None
+            }).collect(),
+            dependencies: loader.depends.iter()
+            .filter_map(|dep| quilt_dependency(dep, DependencyKind::Required))
+            .chain(loader.breaks.iter().filter_map(|dep| quilt_dependency(dep, DependencyKind::Breaks)))
+            .collect(),
+            nested: Vec::new()
+        }
+    }
+}
+
+impl From<McModInfoEntry> for ModMetadata {
+    fn from(entry: McModInfoEntry) -> Self {
+        Self {
+            format: ModFormat::LegacyForge,
+            name: entry.name.unwrap_or(entry.modid.to_string()),
+            id: entry.modid,
+            version: entry.version.unwrap_or_default(),
+            authors: entry.author_list,
+            description: entry.description,
+            icon_path: entry.logo_file.filter(|logo| !logo.is_empty()),
+            provides: Vec::new(),
+            dependencies: Vec::new(),
+            nested: Vec::new()
+        }
+    }
+}
+
+impl McModInfo {
+    pub fn into_entries(self) -> Vec<McModInfoEntry> {
+        match self {
+            McModInfo::List(entries) | McModInfo::Versioned { mod_list: entries } => entries
+        }
+    }
+}
+
+impl ForgeDependency {
+    fn into_dependency(self) -> Option<ModDependency> {
+        if self.side.as_deref().is_some_and(|side| side.eq_ignore_ascii_case("server")) {
+            return None;
+        }
+
+        let kind = match (self.typ.as_deref().map(str::to_lowercase).as_deref(), self.mandatory) {
+            (Some("required"), _) => DependencyKind::Required,
+            (Some("incompatible"), _) => DependencyKind::Breaks,
+            (Some(_), _) | (None, Some(false)) => return None,
+            (None, _) => DependencyKind::Required
+        };
+
+        Some(ModDependency::new(self.mod_id, kind, VersionReq::Maven(self.version_range.unwrap_or_default())))
+    }
+}
+
+impl StringOrList {
+    pub fn into_vec(self) -> Vec<String> {
+        match self {
+            StringOrList::String(string) => vec![string],
+            StringOrList::List(list) => list
+        }
+    }
+}
+
+impl ModIcon {
+    fn get_largest(self) -> Option<String> {
+        match self {
+            ModIcon::Single(path) => Some(path),
+            ModIcon::Sizes(sizes) => sizes.into_iter()
+                .max_by_key(|(size, _)| size.parse::<u32>().unwrap_or(0))
+                .map(|(_, path)| path)
+        }
+    }
+}
+
+
+/// Quilt dependencies are either just an id or an object with optional version requirements
+fn quilt_dependency(dep: &Value, kind: DependencyKind) -> Option<ModDependency> {
+    match dep {
+        Value::String(id) => Some(ModDependency::new(id.to_string(), kind, VersionReq::Any)),
+        Value::Object(obj) => {
+            if obj.get("optional").and_then(Value::as_bool).unwrap_or(false) {
+                return None;
+            }
+
+            let versions = match obj.get("versions") {
+                Some(Value::String(version)) => VersionReq::Fabric(vec![version.to_string()]),
+                Some(Value::Array(versions)) => VersionReq::Fabric(
+                    versions.iter().filter_map(Value::as_str).map(str::to_string).collect()
+                ),
+                _ => VersionReq::Any
+            };
+
+            Some(ModDependency::new(obj.get("id")?.as_str()?.to_string(), kind, versions))
+        },
+        _ => None
+    }
+}
+
+
+pub fn read_file<R: Read + Seek>(jar: &mut ZipArchive<R>, name: &str) -> Option<String> {
+    let mut file = jar.by_name(name).ok()?;
+    let mut contents = String::new();
+
+    file.read_to_string(&mut contents).ok()?;
+    Some(contents)
+}
+
+pub fn read_bytes<R: Read + Seek>(jar: &mut ZipArchive<R>, name: &str) -> Option<Vec<u8>> {
+    let mut file = jar.by_name(name.trim_start_matches('/')).ok()?;
+    let mut contents = Vec::new();
+
+    file.read_to_end(&mut contents).ok()?;
+    Some(contents)
+}
+
+fn get_manifest_version<R: Read + Seek>(jar: &mut ZipArchive<R>) -> Option<String> {
+    read_file(jar, "META-INF/MANIFEST.MF")?
+    .lines()
+    .find_map(|line| line.strip_prefix("Implementation-Version:"))
+    .map(|version| version.trim().to_string())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    use super::*;
+
+    fn jar_bytes(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in files {
+            writer.start_file(*name, SimpleFileOptions::default()).unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn make_jar(files: &[(&str, &str)]) -> ZipArchive<Cursor<Vec<u8>>> {
+        let files: Vec<(&str, &[u8])> = files.iter().map(|(name, contents)| (*name, contents.as_bytes())).collect();
+        ZipArchive::new(Cursor::new(jar_bytes(&files))).unwrap()
+    }
+
+    #[test]
+    fn fabric_jar_without_version_is_read() {
+        let mut jar = make_jar(&[("fabric.mod.json", r#"{ "id": "example", "name": "Example" }"#)]);
+        let metadata = ModMetadata::from_jar(&mut jar).unwrap();
+
+        assert_eq!(metadata.id, "example");
+        assert_eq!(metadata.version, "");
+    }
+
+    #[test]
+    fn quilt_authors_keep_their_order() {
+        let mut jar = make_jar(&[("quilt.mod.json", r#"{ "quilt_loader": {
+            "id": "example", "version": "1.0.0",
+            "metadata": { "contributors": { "Zoe": "Owner", "Adam": "Contributor", "Mia": "Artist" } }
+        } }"#)]);
+        let metadata = ModMetadata::from_jar(&mut jar).unwrap();
+
+        assert_eq!(metadata.authors, ["Zoe", "Adam", "Mia"]);
+    }
+
+    #[test]
+    fn nested_jars_are_read() {
+        let base = jar_bytes(&[("fabric.mod.json", br#"{ "id": "fabric-api-base", "version": "0.4.42" }"#)]);
+        let renderer = jar_bytes(&[("fabric.mod.json", br#"{ "id": "fabric-renderer-api-v1", "version": "3.4.0" }"#)]);
+        let outer = jar_bytes(&[
+            ("fabric.mod.json", br#"{ "id": "fabric-api", "version": "0.100.0", "jars": [{ "file": "META-INF/jars/fabric-api-base.jar" }] }"#),
+            ("META-INF/jars/fabric-api-base.jar", &base),
+            // Not listed in fabric.mod.json, but still picked up from the folder
+            ("META-INF/jars/fabric-renderer-api-v1.jar", &renderer)
+        ]);
+        let metadata = ModMetadata::from_jar(&mut ZipArchive::new(Cursor::new(outer)).unwrap()).unwrap();
+
+        let nested: Vec<&str> = metadata.get_all_nested().iter().map(|nested| nested.id.as_str()).collect();
+        assert_eq!(nested, ["fabric-api-base", "fabric-renderer-api-v1"]);
+    }
+
+    #[test]
+    fn deeply_nested_jars_are_read() {
+        let inner = jar_bytes(&[("fabric.mod.json", br#"{ "id": "inner", "version": "1.0.0" }"#)]);
+        let middle = jar_bytes(&[
+            ("fabric.mod.json", br#"{ "id": "middle", "version": "1.0.0", "jars": [{ "file": "META-INF/jars/inner.jar" }] }"#),
+            ("META-INF/jars/inner.jar", &inner)
+        ]);
+        let outer = jar_bytes(&[
+            ("META-INF/mods.toml", b"[[mods]]\nmodId = \"outer\"\nversion = \"1.0.0\"\n"),
+            ("META-INF/jarjar/middle.jar", &middle)
+        ]);
+        let metadata = ModMetadata::from_jar(&mut ZipArchive::new(Cursor::new(outer)).unwrap()).unwrap();
+
+        let nested: Vec<&str> = metadata.get_all_nested().iter().map(|nested| nested.id.as_str()).collect();
+        assert_eq!(nested, ["middle", "inner"]);
+    }
+}
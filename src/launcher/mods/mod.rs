@@ -0,0 +1,251 @@
+use std::{fs::{self, File}, io::{self, BufReader}, path::{Path, PathBuf}};
+
+use image::RgbaImage;
+use log::*;
+use slint::{Image, SharedPixelBuffer, SharedString};
+use tokio::task;
+use zip::ZipArchive;
+
+use crate::{launcher::instances::{errors::InstanceGatherError, IResult}, SlMod};
+
+use self::metadata::{read_bytes, ModFormat, ModMetadata};
+
+pub mod metadata;
+pub mod dependencies;
+pub mod updates;
+
+
+/// Suffix that MultiMC, Prism and CurseForge all use to mark a mod as disabled
+pub const DISABLED_SUFFIX: &str = ".disabled";
+
+
+#[derive(Debug, Clone)]
+pub struct ModInfo {
+    /// The file name of the jar, without the `.disabled` suffix
+    pub file_name: String,
+    pub path: PathBuf,
+    pub enabled: bool,
+    pub metadata: Option<ModMetadata>,
+    pub icon: Option<RgbaImage>
+}
+
+impl ModInfo {
+    /// ### Reads all mod jars in the `mods` folder of an instance
+    /// Jars without any known metadata are still listed, using their file name.
+    pub async fn get_all(minecraft_path: &Path) -> Vec<Self> {
+        let mods_path = minecraft_path.join("mods");
+
+        task::spawn_blocking(move || {
+            let entries = match fs::read_dir(&mods_path) {
+                Ok(entries) => entries,
+                Err(err) => {
+                    debug!("No mods folder at {mods_path:?}: {err}");
+                    return Vec::new();
+                }
+            };
+
+            let mut mods: Vec<Self> = entries.filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && path.file_name().is_some_and(|name| {
+                let name = name.to_string_lossy();
+                name.ends_with(".jar") || name.ends_with(&format!(".jar{DISABLED_SUFFIX}"))
+            }))
+            .map(|path| Self::from_jar(&path))
+            .collect();
+
+            mods.sort_by_key(|mod_info| mod_info.get_name().to_lowercase());
+            mods
+        }).await.unwrap()
+    }
+
+    pub fn from_jar(path: &Path) -> Self {
+        let full_name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let enabled = !full_name.ends_with(DISABLED_SUFFIX);
+        let file_name = full_name.strip_suffix(DISABLED_SUFFIX).unwrap_or(&full_name).to_string();
+
+        let jar = File::open(path).map_err(|err| err.to_string())
+        .and_then(|file| ZipArchive::new(BufReader::new(file)).map_err(|err| err.to_string()));
+
+        let (metadata, icon) = match jar {
+            Ok(mut jar) => {
+                let metadata = ModMetadata::from_jar(&mut jar);
+                if metadata.is_none() {
+                    warn!("No known mod metadata found in {file_name:?}");
+                }
+
+                let icon = metadata.as_ref()
+                .and_then(|meta| meta.icon_path.as_ref())
+                .and_then(|icon_path| Self::load_icon(&mut jar, icon_path, &file_name));
+
+                (metadata, icon)
+            },
+            Err(err) => {
+                warn!("Failed to open mod jar {path:?}: {err}");
+                (None, None)
+            }
+        };
+
+        Self {
+            file_name,
+            path: path.to_path_buf(),
+            enabled,
+            metadata,
+            icon
+        }
+    }
+
+    fn load_icon(jar: &mut ZipArchive<BufReader<File>>, icon_path: &str, file_name: &str) -> Option<RgbaImage> {
+        let bytes = read_bytes(jar, icon_path)?;
+
+        image::load_from_memory(&bytes).map_err(
+            |err| warn!("Failed to decode icon '{icon_path}' of {file_name:?}: {err}")
+        ).ok()
+        .map(|image| image.into_rgba8())
+    }
+
+    /// ### Enables or disables the mods with the given file names
+    /// Returns the number of mods that were actually renamed.
+    pub async fn set_enabled(minecraft_path: &Path, file_names: &[String], enabled: bool) -> IResult<u32> {
+        Self::rename_all(minecraft_path, vec![(file_names.to_vec(), enabled)]).await
+    }
+
+    /// ### Enables the mods with the given file names and disables all others
+    /// Useful for finding the mod that crashes a pack by bisecting.
+    pub async fn isolate(minecraft_path: &Path, keep: &[String]) -> IResult<u32> {
+        let mods = Self::get_all(minecraft_path).await;
+        let (enable, disable): (Vec<String>, Vec<String>) = mods.into_iter()
+        .map(|mod_info| mod_info.file_name)
+        .partition(|file_name| keep.contains(file_name));
+
+        info!("Isolating {} mod(s), disabling {} other(s)", enable.len(), disable.len());
+        // Disabling first means that a failure never leaves more mods enabled than before
+        Self::rename_all(minecraft_path, vec![(disable, false), (enable, true)]).await
+    }
+
+    /// ### Enables or disables each batch of mods in order
+    /// Mods that fail to be renamed don't stop the others, they are all reported together afterwards.
+    async fn rename_all(minecraft_path: &Path, batches: Vec<(Vec<String>, bool)>) -> IResult<u32> {
+        let mods_path = minecraft_path.join("mods");
+
+        task::spawn_blocking(move || {
+            let mut renamed = 0;
+            let mut failed = Vec::new();
+
+            for (file_names, enabled) in batches {
+                for file_name in &file_names {
+                    match Self::rename(&mods_path, file_name, enabled) {
+                        Ok(true) => renamed += 1,
+                        Ok(false) => (),
+                        Err(err) => {
+                            warn!("Failed to toggle {file_name:?}: {err}");
+                            failed.push(err);
+                        }
+                    }
+                }
+            }
+
+            if failed.is_empty() {
+                Ok(renamed)
+            } else {
+                Err(InstanceGatherError::ModsRenameFailed { renamed, failed })
+            }
+        }).await.unwrap()
+    }
+
+    fn rename(mods_path: &Path, file_name: &str, enabled: bool) -> IResult<bool> {
+        let enabled_path = mods_path.join(file_name);
+        let disabled_path = mods_path.join(format!("{file_name}{DISABLED_SUFFIX}"));
+        let (from, to) = if enabled { (disabled_path, enabled_path) } else { (enabled_path, disabled_path) };
+
+        if !from.exists() {
+            return Ok(false);
+        }
+        if to.exists() {
+            return Err(InstanceGatherError::FileWriteFailed(to, io::Error::from(io::ErrorKind::AlreadyExists)));
+        }
+
+        debug!("Renaming {from:?} to {to:?}");
+        fs::rename(&from, &to).map_err(
+            |err| InstanceGatherError::FileWriteFailed(to, err)
+        )?;
+
+        Ok(true)
+    }
+
+    pub fn get_name(&self) -> &str {
+        self.metadata.as_ref().map_or(&self.file_name, |meta| &meta.name)
+    }
+
+    pub fn to_slint(&self) -> SlMod {
+        let metadata = self.metadata.as_ref();
+
+        SlMod {
+            file_name: self.file_name.to_string().into(),
+            enabled: self.enabled,
+            selected: false,
+            update: SharedString::default(),
+            can_rollback: false,
+            id: metadata.map_or(String::new(), |meta| meta.id.to_string()).into(),
+            name: self.get_name().to_string().into(),
+            version: metadata.map_or(String::new(), |meta| meta.version.to_string()).into(),
+            authors: metadata.map_or(String::new(), |meta| meta.authors.join(", ")).into(),
+            description: metadata.and_then(|meta| meta.description.clone()).unwrap_or_default().into(),
+            format: metadata.map_or("Unknown", |meta| meta.format.to_str()).into(),
+            icon: if let Some(icon) = &self.icon {
+                Image::from_rgba8(
+                SharedPixelBuffer::clone_from_slice(
+                        icon.as_raw(),
+                        icon.width(),
+                        icon.height()
+                    )
+                )
+            } else { Image::load_from_path(PathBuf::from("resources/default_instance.png").as_path()).unwrap_or_default() }
+        }
+    }
+}
+
+impl ModFormat {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            ModFormat::Fabric => "Fabric",
+            ModFormat::Quilt => "Quilt",
+            ModFormat::Forge => "Forge",
+            ModFormat::NeoForge => "NeoForge",
+            ModFormat::LegacyForge => "Forge (legacy)"
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn toggle_continues_past_failures() {
+        let dir = tempfile::tempdir().unwrap();
+        let mods_path = dir.path().join("mods");
+        fs::create_dir(&mods_path).unwrap();
+        for name in ["a.jar", "b.jar", "b.jar.disabled", "c.jar"] {
+            fs::write(mods_path.join(name), "").unwrap();
+        }
+
+        let names = ["a.jar", "b.jar", "c.jar"].map(String::from);
+        match ModInfo::set_enabled(dir.path(), &names, false).await {
+            Err(InstanceGatherError::ModsRenameFailed { renamed, failed }) => {
+                assert_eq!(renamed, 2);
+                assert_eq!(failed.len(), 1);
+            },
+            other => panic!("Expected a partial failure, got {other:?}")
+        }
+        assert!(mods_path.join("a.jar.disabled").exists());
+        assert!(mods_path.join("c.jar.disabled").exists());
+    }
+
+    #[test]
+    fn disabled_suffix_is_stripped_once() {
+        let mod_info = ModInfo::from_jar(Path::new("mods/example.jar.disabled.disabled"));
+        assert_eq!(mod_info.file_name, "example.jar.disabled");
+        assert!(!mod_info.enabled);
+    }
+}
@@ -0,0 +1,353 @@
+use std::{fs, path::{Path, PathBuf}};
+
+use chrono::Utc;
+use log::*;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use sha1_smol::Sha1;
+use tokio::task;
+
+use crate::{app::{consts::MOD_BACKUP_FOLDER, downloader::{Download, Downloader}, notifier::Notifier}, launcher::{instances::{errors::InstanceGatherError, IResult, SimpleInstance}, modrinth_api::{MRVersion, MRVersionFile}}};
+
+use super::{ModInfo, DISABLED_SUFFIX};
+
+
+const BACKUP_INDEX_NAME: &str = "backups.json";
+
+#[derive(Debug, Clone)]
+pub struct ModUpdate {
+    /// The file name of the installed jar, without the `.disabled` suffix
+    pub file_name: String,
+    pub enabled: bool,
+    pub current_version: String,
+    pub new_version: String,
+    pub new_file: MRVersionFile
+}
+
+/// An updated mod whose previous jar was kept in the backup folder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModBackup {
+    /// The full file name of the previous jar, including the `.disabled` suffix if it was disabled
+    pub old_file: String,
+    /// The file name of the jar that replaced it, without the `.disabled` suffix
+    pub new_file: String,
+    pub updated_at: i64
+}
+
+
+impl ModUpdate {
+    /// ### Looks up newer versions of all mods of an instance on Modrinth
+    /// Only versions compatible with the Minecraft version and mod loader of the instance are considered.
+    pub async fn check(instance: &SimpleInstance, api_url: &str) -> IResult<Vec<Self>> {
+        let loaders = instance.modloader.typ.to_modrinth();
+        if loaders.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mods = ModInfo::get_all(&instance.minecraft_path).await;
+        let hashed: Vec<(ModInfo, String)> = task::spawn_blocking(move || {
+            mods.into_iter().filter_map(|mod_info| {
+                let contents = fs::read(&mod_info.path).map_err(
+                    |err| warn!("Failed to read {:?} for hashing: {err}", mod_info.path)
+                ).ok()?;
+
+                let hash = Sha1::from(&contents).digest().to_string();
+                Some((mod_info, hash))
+            }).collect()
+        }).await.unwrap();
+
+        let hashes: Vec<String> = hashed.iter().map(|(_, hash)| hash.to_string()).collect();
+        let client = Client::new();
+
+        let current = MRVersion::from_hashes(&hashes, api_url, &client).await.ok_or(
+            InstanceGatherError::DownloadFailed("Modrinth version lookup".to_string())
+        )?;
+        let latest = MRVersion::get_updates(&hashes, &loaders, &[&instance.mc_version], api_url, &client).await.ok_or(
+            InstanceGatherError::DownloadFailed("Modrinth update lookup".to_string())
+        )?;
+
+        let updates: Vec<Self> = hashed.into_iter().filter_map(|(mod_info, hash)| {
+            let latest = latest.get(&hash)?;
+
+            // The installed file is already part of the newest version
+            if latest.get_file(&hash).is_some() || current.get(&hash).is_some_and(|cur| cur.id == latest.id) {
+                return None;
+            }
+
+            Some(Self {
+                current_version: current.get(&hash).map(|cur| cur.version_number.to_string())
+                    .or(mod_info.metadata.map(|meta| meta.version))
+                    .unwrap_or_default(),
+                new_version: latest.version_number.to_string(),
+                new_file: latest.get_primary_file()?.clone(),
+                file_name: mod_info.file_name,
+                enabled: mod_info.enabled
+            })
+        }).collect();
+
+        info!("Found {} mod update(s) for {:?}", updates.len(), instance.name);
+        Ok(updates)
+    }
+
+    /// ### Downloads the given updates and moves the replaced jars into the backup folder
+    /// Disabled mods stay disabled. If a jar can't be moved or a download fails, the previous jars are restored.
+    pub async fn apply(instance: &SimpleInstance, updates: &[Self], notifier: &Notifier) -> IResult<u32> {
+        let mut notifier = notifier.make_new();
+        notifier.set_progress(1, 2);
+        notifier.send_msg(&format!("Updating {} mod(s) of '{}'...", updates.len(), instance.name));
+
+        let mods_path = instance.minecraft_path.join("mods");
+        let backup_path = instance.minecraft_path.join(MOD_BACKUP_FOLDER);
+        let mut backups = ModBackup::get_all(&instance.minecraft_path).await;
+        let mut downloader = Downloader::new(notifier.make_new(), 8);
+        let mut moved = Vec::new();
+        // Backups of earlier updates that are replaced by these
+        let mut replaced = Vec::new();
+
+        for update in updates {
+            let new_name = &update.new_file.filename;
+            if Path::new(new_name).file_name().is_none_or(|name| name != new_name.as_str()) {
+                warn!("Skipping update of {:?} with unsafe file name {new_name:?}", update.file_name);
+                continue;
+            }
+
+            let old_file = if update.enabled { update.file_name.to_string() } else { format!("{}{DISABLED_SUFFIX}", update.file_name) };
+            let new_file = if update.enabled { new_name.to_string() } else { format!("{new_name}{DISABLED_SUFFIX}") };
+
+            if let Err(err) = ModBackup::move_file(&mods_path.join(&old_file), &backup_path.join(&old_file)).await {
+                ModBackup::restore_moved(&mods_path, &backup_path, &moved).await;
+                notifier.send_remove();
+                return Err(err);
+            }
+            moved.push((old_file.to_string(), new_file.to_string()));
+
+            let (kept, dropped): (Vec<ModBackup>, Vec<ModBackup>) = backups.into_iter().partition(
+                |backup| backup.old_file != old_file && backup.new_file != *new_name
+            );
+            backups = kept;
+            replaced.extend(dropped);
+            backups.push(ModBackup { old_file, new_file: new_name.to_string(), updated_at: Utc::now().timestamp() });
+
+            downloader.add(
+                Download::new(
+                    mods_path.join(new_file),
+                    &update.new_file.url,
+                    update.new_file.hashes.get("sha1").cloned(),
+                    update.new_file.size.try_into().ok()
+                ).with_sha512(update.new_file.hashes.get("sha512").cloned())
+            );
+        }
+
+        notifier.send_progress("Downloading mod updates...", 2);
+        if let Err(err) = downloader.download_all(true, "mod updates").await {
+            ModBackup::restore_moved(&mods_path, &backup_path, &moved).await;
+            notifier.send_remove();
+            Err(InstanceGatherError::DownloadFailed(format!("mod updates: {err:?}")))?;
+        }
+
+        ModBackup::save_all(&instance.minecraft_path, &backups).await?;
+
+        // A jar that was just backed up again under the same name replaced the old one already
+        for backup in replaced.iter().filter(|backup| !moved.iter().any(|(old_file, _)| *old_file == backup.old_file)) {
+            tokio::fs::remove_file(backup_path.join(&backup.old_file)).await.unwrap_or_else(
+                |err| warn!("Failed to delete the replaced backup {:?}: {err}", backup.old_file)
+            );
+        }
+
+        notifier.set_progress(0, 0);
+        notifier.send_success(&format!("Updated {} mod(s) of '{}'", moved.len(), instance.name));
+
+        Ok(moved.len() as u32)
+    }
+}
+
+impl ModBackup {
+    pub async fn get_all(minecraft_path: &Path) -> Vec<Self> {
+        let path = minecraft_path.join(MOD_BACKUP_FOLDER).join(BACKUP_INDEX_NAME);
+
+        match tokio::fs::read(&path).await {
+            Ok(contents) => serde_json::from_slice(&contents).unwrap_or_else(|err| {
+                warn!("Failed to parse mod backup index at {path:?}: {err}");
+                Vec::new()
+            }),
+            Err(_) => Vec::new()
+        }
+    }
+
+    async fn save_all(minecraft_path: &Path, backups: &[Self]) -> IResult<()> {
+        let path = minecraft_path.join(MOD_BACKUP_FOLDER).join(BACKUP_INDEX_NAME);
+
+        tokio::fs::write(&path, serde_json::to_string_pretty(backups).unwrap(/* this cannot fail */)).await.map_err(
+            |err| InstanceGatherError::FileWriteFailed(path, err)
+        )
+    }
+
+    /// ### Replaces the updated jar with the previous one from the backup folder
+    /// `file_name` is the name of the updated jar, without the `.disabled` suffix.
+    pub async fn rollback(minecraft_path: &Path, file_name: &str) -> IResult<()> {
+        let mut backups = Self::get_all(minecraft_path).await;
+        let Some(index) = backups.iter().position(|backup| backup.new_file == file_name) else {
+            warn!("No backup found for {file_name:?}");
+            return Ok(());
+        };
+        let backup = backups.remove(index);
+
+        let mods_path = minecraft_path.join("mods");
+        for path in [mods_path.join(file_name), mods_path.join(format!("{file_name}{DISABLED_SUFFIX}"))] {
+            if path.exists() {
+                tokio::fs::remove_file(&path).await.map_err(
+                    |err| InstanceGatherError::FileWriteFailed(path, err)
+                )?;
+            }
+        }
+
+        info!("Rolling back {file_name:?} to {:?}", backup.old_file);
+        Self::move_file(&minecraft_path.join(MOD_BACKUP_FOLDER).join(&backup.old_file), &mods_path.join(&backup.old_file)).await?;
+        Self::save_all(minecraft_path, &backups).await
+    }
+
+    /// Moves the jars of an unfinished update back from the backup folder, replacing any new jars
+    async fn restore_moved(mods_path: &Path, backup_path: &Path, moved: &[(String, String)]) {
+        for (old_file, new_file) in moved {
+            tokio::fs::remove_file(mods_path.join(new_file)).await.ok();
+            Self::move_file(&backup_path.join(old_file), &mods_path.join(old_file)).await.unwrap_or_else(
+                |err| error!("Failed to restore {old_file:?}: {err}")
+            );
+        }
+    }
+
+    async fn move_file(from: &Path, to: &Path) -> IResult<()> {
+        let (from, to): (PathBuf, PathBuf) = (from.to_path_buf(), to.to_path_buf());
+
+        if let Some(parent) = to.parent() {
+            tokio::fs::create_dir_all(parent).await.map_err(
+                |err| InstanceGatherError::FileWriteFailed(parent.to_path_buf(), err)
+            )?;
+        }
+
+        tokio::fs::rename(&from, &to).await.map_err(
+            |err| InstanceGatherError::FileWriteFailed(to, err)
+        )
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{app::{notifier::InternalNotifier, test_server::{TestResponse, TestServer}}, launcher::{instances::{InstanceType, ModLoader}, modloaders::ModLoaders}};
+
+    fn make_instance(minecraft_path: &Path) -> SimpleInstance {
+        SimpleInstance {
+            name: "Test".to_string(),
+            icon: None,
+            minecraft_path: minecraft_path.to_path_buf(),
+            instance_path: minecraft_path.to_path_buf(),
+            id: 0,
+            mc_version: "1.21".to_string(),
+            modloader: ModLoader { name: "Fabric".to_string(), typ: ModLoaders::Fabric, version: "0.16.5".to_string() },
+            last_played: None,
+            last_played_for: None,
+            total_time_played: None,
+            play_count: None,
+            instance_type: InstanceType::Native
+        }
+    }
+
+    fn version(id: &str, number: &str, contents: &[u8], url: &str) -> serde_json::Value {
+        json!({
+            "id": id, "project_id": "example", "name": number, "version_number": number,
+            "game_versions": ["1.21"], "loaders": ["fabric"],
+            "files": [{
+                "hashes": { "sha1": Sha1::from(contents).digest().to_string() },
+                "url": url, "filename": format!("example-{number}.jar"), "primary": true, "size": contents.len()
+            }]
+        })
+    }
+
+    #[tokio::test]
+    async fn updates_are_found_and_applied() {
+        let dir = tempfile::tempdir().unwrap();
+        let mods_path = dir.path().join("mods");
+        fs::create_dir(&mods_path).unwrap();
+        fs::write(mods_path.join("example-1.0.0.jar"), b"old").unwrap();
+        let hash = Sha1::from(b"old").digest().to_string();
+
+        let server = TestServer::start().await;
+        server.route("/version_files", TestResponse::ok(json!({ &hash: version("v1", "1.0.0", b"old", "") }).to_string()))
+        .route("/version_files/update", TestResponse::ok(json!({ &hash: version("v2", "2.0.0", b"new", &format!("{}/example-2.0.0.jar", server.url)) }).to_string()))
+        .route("/example-2.0.0.jar", TestResponse::ok(b"new".to_vec()));
+
+        let instance = make_instance(dir.path());
+        let updates = ModUpdate::check(&instance, &server.url).await.unwrap();
+        assert_eq!(updates.len(), 1);
+        assert_eq!((updates[0].current_version.as_str(), updates[0].new_version.as_str()), ("1.0.0", "2.0.0"));
+
+        let lookup = server.requests().into_iter().find(|request| request.path == "/version_files/update").unwrap();
+        assert!(lookup.body.contains(r#""loaders":["fabric"]"#) && lookup.body.contains(r#""game_versions":["1.21"]"#));
+
+        let notifier = InternalNotifier::new().make_notifier();
+        assert_eq!(ModUpdate::apply(&instance, &updates, &notifier).await.unwrap(), 1);
+        assert_eq!(fs::read(mods_path.join("example-2.0.0.jar")).unwrap(), b"new");
+        assert!(!mods_path.join("example-1.0.0.jar").exists());
+
+        ModBackup::rollback(dir.path(), "example-2.0.0.jar").await.unwrap();
+        assert_eq!(fs::read(mods_path.join("example-1.0.0.jar")).unwrap(), b"old");
+        assert!(!mods_path.join("example-2.0.0.jar").exists());
+    }
+
+    #[tokio::test]
+    async fn failed_move_restores_moved_jars() {
+        let dir = tempfile::tempdir().unwrap();
+        let mods_path = dir.path().join("mods");
+        fs::create_dir(&mods_path).unwrap();
+        fs::write(mods_path.join("a-1.jar"), b"a").unwrap();
+
+        let update = |file_name: &str, new_name: &str| ModUpdate {
+            file_name: file_name.to_string(),
+            enabled: true,
+            current_version: "1".to_string(),
+            new_version: "2".to_string(),
+            new_file: MRVersionFile { hashes: Default::default(), url: String::new(), filename: new_name.to_string(), primary: true, size: 0 }
+        };
+        // The second jar doesn't exist, so moving it fails after the first one was moved
+        let updates = [update("a-1.jar", "a-2.jar"), update("b-1.jar", "b-2.jar")];
+
+        let notifier = InternalNotifier::new().make_notifier();
+        assert!(ModUpdate::apply(&make_instance(dir.path()), &updates, &notifier).await.is_err());
+        assert_eq!(fs::read(mods_path.join("a-1.jar")).unwrap(), b"a");
+        assert!(!dir.path().join(MOD_BACKUP_FOLDER).join("a-1.jar").exists());
+    }
+
+    #[tokio::test]
+    async fn replaced_backups_are_deleted() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mods_path, backup_path) = (dir.path().join("mods"), dir.path().join(MOD_BACKUP_FOLDER));
+        fs::create_dir_all(&mods_path).unwrap();
+        fs::create_dir_all(&backup_path).unwrap();
+        // Installed again by hand after an earlier update to the same version
+        fs::write(mods_path.join("example-1-fixed.jar"), b"1").unwrap();
+        fs::write(backup_path.join("example-1.jar"), b"1").unwrap();
+        ModBackup::save_all(dir.path(), &[ModBackup { old_file: "example-1.jar".to_string(), new_file: "example-2.jar".to_string(), updated_at: 0 }]).await.unwrap();
+
+        let server = TestServer::start().await;
+        server.route("/example-2.jar", TestResponse::ok(b"2".to_vec()));
+        let update = ModUpdate {
+            file_name: "example-1-fixed.jar".to_string(),
+            enabled: true,
+            current_version: "1".to_string(),
+            new_version: "2".to_string(),
+            new_file: MRVersionFile { hashes: Default::default(), url: format!("{}/example-2.jar", server.url), filename: "example-2.jar".to_string(), primary: true, size: 1 }
+        };
+
+        let notifier = InternalNotifier::new().make_notifier();
+        ModUpdate::apply(&make_instance(dir.path()), &[update], &notifier).await.unwrap();
+
+        let backups = ModBackup::get_all(dir.path()).await;
+        assert_eq!(backups.len(), 1);
+        assert_eq!(backups[0].old_file, "example-1-fixed.jar");
+        assert!(!backup_path.join("example-1.jar").exists());
+    }
+}
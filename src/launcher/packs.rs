@@ -0,0 +1,368 @@
+use std::{cmp::Ordering, fs::File, io::BufReader, path::{Path, PathBuf}};
+
+use image::RgbaImage;
+use log::*;
+use serde::Deserialize;
+use serde_json::Value;
+use slint::{Image, SharedPixelBuffer};
+use tokio::{fs, task};
+use zip::ZipArchive;
+
+use crate::{launcher::{instances::{errors::InstanceGatherError, IResult, SimpleInstance}, mods::{dependencies::compare_versions, metadata::{read_bytes, read_file}}}, SlPack};
+
+
+/// The first Minecraft version of each resource pack format
+const PACK_FORMATS: [(u32, &str); 21] = [
+    (1, "1.6.1"), (2, "1.9"), (3, "1.11"), (4, "1.13"), (5, "1.15"), (6, "1.16.2"), (7, "1.17"),
+    (8, "1.18"), (9, "1.19"), (12, "1.19.3"), (13, "1.19.4"), (15, "1.20"), (18, "1.20.2"), (22, "1.20.3"),
+    (32, "1.20.5"), (34, "1.21"), (42, "1.21.2"), (46, "1.21.4"), (55, "1.21.5"), (63, "1.21.6"), (64, "1.21.7")
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PackType {
+    ResourcePack,
+    ShaderPack
+}
+
+#[derive(Debug, Clone)]
+pub struct PackInfo {
+    pub file_name: String,
+    pub path: PathBuf,
+    pub typ: PackType,
+    pub description: String,
+    pub pack_format: Option<u32>,
+    pub supported_formats: Option<(u32, u32)>,
+    pub icon: Option<RgbaImage>,
+    pub enabled: bool
+}
+
+// Handling the "pack.mcmeta" file
+#[derive(Debug, Deserialize)]
+pub struct PackMcmeta {
+    pub pack: PackMcmetaPack
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PackMcmetaPack {
+    pub pack_format: Option<u32>,
+    /// A text component, which can be a string, an object or a list of either
+    #[serde(default)]
+    pub description: Value,
+    pub supported_formats: Option<Value>
+}
+
+
+impl PackInfo {
+    /// ### Reads all packs of the given type in an instance
+    /// Packs can be zip files or folders. Resource packs are marked as enabled according to `options.txt`.
+    pub async fn get_all(instance: &SimpleInstance, typ: PackType) -> Vec<Self> {
+        let folder = instance.minecraft_path.join(typ.folder());
+        let mut entries = match fs::read_dir(&folder).await {
+            Ok(entries) => entries,
+            Err(err) => {
+                debug!("No {} folder at {folder:?}: {err}", typ.folder());
+                return Vec::new();
+            }
+        };
+
+        let enabled = if typ == PackType::ResourcePack {
+            get_enabled_packs(&instance.minecraft_path).await
+        } else { Vec::new() };
+
+        let mut packs = Vec::new();
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            let file_name = entry.file_name().to_string_lossy().to_string();
+
+            if !path.is_dir() && path.extension().is_none_or(|ext| ext != "zip") {
+                continue;
+            }
+
+            let mut pack = if path.is_dir() {
+                Self::from_folder(&path, typ).await
+            } else {
+                let zip_path = path.clone();
+                task::spawn_blocking(move || Self::from_zip(&zip_path, typ)).await.unwrap()
+            };
+
+            pack.enabled = enabled.iter().any(|name| *name == file_name || *name == format!("file/{file_name}"));
+            packs.push(pack);
+        }
+
+        packs.sort_by_key(|pack| pack.file_name.to_lowercase());
+        packs
+    }
+
+    fn new(path: &Path, typ: PackType) -> Self {
+        Self {
+            file_name: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+            path: path.to_path_buf(),
+            typ,
+            description: String::new(),
+            pack_format: None,
+            supported_formats: None,
+            icon: None,
+            enabled: false
+        }
+    }
+
+    async fn from_folder(path: &Path, typ: PackType) -> Self {
+        let mut pack = Self::new(path, typ);
+
+        if let Ok(mcmeta) = fs::read_to_string(path.join("pack.mcmeta")).await {
+            pack.apply_mcmeta(&mcmeta);
+        }
+        if path.join("pack.png").is_file() {
+            pack.icon = SimpleInstance::load_image(path.join("pack.png").to_string_lossy().to_string()).await;
+        }
+
+        pack
+    }
+
+    fn from_zip(path: &Path, typ: PackType) -> Self {
+        let mut pack = Self::new(path, typ);
+
+        let archive = File::open(path).map_err(|err| err.to_string())
+        .and_then(|file| ZipArchive::new(BufReader::new(file)).map_err(|err| err.to_string()));
+
+        match archive {
+            Ok(mut archive) => {
+                if let Some(mcmeta) = read_file(&mut archive, "pack.mcmeta") {
+                    pack.apply_mcmeta(&mcmeta);
+                }
+
+                pack.icon = read_bytes(&mut archive, "pack.png").and_then(|bytes| {
+                    image::load_from_memory(&bytes).map_err(
+                        |err| warn!("Failed to decode pack.png of {:?}: {err}", pack.file_name)
+                    ).ok()
+                }).map(|image| image.into_rgba8());
+            },
+            Err(err) => warn!("Failed to open pack {path:?}: {err}")
+        }
+
+        pack
+    }
+
+    fn apply_mcmeta(&mut self, mcmeta: &str) {
+        // Some packs start with a byte order mark
+        match serde_json::from_str::<PackMcmeta>(mcmeta.trim_start_matches('\u{feff}')) {
+            Ok(mcmeta) => {
+                self.description = strip_formatting(&flatten_text(&mcmeta.pack.description));
+                self.pack_format = mcmeta.pack.pack_format;
+                self.supported_formats = mcmeta.pack.supported_formats.as_ref().and_then(parse_supported_formats);
+            },
+            Err(err) => warn!("Failed to parse pack.mcmeta of {:?}: {err}", self.file_name)
+        }
+    }
+
+    /// ### Checks if the pack format fits the given Minecraft version
+    /// Returns a warning if it doesn't. Versions without a known pack format are never warned about.
+    pub fn get_format_warning(&self, mc_version: &str) -> Option<String> {
+        let pack_format = self.pack_format?;
+        let expected = expected_pack_format(mc_version)?;
+
+        let fits = match self.supported_formats {
+            Some((min, max)) => (min..=max).contains(&expected),
+            None => pack_format == expected
+        };
+
+        if fits {
+            None
+        } else {
+            Some(format!("Made for pack format {pack_format}, but {mc_version} uses {expected}"))
+        }
+    }
+
+    /// ### Enables or disables a resource pack by editing the `resourcePacks` line in `options.txt`
+    /// Enabled packs are added last, which gives them the highest priority.
+    pub async fn set_enabled(instance: &SimpleInstance, file_name: &str, enabled: bool) -> IResult<()> {
+        let path = instance.minecraft_path.join("options.txt");
+        let options = fs::read_to_string(&path).await.unwrap_or_default();
+
+        let legacy = uses_legacy_pack_names(&instance.mc_version);
+
+        // A line that can't be read is left alone instead of replacing the user's packs
+        let mut packs = parse_pack_list(&options).map_err(
+            |err| InstanceGatherError::ParseFailedMeta(path.to_path_buf(), err)
+        )?.unwrap_or_else(|| if legacy { Vec::new() } else { vec!["vanilla".to_string()] });
+        packs.retain(|name| name != file_name && *name != format!("file/{file_name}"));
+
+        if enabled {
+            if legacy {
+                packs.push(file_name.to_string());
+            } else {
+                packs.push(format!("file/{file_name}"));
+            }
+        }
+
+        let pack_line = format!("resourcePacks:{}", serde_json::to_string(&packs).unwrap(/* this cannot fail */));
+        let mut found = false;
+        let mut lines: Vec<String> = options.lines().map(|line| {
+            if line.starts_with("resourcePacks:") {
+                found = true;
+                pack_line.to_string()
+            } else { line.to_string() }
+        }).collect();
+
+        if !found {
+            lines.push(pack_line);
+        }
+
+        debug!("{} resource pack {file_name:?} in {path:?}", if enabled { "Enabling" } else { "Disabling" });
+        fs::write(&path, lines.join("\n") + "\n").await.map_err(
+            |err| InstanceGatherError::FileWriteFailed(path, err)
+        )
+    }
+
+    pub fn to_slint(&self, mc_version: &str) -> SlPack {
+        SlPack {
+            file_name: self.file_name.to_string().into(),
+            description: self.description.to_string().into(),
+            pack_format: self.pack_format.map_or(String::new(), |format| format.to_string()).into(),
+            warning: self.get_format_warning(mc_version).unwrap_or_default().into(),
+            enabled: self.enabled,
+            icon: if let Some(icon) = &self.icon {
+                Image::from_rgba8(
+                SharedPixelBuffer::clone_from_slice(
+                        icon.as_raw(),
+                        icon.width(),
+                        icon.height()
+                    )
+                )
+            } else { Image::load_from_path(PathBuf::from("resources/default_instance.png").as_path()).unwrap_or_default() }
+        }
+    }
+}
+
+impl PackType {
+    pub fn folder(&self) -> &'static str {
+        match self {
+            PackType::ResourcePack => "resourcepacks",
+            PackType::ShaderPack => "shaderpacks"
+        }
+    }
+}
+
+
+/// Gets the pack format of the newest format change at or before the given version
+pub fn expected_pack_format(mc_version: &str) -> Option<u32> {
+    // Snapshots and other odd versions can't be compared
+    if !mc_version.starts_with("1.") {
+        return None;
+    }
+
+    PACK_FORMATS.iter().rev()
+    .find(|(_, first_version)| compare_versions(mc_version, first_version) != Ordering::Less)
+    .map(|(format, _)| *format)
+}
+
+async fn get_enabled_packs(minecraft_path: &Path) -> Vec<String> {
+    fs::read_to_string(minecraft_path.join("options.txt")).await.ok()
+    .and_then(|options| parse_pack_list(&options).map_err(
+        |err| warn!("Failed to parse resourcePacks line in options.txt: {err}")
+    ).ok().flatten())
+    .unwrap_or_default()
+}
+
+/// Is `None` if `options.txt` has no `resourcePacks` line yet
+fn parse_pack_list(options: &str) -> Result<Option<Vec<String>>, serde_json::Error> {
+    options.lines()
+    .find_map(|line| line.strip_prefix("resourcePacks:"))
+    .map(|line| serde_json::from_str(line.trim()))
+    .transpose()
+}
+
+/// Versions before 1.13 list packs without the "file/" prefix and have no "vanilla" entry
+fn uses_legacy_pack_names(mc_version: &str) -> bool {
+    mc_version.starts_with("1.") && compare_versions(mc_version, "1.13") == Ordering::Less
+}
+
+/// `supported_formats` can be a single number, a `[min, max]` list or a `{min_inclusive, max_inclusive}` object
+fn parse_supported_formats(value: &Value) -> Option<(u32, u32)> {
+    let get = |value: Option<&Value>| value.and_then(Value::as_u64).map(|num| num as u32);
+
+    match value {
+        Value::Number(num) => num.as_u64().map(|num| (num as u32, num as u32)),
+        Value::Array(list) => Some((get(list.first())?, get(list.get(1))?)),
+        Value::Object(obj) => Some((get(obj.get("min_inclusive"))?, get(obj.get("max_inclusive"))?)),
+        _ => None
+    }
+}
+
+fn flatten_text(component: &Value) -> String {
+    match component {
+        Value::String(text) => text.to_string(),
+        Value::Array(parts) => parts.iter().map(flatten_text).collect(),
+        Value::Object(obj) => {
+            let text = obj.get("text").or(obj.get("translate")).and_then(Value::as_str).unwrap_or_default();
+            let extra: String = obj.get("extra").map(flatten_text).unwrap_or_default();
+            format!("{text}{extra}")
+        },
+        Value::Number(num) => num.to_string(),
+        Value::Bool(bool) => bool.to_string(),
+        Value::Null => String::new()
+    }
+}
+
+/// Removes `§` formatting codes from a description
+fn strip_formatting(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '§' {
+            chars.next();
+        } else {
+            result.push(c);
+        }
+    }
+
+    result
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::launcher::{instances::{InstanceType, ModLoader}, modloaders::ModLoaders};
+
+    use super::*;
+
+    fn make_instance(minecraft_path: &Path, mc_version: &str) -> SimpleInstance {
+        SimpleInstance {
+            name: "Test".to_string(),
+            icon: None,
+            minecraft_path: minecraft_path.to_path_buf(),
+            instance_path: minecraft_path.to_path_buf(),
+            id: 0,
+            mc_version: mc_version.to_string(),
+            modloader: ModLoader { name: "Vanilla".to_string(), typ: ModLoaders::Vanilla, version: String::new() },
+            last_played: None,
+            last_played_for: None,
+            total_time_played: None,
+            play_count: None,
+            instance_type: InstanceType::Native
+        }
+    }
+
+    #[tokio::test]
+    async fn unreadable_pack_list_is_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = "resourcePacks:[\"vanilla\",\"file/a.zip\"\nlang:en_us\n";
+        std::fs::write(dir.path().join("options.txt"), options).unwrap();
+
+        assert!(PackInfo::set_enabled(&make_instance(dir.path(), "1.20.1"), "b.zip", true).await.is_err());
+        assert_eq!(std::fs::read_to_string(dir.path().join("options.txt")).unwrap(), options);
+    }
+
+    #[tokio::test]
+    async fn vanilla_is_only_added_where_it_exists() {
+        let dir = tempfile::tempdir().unwrap();
+
+        PackInfo::set_enabled(&make_instance(dir.path(), "1.12.2"), "a.zip", true).await.unwrap();
+        assert_eq!(std::fs::read_to_string(dir.path().join("options.txt")).unwrap(), "resourcePacks:[\"a.zip\"]\n");
+
+        std::fs::remove_file(dir.path().join("options.txt")).unwrap();
+        PackInfo::set_enabled(&make_instance(dir.path(), "1.20.1"), "a.zip", true).await.unwrap();
+        assert_eq!(std::fs::read_to_string(dir.path().join("options.txt")).unwrap(), "resourcePacks:[\"vanilla\",\"file/a.zip\"]\n");
+    }
+}
@@ -0,0 +1,310 @@
+use std::{collections::HashSet, fs::File, path::{Path, PathBuf}};
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeDelta, TimeZone};
+use log::*;
+use serde::{Deserialize, Serialize};
+use tokio::{fs, task};
+use zip::ZipWriter;
+
+use crate::{app::{consts::WORLD_BACKUP_FOLDER, utils::{extract_zip_folder, list_files_relative, zip_files}}, launcher::instances::{errors::InstanceGatherError, open_archive, IResult}, SlBackupSettings, SlWorldBackup};
+
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d_%H-%M-%S";
+/// Minecraft keeps this file locked while a world is open, so it can't be copied
+const SESSION_LOCK_NAME: &str = "session.lock";
+
+/// What a backup contains. Both kinds of archives hold paths relative to the `saves` folder.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackupTarget {
+    /// A single world by its folder name
+    World(String),
+    /// The whole `saves` folder
+    Saves
+}
+
+#[derive(Debug, Clone)]
+pub struct WorldBackup {
+    pub path: PathBuf,
+    pub target: BackupTarget,
+    pub created: DateTime<Local>,
+    pub size: u64
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupSettings {
+    /// Always keep this many of the newest backups of a world
+    pub keep_last: Option<u32>,
+    /// Additionally keep the newest backup of each of this many past days
+    pub keep_daily_days: Option<u32>,
+    /// Back up all worlds right before an instance is launched
+    pub before_launch: bool,
+    /// Back up all worlds every this many minutes while an instance is running
+    pub interval_minutes: Option<u32>
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        Self {
+            keep_last: Some(5),
+            keep_daily_days: Some(7),
+            before_launch: false,
+            interval_minutes: None
+        }
+    }
+}
+
+
+impl WorldBackup {
+    /// ### Zips a world or the whole `saves` folder into the backup folder of an instance
+    /// Older backups of the same target are pruned afterwards according to `settings`.
+    pub async fn create(minecraft_path: &Path, target: BackupTarget, settings: &BackupSettings) -> IResult<Self> {
+        let backup = Self::write(minecraft_path, target).await?;
+        Self::prune(minecraft_path, &backup.target, settings).await;
+
+        Ok(backup)
+    }
+
+    async fn write(minecraft_path: &Path, target: BackupTarget) -> IResult<Self> {
+        let saves_path = minecraft_path.join("saves");
+        let (source, prefix) = match &target {
+            BackupTarget::World(folder_name) => (saves_path.join(folder_name), folder_name.to_string()),
+            BackupTarget::Saves => (saves_path.clone(), String::new())
+        };
+
+        if !source.is_dir() {
+            return Err(InstanceGatherError::InvalidWorld(source, "Nothing to back up".to_string()));
+        }
+
+        let folder = minecraft_path.join(WORLD_BACKUP_FOLDER).join(target.folder());
+        fs::create_dir_all(&folder).await.map_err(
+            |err| InstanceGatherError::FileWriteFailed(folder.clone(), err)
+        )?;
+
+        let created = Local::now();
+        let mut path = folder.join(format!("{}.zip", created.format(TIMESTAMP_FORMAT)));
+        let mut count = 1;
+        while path.exists() {
+            count += 1;
+            path = folder.join(format!("{}_{count}.zip", created.format(TIMESTAMP_FORMAT)));
+        }
+
+        info!("Backing up {source:?} to {path:?}");
+        let (temp_path, target_path) = (path.with_extension("zip.tmp"), path.clone());
+
+        task::spawn_blocking(move || {
+            let files: Vec<PathBuf> = list_files_relative(&source, &[]).map_err(
+                |err| InstanceGatherError::FileReadFailed(source.clone(), err)
+            )?
+            .into_iter()
+            .filter(|rel_path| rel_path.file_name().is_none_or(|name| name != SESSION_LOCK_NAME))
+            .collect();
+
+            let file = File::create(&temp_path).map_err(
+                |err| InstanceGatherError::FileWriteFailed(temp_path.clone(), err)
+            )?;
+
+            let mut writer = ZipWriter::new(file);
+            let result = zip_files(&mut writer, &source, &files, &prefix).and_then(|_| writer.finish().map(|_| ()));
+            if let Err(err) = result {
+                std::fs::remove_file(&temp_path).ok();
+                return Err(InstanceGatherError::ArchiveWriteFailed(target_path, err));
+            }
+
+            // Only finished archives get the .zip extension, so an interrupted backup is never listed
+            std::fs::rename(&temp_path, &target_path).map_err(
+                |err| InstanceGatherError::FileWriteFailed(target_path.clone(), err)
+            )
+        }).await.unwrap()?;
+
+        Ok(Self {
+            size: fs::metadata(&path).await.map_or(0, |meta| meta.len()),
+            path,
+            target,
+            created
+        })
+    }
+
+    /// ### Reads all backups of an instance
+    /// The newest backups come first.
+    pub async fn get_all(minecraft_path: &Path) -> Vec<Self> {
+        let backup_path = minecraft_path.join(WORLD_BACKUP_FOLDER);
+        let mut backups = read_backup_folder(&backup_path.join(BackupTarget::Saves.folder()), BackupTarget::Saves).await;
+
+        if let Ok(mut entries) = fs::read_dir(backup_path.join("worlds")).await {
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                let target = BackupTarget::World(entry.file_name().to_string_lossy().to_string());
+                backups.append(&mut read_backup_folder(&entry.path(), target).await);
+            }
+        }
+
+        backups.sort_by_key(|backup| std::cmp::Reverse((backup.created, backup.path.clone())));
+        backups
+    }
+
+    /// ### Replaces the world (or all worlds) with the contents of this backup
+    /// The current state is backed up first, so a restore can be undone.
+    pub async fn restore(&self, minecraft_path: &Path, settings: &BackupSettings) -> IResult<()> {
+        let saves_path = minecraft_path.join("saves");
+        let current = match &self.target {
+            BackupTarget::World(folder_name) => saves_path.join(folder_name),
+            BackupTarget::Saves => saves_path.clone()
+        };
+
+        if current.is_dir() {
+            Self::write(minecraft_path, self.target.clone()).await?;
+
+            fs::remove_dir_all(&current).await.map_err(
+                |err| InstanceGatherError::FileWriteFailed(current.clone(), err)
+            )?;
+        }
+
+        info!("Restoring {:?} from {:?}", self.target.get_name(), self.path);
+        let path = self.path.clone();
+        task::spawn_blocking(move || {
+            extract_zip_folder(&mut open_archive(&path)?, "", &saves_path).map_err(
+                |err| InstanceGatherError::ArchiveReadFailed(path, err)
+            )
+        }).await.unwrap()?;
+
+        // The safety backup is only pruned now, as it might have replaced the backup that was just restored
+        Self::prune(minecraft_path, &self.target, settings).await;
+        Ok(())
+    }
+
+    pub async fn delete(&self) -> IResult<()> {
+        info!("Deleting world backup at {:?}", self.path);
+
+        fs::remove_file(&self.path).await.map_err(
+            |err| InstanceGatherError::FileWriteFailed(self.path.clone(), err)
+        )
+    }
+
+    /// ### Deletes backups of `target` that aren't covered by the retention rules
+    /// Without any rules, all backups are kept.
+    async fn prune(minecraft_path: &Path, target: &BackupTarget, settings: &BackupSettings) {
+        if settings.keep_last.is_none() && settings.keep_daily_days.is_none() {
+            return;
+        }
+
+        let backups: Vec<Self> = Self::get_all(minecraft_path).await.into_iter()
+        .filter(|backup| backup.target == *target)
+        .collect();
+
+        let mut keep: HashSet<PathBuf> = backups.iter()
+        .take(settings.keep_last.unwrap_or(0) as usize)
+        .map(|backup| backup.path.clone())
+        .collect();
+
+        if let Some(days) = settings.keep_daily_days {
+            let oldest_day = Local::now().date_naive() - TimeDelta::days(days.saturating_sub(1) as i64);
+            let mut seen_days = HashSet::new();
+
+            // Backups are sorted newest first, so the first one of each day is the one to keep
+            for backup in &backups {
+                let day = backup.created.date_naive();
+                if day >= oldest_day && seen_days.insert(day) {
+                    keep.insert(backup.path.clone());
+                }
+            }
+        }
+
+        for backup in backups.iter().filter(|backup| !keep.contains(&backup.path)) {
+            debug!("Pruning world backup {:?}", backup.path);
+            backup.delete().await.unwrap_or_else(
+                |err| warn!("Failed to prune world backup: {err}")
+            );
+        }
+    }
+
+    /// Identifies the backup by its path inside the backup folder
+    pub fn get_id(&self) -> String {
+        format!(
+            "{}/{}",
+            self.target.folder().to_string_lossy().replace('\\', "/"),
+            self.path.file_name().unwrap_or_default().to_string_lossy()
+        )
+    }
+
+    pub fn to_slint(&self) -> SlWorldBackup {
+        SlWorldBackup {
+            id: self.get_id().into(),
+            name: self.target.get_name().into(),
+            created: self.created.format("%d/%m/%Y, %H:%M:%S").to_string().into(),
+            size: format!("{:.1} MB", self.size as f64 / 1_000_000.0).into()
+        }
+    }
+}
+
+impl BackupSettings {
+    /// Unset values are shown as 0 in the settings page
+    pub fn to_slint(&self) -> SlBackupSettings {
+        SlBackupSettings {
+            keep_last: self.keep_last.unwrap_or(0) as i32,
+            keep_daily_days: self.keep_daily_days.unwrap_or(0) as i32,
+            before_launch: self.before_launch,
+            interval_minutes: self.interval_minutes.unwrap_or(0) as i32
+        }
+    }
+
+    pub fn from_slint(slint: SlBackupSettings) -> Self {
+        let to_option = |value: i32| u32::try_from(value).ok().filter(|&value| value > 0);
+
+        Self {
+            keep_last: to_option(slint.keep_last),
+            keep_daily_days: to_option(slint.keep_daily_days),
+            before_launch: slint.before_launch,
+            interval_minutes: to_option(slint.interval_minutes)
+        }
+    }
+}
+
+impl BackupTarget {
+    /// The folder below the backup folder that holds the archives of this target
+    fn folder(&self) -> PathBuf {
+        match self {
+            BackupTarget::World(folder_name) => Path::new("worlds").join(folder_name),
+            BackupTarget::Saves => PathBuf::from("all-saves")
+        }
+    }
+
+    pub fn get_name(&self) -> String {
+        match self {
+            BackupTarget::World(folder_name) => folder_name.to_string(),
+            BackupTarget::Saves => "All worlds".to_string()
+        }
+    }
+}
+
+
+async fn read_backup_folder(folder: &Path, target: BackupTarget) -> Vec<WorldBackup> {
+    let Ok(mut entries) = fs::read_dir(folder).await else {
+        return Vec::new();
+    };
+
+    let mut backups = Vec::new();
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        if path.extension().is_none_or(|ext| ext != "zip") {
+            continue;
+        }
+
+        // Backups made within the same second get a counter after the timestamp
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let created = file_name.get(..19)
+        .and_then(|timestamp| NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT).ok())
+        .and_then(|time| Local.from_local_datetime(&time).earliest());
+
+        match created {
+            Some(created) => backups.push(WorldBackup {
+                size: entry.metadata().await.map_or(0, |meta| meta.len()),
+                path,
+                target: target.clone(),
+                created
+            }),
+            None => debug!("Ignoring unknown file in backup folder: {path:?}")
+        }
+    }
+
+    backups
+}
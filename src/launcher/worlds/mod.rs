@@ -0,0 +1,184 @@
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use image::RgbaImage;
+use log::*;
+use slint::{Image, SharedPixelBuffer};
+use tokio::{fs, task};
+
+use crate::{app::{slint_utils::SlintOption, utils::copy_dir_all}, launcher::instances::{errors::InstanceGatherError, get_free_instance_path, IResult, SimpleInstance}, SlWorld};
+
+use self::nbt::NbtTag;
+
+pub mod backups;
+pub mod nbt;
+
+
+#[derive(Debug, Clone)]
+pub struct WorldInfo {
+    pub folder_name: String,
+    pub path: PathBuf,
+    pub name: String,
+    pub game_mode: Option<i64>,
+    pub hardcore: bool,
+    pub last_played: Option<DateTime<Utc>>,
+    pub data_version: Option<i64>,
+    pub version_name: Option<String>,
+    pub seed: Option<i64>,
+    pub icon: Option<RgbaImage>
+}
+
+impl WorldInfo {
+    /// ### Reads all worlds in the `saves` folder of an instance
+    /// Folders without a readable `level.dat` are skipped. The most recently played worlds come first.
+    pub async fn get_all(minecraft_path: &Path) -> Vec<Self> {
+        let saves_path = minecraft_path.join("saves");
+        let mut entries = match fs::read_dir(&saves_path).await {
+            Ok(entries) => entries,
+            Err(err) => {
+                debug!("No saves folder at {saves_path:?}: {err}");
+                return Vec::new();
+            }
+        };
+
+        let mut worlds = Vec::new();
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            if !path.join("level.dat").is_file() {
+                continue;
+            }
+
+            match Self::get(&path).await {
+                Ok(world) => worlds.push(world),
+                Err(err) => warn!("Failed to read world at {path:?}: {err}")
+            }
+        }
+
+        worlds.sort_by_key(|world| std::cmp::Reverse(world.last_played));
+        worlds
+    }
+
+    pub async fn get(path: &Path) -> IResult<Self> {
+        let level_path = path.join("level.dat");
+        let read_path = level_path.clone();
+
+        let (_, root) = task::spawn_blocking(move || NbtTag::read_gzip(&read_path)).await.unwrap().map_err(
+            |err| InstanceGatherError::FileReadFailed(level_path.clone(), err)
+        )?;
+        let data = root.get("Data").ok_or_else(
+            || InstanceGatherError::InvalidWorld(path.to_path_buf(), "No Data tag in level.dat".to_string())
+        )?;
+
+        let folder_name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let icon_path = path.join("icon.png");
+
+        Ok(Self {
+            name: data.get("LevelName").and_then(NbtTag::as_str).map_or(folder_name.to_string(), str::to_string),
+            game_mode: data.get("GameType").and_then(NbtTag::as_i64),
+            hardcore: data.get("hardcore").and_then(NbtTag::as_i64).is_some_and(|hardcore| hardcore != 0),
+            last_played: data.get("LastPlayed").and_then(NbtTag::as_i64).and_then(DateTime::from_timestamp_millis),
+            data_version: data.get("DataVersion").and_then(NbtTag::as_i64),
+            version_name: data.get("Version").and_then(|ver| ver.get("Name")).and_then(NbtTag::as_str).map(str::to_string),
+            // The seed moved into "WorldGenSettings" in 1.16
+            seed: data.get("WorldGenSettings").and_then(|settings| settings.get("seed"))
+                .or(data.get("RandomSeed"))
+                .and_then(NbtTag::as_i64),
+            icon: if icon_path.is_file() {
+                SimpleInstance::load_image(icon_path.to_string_lossy().to_string()).await
+            } else { None },
+            folder_name,
+            path: path.to_path_buf()
+        })
+    }
+
+    /// ### Changes the name of the world shown in Minecraft
+    /// The folder is kept as is, just like renaming a world in game does.
+    pub async fn rename(&self, new_name: &str) -> IResult<()> {
+        if new_name.trim().is_empty() {
+            Err(InstanceGatherError::InvalidWorld(self.path.clone(), "The name can't be empty".to_string()))?;
+        }
+
+        info!("Renaming world {:?} to {new_name:?}", self.folder_name);
+        set_level_name(&self.path, new_name).await
+    }
+
+    pub async fn delete(&self) -> IResult<()> {
+        info!("Deleting world at {:?}", self.path);
+
+        fs::remove_dir_all(&self.path).await.map_err(
+            |err| InstanceGatherError::FileWriteFailed(self.path.clone(), err)
+        )
+    }
+
+    /// Copies the world into a new folder next to it and marks its name as a copy
+    pub async fn duplicate(&self) -> IResult<PathBuf> {
+        let target = get_free_instance_path(self.path.parent().unwrap_or(&self.path), &self.folder_name);
+        info!("Duplicating world {:?} to {target:?}", self.folder_name);
+
+        let (from, to) = (self.path.clone(), target.clone());
+        task::spawn_blocking(move || copy_dir_all(&from, &to)).await.unwrap().map_err(
+            |err| InstanceGatherError::FileWriteFailed(target.clone(), err)
+        )?;
+
+        // The lock of the original world is meaningless for the copy
+        fs::remove_file(target.join("session.lock")).await.ok();
+        set_level_name(&target, &format!("{} (Copy)", self.name)).await?;
+
+        Ok(target)
+    }
+
+    pub fn open_folder(&self) {
+        open::that_detached(&self.path).unwrap_or_else(
+            |err| error!("Failed to open world folder {:?}: {err}", self.path)
+        );
+    }
+
+    pub fn to_slint(&self) -> SlWorld {
+        SlWorld {
+            folder_name: self.folder_name.to_string().into(),
+            name: self.name.to_string().into(),
+            game_mode: if self.hardcore { "Hardcore".to_string() } else {
+                match self.game_mode {
+                    Some(0) => "Survival",
+                    Some(1) => "Creative",
+                    Some(2) => "Adventure",
+                    Some(3) => "Spectator",
+                    _ => "Unknown"
+                }.to_string()
+            }.into(),
+            last_played: SlintOption::from(self.last_played.map(|time| time.format("%d/%m/%Y, %H:%M").to_string())).into(),
+            version: self.version_name.clone().unwrap_or_default().into(),
+            data_version: self.data_version.map_or(String::new(), |ver| ver.to_string()).into(),
+            seed: self.seed.map_or(String::new(), |seed| seed.to_string()).into(),
+            icon: if let Some(icon) = &self.icon {
+                Image::from_rgba8(
+                SharedPixelBuffer::clone_from_slice(
+                        icon.as_raw(),
+                        icon.width(),
+                        icon.height()
+                    )
+                )
+            } else { Image::load_from_path(PathBuf::from("resources/default_instance.png").as_path()).unwrap_or_default() }
+        }
+    }
+}
+
+/// Sets "LevelName" in the `level.dat` of a world, keeping the previous file as `level.dat_old` like Minecraft does
+async fn set_level_name(world_path: &Path, name: &str) -> IResult<()> {
+    let level_path = world_path.join("level.dat");
+    let (path, name) = (level_path.clone(), name.to_string());
+
+    task::spawn_blocking(move || {
+        let (root_name, mut root) = NbtTag::read_gzip(&path)?;
+
+        match root.get_mut("Data") {
+            Some(NbtTag::Compound(data)) => { data.insert("LevelName".to_string(), NbtTag::String(name)); },
+            _ => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "No Data tag in level.dat"))
+        }
+
+        std::fs::copy(&path, path.with_file_name("level.dat_old"))?;
+        root.write_gzip(&root_name, &path)
+    }).await.unwrap().map_err(
+        |err| InstanceGatherError::FileWriteFailed(level_path, err)
+    )
+}
@@ -0,0 +1,332 @@
+use std::{collections::HashMap, fs::{self, File}, io::{self, BufReader, BufWriter, Read, Write}, path::Path};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+
+
+/// Deeper nesting than this is treated as a corrupt file
+const MAX_DEPTH: u32 = 512;
+
+/// A single tag of Minecraft's Named Binary Tag format
+#[derive(Debug, Clone, PartialEq)]
+pub enum NbtTag {
+    End,
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    List(u8, Vec<NbtTag>),
+    Compound(HashMap<String, NbtTag>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>)
+}
+
+
+impl NbtTag {
+    /// ### Reads a gzip compressed NBT file, like `level.dat`
+    /// Returns the name of the root tag and the root tag itself.
+    pub fn read_gzip(path: &Path) -> io::Result<(String, Self)> {
+        let mut reader = GzDecoder::new(BufReader::new(File::open(path)?));
+        Self::read_named(&mut reader)
+    }
+
+    /// ### Writes a gzip compressed NBT file with the given root tag
+    /// The file is written next to the target first and then moved over it, so a failed write never leaves a truncated file.
+    pub fn write_gzip(&self, name: &str, path: &Path) -> io::Result<()> {
+        let file_name = path.file_name().ok_or_else(|| invalid_data("NBT file path has no file name"))?;
+        let temp_path = path.with_file_name(format!("{}_new", file_name.to_string_lossy()));
+
+        let mut writer = GzEncoder::new(BufWriter::new(File::create(&temp_path)?), Compression::default());
+        writer.write_all(&[self.get_id()])?;
+        write_string(&mut writer, name)?;
+        self.write_payload(&mut writer)?;
+
+        writer.finish()?.into_inner().map_err(io::IntoInnerError::into_error)?.sync_all()?;
+        fs::rename(&temp_path, path)
+    }
+
+    pub fn read_named<R: Read>(reader: &mut R) -> io::Result<(String, Self)> {
+        let id = read_u8(reader)?;
+        if id == 0 {
+            return Ok((String::new(), NbtTag::End));
+        }
+
+        let name = read_string(reader)?;
+        Ok((name, Self::read_payload(reader, id, 0)?))
+    }
+
+    fn read_payload<R: Read>(reader: &mut R, id: u8, depth: u32) -> io::Result<Self> {
+        if depth > MAX_DEPTH {
+            return Err(invalid_data("NBT nesting is too deep"));
+        }
+
+        Ok(match id {
+            0 => NbtTag::End,
+            1 => NbtTag::Byte(read_u8(reader)? as i8),
+            2 => NbtTag::Short(i16::from_be_bytes(read_array(reader)?)),
+            3 => NbtTag::Int(i32::from_be_bytes(read_array(reader)?)),
+            4 => NbtTag::Long(i64::from_be_bytes(read_array(reader)?)),
+            5 => NbtTag::Float(f32::from_be_bytes(read_array(reader)?)),
+            6 => NbtTag::Double(f64::from_be_bytes(read_array(reader)?)),
+            7 => {
+                let len = read_len(reader)?;
+                NbtTag::ByteArray(read_vec(reader, len)?.into_iter().map(|byte| byte as i8).collect())
+            },
+            8 => NbtTag::String(read_string(reader)?),
+            9 => {
+                let item_id = read_u8(reader)?;
+                let len = read_len(reader)?;
+
+                // End tags take no bytes, so a long list of them could be "read" without the file backing it
+                if item_id == 0 && len > 0 {
+                    return Err(invalid_data(&format!("NBT list of {len} end tags")));
+                }
+
+                let mut items = Vec::new();
+                for _ in 0..len {
+                    items.push(Self::read_payload(reader, item_id, depth + 1)?);
+                }
+                NbtTag::List(item_id, items)
+            },
+            10 => {
+                let mut entries = HashMap::new();
+                loop {
+                    let entry_id = read_u8(reader)?;
+                    if entry_id == 0 {
+                        break;
+                    }
+
+                    let name = read_string(reader)?;
+                    entries.insert(name, Self::read_payload(reader, entry_id, depth + 1)?);
+                }
+                NbtTag::Compound(entries)
+            },
+            11 => {
+                let len = read_len(reader)?;
+                NbtTag::IntArray(read_vec(reader, len * 4)?.chunks_exact(4).map(|chunk| i32::from_be_bytes(chunk.try_into().unwrap())).collect())
+            },
+            12 => {
+                let len = read_len(reader)?;
+                NbtTag::LongArray(read_vec(reader, len * 8)?.chunks_exact(8).map(|chunk| i64::from_be_bytes(chunk.try_into().unwrap())).collect())
+            },
+            _ => return Err(invalid_data(&format!("Unknown NBT tag id {id}")))
+        })
+    }
+
+    fn write_payload<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        match self {
+            NbtTag::End => Ok(()),
+            NbtTag::Byte(val) => writer.write_all(&val.to_be_bytes()),
+            NbtTag::Short(val) => writer.write_all(&val.to_be_bytes()),
+            NbtTag::Int(val) => writer.write_all(&val.to_be_bytes()),
+            NbtTag::Long(val) => writer.write_all(&val.to_be_bytes()),
+            NbtTag::Float(val) => writer.write_all(&val.to_be_bytes()),
+            NbtTag::Double(val) => writer.write_all(&val.to_be_bytes()),
+            NbtTag::ByteArray(vals) => {
+                writer.write_all(&(vals.len() as i32).to_be_bytes())?;
+                writer.write_all(&vals.iter().map(|&val| val as u8).collect::<Vec<u8>>())
+            },
+            NbtTag::String(val) => write_string(writer, val),
+            NbtTag::List(item_id, items) => {
+                writer.write_all(&[*item_id])?;
+                writer.write_all(&(items.len() as i32).to_be_bytes())?;
+                items.iter().try_for_each(|item| item.write_payload(writer))
+            },
+            NbtTag::Compound(entries) => {
+                for (name, entry) in entries {
+                    writer.write_all(&[entry.get_id()])?;
+                    write_string(writer, name)?;
+                    entry.write_payload(writer)?;
+                }
+                writer.write_all(&[0])
+            },
+            NbtTag::IntArray(vals) => {
+                writer.write_all(&(vals.len() as i32).to_be_bytes())?;
+                vals.iter().try_for_each(|val| writer.write_all(&val.to_be_bytes()))
+            },
+            NbtTag::LongArray(vals) => {
+                writer.write_all(&(vals.len() as i32).to_be_bytes())?;
+                vals.iter().try_for_each(|val| writer.write_all(&val.to_be_bytes()))
+            }
+        }
+    }
+
+    pub fn get_id(&self) -> u8 {
+        match self {
+            NbtTag::End => 0,
+            NbtTag::Byte(_) => 1,
+            NbtTag::Short(_) => 2,
+            NbtTag::Int(_) => 3,
+            NbtTag::Long(_) => 4,
+            NbtTag::Float(_) => 5,
+            NbtTag::Double(_) => 6,
+            NbtTag::ByteArray(_) => 7,
+            NbtTag::String(_) => 8,
+            NbtTag::List(..) => 9,
+            NbtTag::Compound(_) => 10,
+            NbtTag::IntArray(_) => 11,
+            NbtTag::LongArray(_) => 12
+        }
+    }
+
+    /// Gets an entry of a compound tag
+    pub fn get(&self, key: &str) -> Option<&Self> {
+        match self {
+            NbtTag::Compound(entries) => entries.get(key),
+            _ => None
+        }
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Self> {
+        match self {
+            NbtTag::Compound(entries) => entries.get_mut(key),
+            _ => None
+        }
+    }
+
+    /// Gets any integer tag as an i64
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            NbtTag::Byte(val) => Some(*val as i64),
+            NbtTag::Short(val) => Some(*val as i64),
+            NbtTag::Int(val) => Some(*val as i64),
+            NbtTag::Long(val) => Some(*val),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            NbtTag::String(val) => Some(val),
+            _ => None
+        }
+    }
+}
+
+
+fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    Ok(read_array::<R, 1>(reader)?[0])
+}
+
+fn read_array<R: Read, const N: usize>(reader: &mut R) -> io::Result<[u8; N]> {
+    let mut buf = [0; N];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn read_vec<R: Read>(reader: &mut R, len: usize) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    reader.take(len as u64).read_to_end(&mut buf)?;
+
+    if buf.len() != len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+    }
+    Ok(buf)
+}
+
+fn read_len<R: Read>(reader: &mut R) -> io::Result<usize> {
+    let len = i32::from_be_bytes(read_array(reader)?);
+    usize::try_from(len).map_err(|_| invalid_data(&format!("Negative NBT length {len}")))
+}
+
+fn read_string<R: Read>(reader: &mut R) -> io::Result<String> {
+    let len = u16::from_be_bytes(read_array(reader)?);
+    decode_mutf8(&read_vec(reader, len as usize)?)
+}
+
+fn write_string<W: Write>(writer: &mut W, string: &str) -> io::Result<()> {
+    let bytes = encode_mutf8(string);
+    let len = u16::try_from(bytes.len()).map_err(|_| invalid_data("NBT string is too long"))?;
+    writer.write_all(&len.to_be_bytes())?;
+    writer.write_all(&bytes)
+}
+
+/// ### Decodes Java's modified UTF-8, which NBT strings use
+/// It encodes UTF-16 code units instead of characters, so characters outside the BMP (like emoji) are two 3 byte surrogates. NUL is written as two bytes.
+fn decode_mutf8(bytes: &[u8]) -> io::Result<String> {
+    fn next_continuation(iter: &mut impl Iterator<Item = u8>) -> io::Result<u16> {
+        match iter.next() {
+            Some(byte) if byte & 0xC0 == 0x80 => Ok((byte & 0x3F) as u16),
+            _ => Err(invalid_data("Invalid modified UTF-8 in NBT string"))
+        }
+    }
+
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut iter = bytes.iter().copied();
+
+    while let Some(byte) = iter.next() {
+        let unit = match byte {
+            0x01..=0x7F => byte as u16,
+            0xC0..=0xDF => ((byte & 0x1F) as u16) << 6 | next_continuation(&mut iter)?,
+            0xE0..=0xEF => ((byte & 0x0F) as u16) << 12 | next_continuation(&mut iter)? << 6 | next_continuation(&mut iter)?,
+            _ => return Err(invalid_data("Invalid modified UTF-8 in NBT string"))
+        };
+        units.push(unit);
+    }
+
+    // Java allows unpaired surrogates, which Rust strings can't hold
+    Ok(String::from_utf16_lossy(&units))
+}
+
+fn encode_mutf8(string: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(string.len());
+
+    for unit in string.encode_utf16() {
+        match unit {
+            0x0001..=0x007F => bytes.push(unit as u8),
+            0x0000 | 0x0080..=0x07FF => bytes.extend([0xC0 | (unit >> 6) as u8, 0x80 | (unit & 0x3F) as u8]),
+            _ => bytes.extend([0xE0 | (unit >> 12) as u8, 0x80 | ((unit >> 6) & 0x3F) as u8, 0x80 | (unit & 0x3F) as u8])
+        }
+    }
+
+    bytes
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mutf8_encodes_nul_and_supplementary_characters() {
+        assert_eq!(encode_mutf8("a\0b"), [b'a', 0xC0, 0x80, b'b']);
+        assert_eq!(encode_mutf8("\u{1F600}"), [0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80]);
+        assert_eq!(encode_mutf8("\u{e9}"), "\u{e9}".as_bytes());
+    }
+
+    #[test]
+    fn mutf8_round_trips() {
+        for string in ["World", "My \u{1F600} world", "nul\0inside", "\u{4e16}\u{754c}", ""] {
+            assert_eq!(decode_mutf8(&encode_mutf8(string)).unwrap(), string);
+        }
+        assert!(decode_mutf8(&[0xE0, 0x80]).is_err());
+    }
+
+    #[test]
+    fn lists_of_end_tags_are_rejected() {
+        let list = |len: i32| [&[9, 0, 0, 0][..], &len.to_be_bytes()].concat();
+
+        assert_eq!(NbtTag::read_named(&mut &list(0)[..]).unwrap(), (String::new(), NbtTag::List(0, Vec::new())));
+        assert_eq!(NbtTag::read_named(&mut &list(i32::MAX)[..]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn level_dat_is_rewritten_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("level.dat");
+        let root = NbtTag::Compound(HashMap::from([
+            ("LevelName".to_string(), NbtTag::String("Sign \u{1F600} text".to_string()))
+        ]));
+
+        root.write_gzip("", &path).unwrap();
+        assert_eq!(NbtTag::read_gzip(&path).unwrap(), (String::new(), root));
+        assert!(!dir.path().join("level.dat_new").exists());
+    }
+}
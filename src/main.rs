@@ -5,15 +5,15 @@
 )]
 #![allow(clippy::expect_fun_call)]
 
-use std::{sync::{Arc, RwLock}, time::Instant};
+use std::{collections::HashMap, sync::{Arc, RwLock}, time::{Duration, Instant}};
 
-use app::{settings::AppSettings, slint_utils::SlintOption, notifier::InternalNotifier};
-use launcher::{instances::SimpleInstance, authentication::{add_account, auth_structs}, instances, java::{get_java_version, JavaDetails}, launching::mc_structs::{MCSimpleVersion, MCVersionDetails, MCVersionList}};
+use app::{redaction::RedactingLogger, settings::AppSettings, slint_utils::SlintOption, notifier::{InternalNotifier, Notifier}};
+use launcher::{instances::{modrinth::{is_safe_relative_path, MRPackIndex}, multimc::MMCConfig, native::NativeConfig, SimpleInstance}, authentication::{add_account, add_account_device_code, auth_structs::{self, OfflineAccount}}, instances, java::{get_java_version, JavaDetails}, launching::{logs::{LogLevel, LogRecord, MAX_LOG_RECORDS, MAX_SHOWN_LOG_RECORDS}, processes::{focus_window, RunningGuard, RunningInstance}, mc_structs::{MCSimpleVersion, MCVersionDetails, MCVersionList}}, modloaders::ModLoaders, mods::{dependencies::DependencyReport, updates::{ModBackup, ModUpdate}, ModInfo}, packs::{PackInfo, PackType}, worlds::{backups::{BackupSettings, BackupTarget, WorldBackup}, WorldInfo}};
 use log::*;
 use reqwest::Client;
 use rfd::AsyncFileDialog;
 use simple_logger::SimpleLogger;
-use slint::{invoke_from_event_loop, spawn_local, Model, ModelRc, PlatformError, VecModel, Weak};
+use slint::{invoke_from_event_loop, spawn_local, Model, ModelRc, PlatformError, SharedString, VecModel, Weak};
 use clone_macro::clone;
 use tokio::{runtime::Runtime, sync::mpsc};
 use tokio_util::sync::CancellationToken;
@@ -28,10 +28,11 @@ pub mod launcher;
 
 fn main() {
     println!("Initializing YetaLauncher...");
-    SimpleLogger::new()
-    .with_level(log::LevelFilter::Debug)
-    .env()
-    .init()
+    RedactingLogger::init(
+        SimpleLogger::new()
+        .with_level(log::LevelFilter::Debug)
+        .env()
+    )
     .unwrap_or_else(|err| eprintln!("Failed to initialize logger: {err}"));
 
     YetaLauncher::start().expect("Failed to start YetaLauncher!");
@@ -43,7 +44,13 @@ fn main() {
 pub struct YetaLauncher {
     settings: RwLock<AppSettings>,
     accounts: RwLock<auth_structs::Accounts>,
-    instances: RwLock<Option<Vec<SimpleInstance>>>
+    instances: RwLock<Option<Vec<SimpleInstance>>>,
+    /// Mod updates found by the last update check, by instance id
+    mod_updates: RwLock<HashMap<u32, Vec<ModUpdate>>>,
+    /// Game output of the last launch, by instance id
+    game_logs: RwLock<HashMap<u32, Vec<LogRecord>>>,
+    /// Game processes that are currently running, by instance id
+    running: RwLock<HashMap<u32, RunningInstance>>
 }
 
 impl YetaLauncher {
@@ -101,6 +108,13 @@ impl YetaLauncher {
         }));
 
 
+        window.global::<App>().on_open_link(|link| {
+            open::that_detached(link.as_str()).unwrap_or_else(
+                |err| error!("Failed to open {link:?}: {err}")
+            );
+        });
+
+
         // Settings related callbacks
         settings.on_update_instance_path(clone!([{ window.as_weak() } as window, app, rt], move || {
             let _guard = rt.enter();
@@ -163,6 +177,7 @@ impl YetaLauncher {
             settings.java_settings = new_settings.java_settings.iter()
             .map(JavaDetails::from_slint)
             .collect();
+            settings.backup_settings = BackupSettings::from_slint(new_settings.backup_settings);
 
             settings.set();
         }));
@@ -291,25 +306,473 @@ impl YetaLauncher {
             })
         }));
 
-        instances.on_launch_instance(clone!([app, rt, notifier], move |instance_id| {
-            rt.spawn(clone!([app, notifier], async move {
+        instances.on_launch_instance(clone!([app, rt, notifier, { window.as_weak() } as window], move |instance_id, force| {
+            rt.spawn(clone!([app, notifier, window], async move {
                 let mut notifier = notifier.make_new();
-                SimpleInstance::launch(app, instance_id, &mut notifier).await.unwrap_or_else(|err| {
+
+                if let Some(instance) = app.get_instance(instance_id).filter(|_| !force) {
+                    let report = DependencyReport::check(&instance).await;
+
+                    if !report.is_ok() {
+                        notifier.send_error(&format!("Launch of '{}' blocked. {report}", instance.name));
+
+                        window.upgrade_in_event_loop(move |win| {
+                            win.global::<Instances>().set_launch_issues(SlLaunchIssues {
+                                instance_id,
+                                name: instance.name.into(),
+                                issues: ModelRc::new(VecModel::from(
+                                    report.issues.iter().map(|issue| issue.to_string().into()).collect::<Vec<SharedString>>()
+                                ))
+                            });
+                        }).unwrap();
+                        return;
+                    }
+                }
+
+                let account = app.accounts.read().unwrap().get_selected().map(|acc| acc.get_name().to_string());
+                {
+                    let mut running = app.running.write().unwrap();
+                    if running.contains_key(&(instance_id as u32)) {
+                        let name = app.get_instance(instance_id).map_or(instance_id.to_string(), |inst| inst.name);
+                        notifier.send_error(&format!("'{name}' is already running. Kill it first to launch it again."));
+                        return;
+                    }
+                    running.insert(instance_id as u32, RunningInstance::new(account));
+                }
+                app.sync_running(window.clone());
+                let running_guard = RunningGuard::new(clone!([app, window], move || {
+                    app.running.write().unwrap().remove(&(instance_id as u32));
+                    app.sync_running(window);
+                }));
+
+                app.game_logs.write().unwrap().insert(instance_id as u32, Vec::new());
+                let (log_sender, mut log_receiver) = mpsc::unbounded_channel();
+
+                tokio::spawn(clone!([app, window], async move {
+                    while let Some(record) = log_receiver.recv().await {
+                        // Records are collected for a bit, so the logs tab isn't rebuilt for every single line
+                        tokio::time::sleep(Duration::from_millis(250)).await;
+                        let mut records = vec![record];
+                        while let Ok(record) = log_receiver.try_recv() {
+                            records.push(record);
+                        }
+
+                        if let Some(logs) = app.game_logs.write().unwrap().get_mut(&(instance_id as u32)) {
+                            logs.append(&mut records);
+                            logs.drain(..logs.len().saturating_sub(MAX_LOG_RECORDS));
+                        }
+                        app.clone().sync_logs(instance_id, window.clone());
+                    }
+                }));
+
+                SimpleInstance::launch(app.clone(), instance_id, &mut notifier, log_sender).await.unwrap_or_else(|err| {
                     notifier.send_error(&format!("Failed to launch instance: {err}"));
                 });
+
+                drop(running_guard);
+
+                // Refreshing the account may have marked it as needing a new login
+                invoke_from_event_loop(move || {
+                    app.sync_accounts(window);
+                }).unwrap();
+            }));
+        }));
+
+        instances.on_kill_instance(clone!([app, notifier], move |instance_id| {
+            if let Some(running) = app.running.read().unwrap().get(&(instance_id as u32)) {
+                let name = app.get_instance(instance_id).map_or(instance_id.to_string(), |inst| inst.name);
+                notifier.make_new().send_msg(&format!("Stopping {name}..."));
+                running.kill();
+            }
+        }));
+
+        instances.on_focus_instance(clone!([app, rt, notifier], move |instance_id| {
+            let Some(pid) = app.running.read().unwrap().get(&(instance_id as u32)).and_then(|running| running.pid) else {
+                notifier.make_new().send_error("The game window isn't open yet");
+                return;
+            };
+
+            rt.spawn(clone!([notifier], async move {
+                if let Err(err) = focus_window(pid).await {
+                    warn!("Failed to focus game process {pid}: {err}");
+                    notifier.make_new().send_error(&format!("Failed to focus the game window: {err}"));
+                }
+            }));
+        }));
+
+        instances.on_is_running(clone!([], move |instance_id, running| {
+            running.iter().any(|id| id == instance_id)
+        }));
+
+        instances.on_get_logs(clone!([app, { window.as_weak() } as window], move |instance_id| {
+            app.clone().sync_logs(instance_id, window.clone());
+        }));
+
+        instances.on_create_instance(clone!([{ window.as_weak() } as window, app, rt, notifier], move |name, mc_version, loader, loader_version| {
+            rt.spawn(clone!([window, app, notifier], async move {
+                let notif = notifier.make_new();
+                let loader = ModLoaders::from_name(&loader).unwrap_or(ModLoaders::Vanilla);
+
+                notif.send_msg(&format!("Creating instance '{name}'..."));
+
+                match NativeConfig::new(&name, &mc_version, loader, &loader_version).create(app).await {
+                    Ok(instance) => {
+                        notif.send_success(&format!("Successfully created instance '{}'", instance.name));
+
+                        window.upgrade_in_event_loop(|win| {
+                            win.global::<Instances>().set_is_loading_instances(true);
+                            win.global::<Instances>().invoke_get_instances(true);
+                            win.global::<App>().set_page(Pages::Home);
+                        }).unwrap();
+                    },
+                    Err(err) => {
+                        notif.send_error(&format!("Failed to create instance: {err}"));
+                        error!("Failed to create instance: {err}");
+                    }
+                }
+            }));
+        }));
+
+        instances.on_import_instance(clone!([{ window.as_weak() } as window, app, rt, notifier], move || {
+            let _guard = rt.enter();
+            rt.spawn(clone!([window, app, notifier], async move {
+                debug!("Opening file picker...");
+                let picked = AsyncFileDialog::new()
+                .set_title("Select modpack to import")
+                .add_filter("Modpacks", &["mrpack", "zip"])
+                .pick_file()
+                .await;
+
+                if let Some(file) = picked {
+                    let notif = notifier.make_new();
+
+                    match instances::import_instance(file.path(), app, &notifier).await {
+                        Ok(instance) => {
+                            info!("Imported instance '{}'", instance.name);
+
+                            window.upgrade_in_event_loop(|win| {
+                                win.global::<Instances>().set_is_loading_instances(true);
+                                win.global::<Instances>().invoke_get_instances(true);
+                            }).unwrap();
+                        },
+                        Err(err) => {
+                            notif.send_error(&format!("Failed to import instance: {err}"));
+                            error!("Failed to import instance: {err}");
+                        }
+                    }
+                }
+            }));
+        }));
+
+        instances.on_export_mrpack(clone!([app, rt, notifier], move |instance_id| {
+            let _guard = rt.enter();
+            rt.spawn(clone!([app, notifier], async move {
+                if let Some(instance) = app.get_instance(instance_id) {
+                    debug!("Opening file picker...");
+                    let picked = AsyncFileDialog::new()
+                    .set_title("Export instance as .mrpack")
+                    .set_file_name(format!("{}.mrpack", instance.name))
+                    .add_filter("Modrinth modpack", &["mrpack"])
+                    .save_file()
+                    .await;
+
+                    if let Some(file) = picked {
+                        MRPackIndex::export(&instance, file.path(), app, &notifier).await.unwrap_or_else(|err| {
+                            notifier.make_new().send_error(&format!("Failed to export instance: {err}"));
+                            error!("Failed to export instance: {err}");
+                        });
+                    }
+                }
+            }));
+        }));
+
+        instances.on_export_mmc(clone!([app, rt, notifier], move |instance_id, saves, logs, screenshots| {
+            let _guard = rt.enter();
+            rt.spawn(clone!([app, notifier], async move {
+                if let Some(instance) = app.get_instance(instance_id) {
+                    let excludes: Vec<&str> = [("saves", saves), ("logs", logs), ("screenshots", screenshots)]
+                    .into_iter()
+                    .filter_map(|(folder, include)| if include { None } else { Some(folder) })
+                    .collect();
+
+                    debug!("Opening file picker...");
+                    let picked = AsyncFileDialog::new()
+                    .set_title("Export instance as MultiMC zip")
+                    .set_file_name(format!("{}.zip", instance.name))
+                    .add_filter("Zip archive", &["zip"])
+                    .save_file()
+                    .await;
+
+                    if let Some(file) = picked {
+                        MMCConfig::export(&instance, file.path(), &excludes, &notifier).await.unwrap_or_else(|err| {
+                            notifier.make_new().send_error(&format!("Failed to export instance: {err}"));
+                            error!("Failed to export instance: {err}");
+                        });
+                    }
+                }
             }));
         }));
 
         instances.on_get_mods(clone!([rt, app, { window.as_weak() } as window], move |instance_id| {
             rt.spawn(clone!([app, window], async move {
-                if let Some(instances) = app.instances.read().unwrap().as_ref() {
-                    if let Some(instance) = instances.iter().find(|inst| inst.id == instance_id as u32) {
-                        info!("getting {:?}", instance.name);
-                        
-                        window.upgrade_in_event_loop(clone!([{ instance.name } as name], |win| {
-                            win.global::<CurrentInstance>().invoke_set_name(name.into());
-                        })).unwrap();
+                if let Some(instance) = app.get_instance(instance_id) {
+                    app.sync_mods(instance, window).await;
+                }
+            }));
+        }));
+
+        instances.on_toggle_mods(clone!([rt, app, notifier, { window.as_weak() } as window], move |instance_id, file_names, enabled| {
+            let file_names: Vec<String> = file_names.iter().map(|name| name.to_string()).collect();
+
+            rt.spawn(clone!([app, notifier, window], async move {
+                if let Some(instance) = app.get_instance(instance_id) {
+                    ModInfo::set_enabled(&instance.minecraft_path, &file_names, enabled).await.unwrap_or_else(|err| {
+                        notifier.make_new().send_error(&format!("Failed to toggle mods: {err}"));
+                        error!("Failed to toggle mods: {err}");
+                        0
+                    });
+                    app.sync_mods(instance, window).await;
+                }
+            }));
+        }));
+
+        instances.on_toggle_selected_mods(clone!([rt, app, notifier, { window.as_weak() } as window], move |instance_id, enabled| {
+            let selected = YetaLauncher::get_selected_mods(&window.unwrap());
+
+            rt.spawn(clone!([app, notifier, window], async move {
+                if let Some(instance) = app.get_instance(instance_id) {
+                    match ModInfo::set_enabled(&instance.minecraft_path, &selected, enabled).await {
+                        Ok(count) => info!("{} {count} mod(s)", if enabled { "Enabled" } else { "Disabled" }),
+                        Err(err) => {
+                            notifier.make_new().send_error(&format!("Failed to toggle mods: {err}"));
+                            error!("Failed to toggle mods: {err}");
+                        }
+                    }
+                    app.sync_mods(instance, window).await;
+                }
+            }));
+        }));
+
+        instances.on_isolate_selected_mods(clone!([rt, app, notifier, { window.as_weak() } as window], move |instance_id| {
+            let selected = YetaLauncher::get_selected_mods(&window.unwrap());
+
+            rt.spawn(clone!([app, notifier, window], async move {
+                if let Some(instance) = app.get_instance(instance_id) {
+                    match ModInfo::isolate(&instance.minecraft_path, &selected).await {
+                        Ok(count) => info!("Renamed {count} mod(s)"),
+                        Err(err) => {
+                            notifier.make_new().send_error(&format!("Failed to isolate mods: {err}"));
+                            error!("Failed to isolate mods: {err}");
+                        }
+                    }
+                    app.sync_mods(instance, window).await;
+                }
+            }));
+        }));
+
+        instances.on_select_all_mods(clone!([{ window.as_weak() } as window], move |selected| {
+            let mods = window.unwrap().global::<CurrentInstance>().get_current_mods().1;
+
+            for i in 0..mods.row_count() {
+                if let Some(mut mod_info) = mods.row_data(i) {
+                    mod_info.selected = selected;
+                    mods.set_row_data(i, mod_info);
+                }
+            }
+        }));
+        instances.on_check_mod_updates(clone!([rt, app, notifier, { window.as_weak() } as window], move |instance_id| {
+            rt.spawn(clone!([app, notifier, window], async move {
+                if let Some(instance) = app.get_instance(instance_id) {
+                    let notifier = notifier.make_new();
+                    notifier.send_msg(&format!("Checking for mod updates of '{}'...", instance.name));
+
+                    let api_url = app.settings.read().unwrap().modrinth_api_url.to_string();
+                    match ModUpdate::check(&instance, &api_url).await {
+                        Ok(updates) => {
+                            notifier.send_success(&format!("Found {} mod update(s)", updates.len()));
+                            app.mod_updates.write().unwrap().insert(instance.id, updates);
+                        },
+                        Err(err) => {
+                            notifier.send_error(&format!("Failed to check for mod updates: {err}"));
+                            error!("Failed to check for mod updates: {err}");
+                        }
+                    }
+                    app.sync_mods(instance, window).await;
+                }
+            }));
+        }));
+
+        instances.on_update_mods(clone!([rt, app, notifier, { window.as_weak() } as window], move |instance_id, file_names| {
+            let file_names: Vec<String> = file_names.iter().map(|name| name.to_string()).collect();
+
+            rt.spawn(clone!([app, notifier, window], async move {
+                if let Some(instance) = app.get_instance(instance_id) {
+                    YetaLauncher::apply_mod_updates(app.clone(), &instance, Some(&file_names), &notifier).await;
+                    app.sync_mods(instance, window).await;
+                }
+            }));
+        }));
+
+        instances.on_update_all_mods(clone!([rt, app, notifier, { window.as_weak() } as window], move |instance_id| {
+            rt.spawn(clone!([app, notifier, window], async move {
+                if let Some(instance) = app.get_instance(instance_id) {
+                    YetaLauncher::apply_mod_updates(app.clone(), &instance, None, &notifier).await;
+                    app.sync_mods(instance, window).await;
+                }
+            }));
+        }));
+
+        instances.on_rollback_mod(clone!([rt, app, notifier, { window.as_weak() } as window], move |instance_id, file_name| {
+            rt.spawn(clone!([app, notifier, window], async move {
+                if let Some(instance) = app.get_instance(instance_id) {
+                    match ModBackup::rollback(&instance.minecraft_path, &file_name).await {
+                        Ok(()) => notifier.make_new().send_success(&format!("Rolled back {file_name}")),
+                        Err(err) => {
+                            notifier.make_new().send_error(&format!("Failed to roll back {file_name}: {err}"));
+                            error!("Failed to roll back {file_name}: {err}");
+                        }
+                    }
+                    app.sync_mods(instance, window).await;
+                }
+            }));
+        }));
+        instances.on_get_packs(clone!([rt, app, { window.as_weak() } as window], move |instance_id| {
+            rt.spawn(clone!([app, window], async move {
+                if let Some(instance) = app.get_instance(instance_id) {
+                    YetaLauncher::sync_packs(instance, window).await;
+                }
+            }));
+        }));
+
+        instances.on_toggle_resource_pack(clone!([rt, app, notifier, { window.as_weak() } as window], move |instance_id, file_name, enabled| {
+            rt.spawn(clone!([app, notifier, window], async move {
+                if let Some(instance) = app.get_instance(instance_id) {
+                    PackInfo::set_enabled(&instance, &file_name, enabled).await.unwrap_or_else(|err| {
+                        notifier.make_new().send_error(&format!("Failed to toggle resource pack: {err}"));
+                        error!("Failed to toggle resource pack: {err}");
+                    });
+                    YetaLauncher::sync_packs(instance, window).await;
+                }
+            }));
+        }));
+        instances.on_get_worlds(clone!([rt, app, { window.as_weak() } as window], move |instance_id| {
+            rt.spawn(clone!([app, window], async move {
+                if let Some(instance) = app.get_instance(instance_id) {
+                    YetaLauncher::sync_worlds(instance, window).await;
+                }
+            }));
+        }));
+
+        instances.on_open_world(clone!([rt, app], move |instance_id, folder_name| {
+            rt.spawn(clone!([app], async move {
+                if let Some(world) = YetaLauncher::get_world(&app, instance_id, &folder_name).await {
+                    world.open_folder();
+                }
+            }));
+        }));
+
+        instances.on_rename_world(clone!([rt, app, notifier, { window.as_weak() } as window], move |instance_id, folder_name, new_name| {
+            rt.spawn(clone!([app, notifier, window], async move {
+                if let (Some(instance), Some(world)) = (app.get_instance(instance_id), YetaLauncher::get_world(&app, instance_id, &folder_name).await) {
+                    if app.is_running(instance_id) {
+                        notifier.make_new().send_error(&format!("'{}' is running. Close the game before renaming a world.", instance.name));
+                        return;
+                    }
+
+                    world.rename(&new_name).await.unwrap_or_else(|err| {
+                        notifier.make_new().send_error(&format!("Failed to rename world: {err}"));
+                        error!("Failed to rename world: {err}");
+                    });
+                    YetaLauncher::sync_worlds(instance, window).await;
+                }
+            }));
+        }));
+
+        instances.on_duplicate_world(clone!([rt, app, notifier, { window.as_weak() } as window], move |instance_id, folder_name| {
+            rt.spawn(clone!([app, notifier, window], async move {
+                if let (Some(instance), Some(world)) = (app.get_instance(instance_id), YetaLauncher::get_world(&app, instance_id, &folder_name).await) {
+                    match world.duplicate().await {
+                        Ok(_) => notifier.make_new().send_success(&format!("Duplicated '{}'", world.name)),
+                        Err(err) => {
+                            notifier.make_new().send_error(&format!("Failed to duplicate world: {err}"));
+                            error!("Failed to duplicate world: {err}");
+                        }
+                    }
+                    YetaLauncher::sync_worlds(instance, window).await;
+                }
+            }));
+        }));
+
+        instances.on_delete_world(clone!([rt, app, notifier, { window.as_weak() } as window], move |instance_id, folder_name| {
+            rt.spawn(clone!([app, notifier, window], async move {
+                if let (Some(instance), Some(world)) = (app.get_instance(instance_id), YetaLauncher::get_world(&app, instance_id, &folder_name).await) {
+                    if app.is_running(instance_id) {
+                        notifier.make_new().send_error(&format!("'{}' is running. Close the game before deleting a world.", instance.name));
+                        return;
+                    }
+
+                    match world.delete().await {
+                        Ok(()) => notifier.make_new().send_success(&format!("Deleted '{}'", world.name)),
+                        Err(err) => {
+                            notifier.make_new().send_error(&format!("Failed to delete world: {err}"));
+                            error!("Failed to delete world: {err}");
+                        }
                     }
+                    YetaLauncher::sync_worlds(instance, window).await;
+                }
+            }));
+        }));
+
+        instances.on_backup_world(clone!([rt, app, notifier, { window.as_weak() } as window], move |instance_id, folder_name| {
+            rt.spawn(clone!([app, notifier, window], async move {
+                if let (Some(instance), Some(world)) = (app.get_instance(instance_id), YetaLauncher::get_world(&app, instance_id, &folder_name).await) {
+                    YetaLauncher::create_world_backup(&app, &instance, BackupTarget::World(world.folder_name), &notifier).await;
+                    YetaLauncher::sync_worlds(instance, window).await;
+                }
+            }));
+        }));
+
+        instances.on_backup_all_worlds(clone!([rt, app, notifier, { window.as_weak() } as window], move |instance_id| {
+            rt.spawn(clone!([app, notifier, window], async move {
+                if let Some(instance) = app.get_instance(instance_id) {
+                    YetaLauncher::create_world_backup(&app, &instance, BackupTarget::Saves, &notifier).await;
+                    YetaLauncher::sync_worlds(instance, window).await;
+                }
+            }));
+        }));
+
+        instances.on_restore_world_backup(clone!([rt, app, notifier, { window.as_weak() } as window], move |instance_id, backup_id| {
+            rt.spawn(clone!([app, notifier, window], async move {
+                if let (Some(instance), Some(backup)) = (app.get_instance(instance_id), YetaLauncher::get_world_backup(&app, instance_id, &backup_id).await) {
+                    let notifier = notifier.make_new();
+                    if app.is_running(instance_id) {
+                        notifier.send_error(&format!("'{}' is running. Close the game before restoring a backup.", instance.name));
+                        return;
+                    }
+
+                    let settings = app.settings.read().unwrap().backup_settings.clone();
+                    notifier.send_msg(&format!("Restoring '{}'...", backup.target.get_name()));
+
+                    match backup.restore(&instance.minecraft_path, &settings).await {
+                        Ok(()) => notifier.send_success(&format!("Restored '{}' from {}", backup.target.get_name(), backup.created.format("%d/%m/%Y, %H:%M"))),
+                        Err(err) => {
+                            notifier.send_error(&format!("Failed to restore world backup: {err}"));
+                            error!("Failed to restore world backup: {err}");
+                        }
+                    }
+                    YetaLauncher::sync_worlds(instance, window).await;
+                }
+            }));
+        }));
+
+        instances.on_delete_world_backup(clone!([rt, app, notifier, { window.as_weak() } as window], move |instance_id, backup_id| {
+            rt.spawn(clone!([app, notifier, window], async move {
+                if let (Some(instance), Some(backup)) = (app.get_instance(instance_id), YetaLauncher::get_world_backup(&app, instance_id, &backup_id).await) {
+                    backup.delete().await.unwrap_or_else(|err| {
+                        notifier.make_new().send_error(&format!("Failed to delete world backup: {err}"));
+                        error!("Failed to delete world backup: {err}");
+                    });
+                    YetaLauncher::sync_worlds(instance, window).await;
                 }
             }));
         }));
@@ -374,11 +837,10 @@ impl YetaLauncher {
         }));
 
         accounts.on_add_account(clone!([rt, app, { window.as_weak() } as window, notifier], move || {
-            let _guard = rt.enter();
-            rt.spawn(clone!([rt, app, window, notifier], async move {
+            rt.spawn(clone!([app, window, notifier], async move {
                 let (sender, mut receiver) = mpsc::unbounded_channel();
 
-                add_account(rt, app.clone(), notifier.make_new(), sender).await;
+                add_account(app.clone(), notifier.make_new(), sender).await;
 
                 if let Some(()) = receiver.recv().await {
                     invoke_from_event_loop(move || {
@@ -387,6 +849,32 @@ impl YetaLauncher {
                 }
             }));
         }));
+
+        accounts.on_add_account_device_code(clone!([rt, app, { window.as_weak() } as window, notifier], move || {
+            rt.spawn(clone!([app, window, notifier], async move {
+                let (sender, mut receiver) = mpsc::unbounded_channel();
+
+                add_account_device_code(app.clone(), notifier.make_new(), sender).await;
+
+                if let Some(()) = receiver.recv().await {
+                    invoke_from_event_loop(move || {
+                        app.sync_accounts(window);
+                    }).unwrap();
+                }
+            }));
+        }));
+
+        accounts.on_add_offline_account(clone!([app, { window.as_weak() } as window, notifier], move |name| {
+            match OfflineAccount::new(name.trim()) {
+                Ok(account) => {
+                    info!("Adding offline account {} ({})", account.name, account.id);
+                    notifier.make_new().send_success(&format!("Added offline account '{}'", account.name));
+                    app.accounts.write().unwrap().save_new_account(auth_structs::Account::Offline(account));
+                    app.sync_accounts(window.clone());
+                },
+                Err(err) => notifier.make_new().send_error(&err)
+            }
+        }));
         // End Account related callbacks
 
 
@@ -402,7 +890,10 @@ impl YetaLauncher {
         Self {
             settings: RwLock::new(AppSettings::get()),
             accounts: RwLock::new(auth_structs::Accounts::get()),
-            instances: RwLock::new(None)
+            instances: RwLock::new(None),
+            mod_updates: RwLock::new(HashMap::new()),
+            game_logs: RwLock::new(HashMap::new()),
+            running: RwLock::new(HashMap::new())
         }
     }
 
@@ -410,6 +901,185 @@ impl YetaLauncher {
         window.unwrap().global::<Settings>().set_settings(self.settings.read().unwrap().to_slint());
     }
 
+    /// ### Shows the game output of an instance in the logs tab, if that instance is shown
+    /// The level filter and search of the tab are applied.
+    fn sync_logs(self: Arc<Self>, instance_id: i32, window: Weak<MainWindow>) {
+        window.upgrade_in_event_loop(move |win| {
+            let cur_instance = win.global::<CurrentInstance>();
+            if cur_instance.get_logs_instance_id() != instance_id {
+                return;
+            }
+
+            let min_level = LogLevel::from_filter_index(cur_instance.get_log_level());
+            let search = cur_instance.get_log_search().to_lowercase();
+
+            let mut records: Vec<SlLogRecord> = self.game_logs.read().unwrap().get(&(instance_id as u32)).into_iter().flatten()
+            .rev()
+            .filter(|record| record.matches(min_level, &search))
+            .take(MAX_SHOWN_LOG_RECORDS)
+            .map(LogRecord::to_slint)
+            .collect();
+            records.reverse();
+
+            cur_instance.set_log_records(ModelRc::new(VecModel::from(records)));
+        }).unwrap();
+    }
+
+    /// Marks the instances with a running game process in the UI
+    fn sync_running(&self, window: Weak<MainWindow>) {
+        let running: Vec<i32> = self.running.read().unwrap().keys().map(|&id| id as i32).collect();
+
+        window.upgrade_in_event_loop(move |win| {
+            win.global::<Instances>().set_running_instances(ModelRc::new(VecModel::from(running)));
+        }).unwrap();
+    }
+
+    fn get_instance(&self, instance_id: i32) -> Option<SimpleInstance> {
+        self.instances.read().unwrap().as_ref().and_then(
+            |instances| instances.iter().find(|inst| inst.id == instance_id as u32).cloned()
+        )
+    }
+
+    /// Whether the game of an instance is running, so its files are in use
+    fn is_running(&self, instance_id: i32) -> bool {
+        self.running.read().unwrap().contains_key(&(instance_id as u32))
+    }
+
+    /// ### Rescans the mods of an instance and shows them in the mods tab
+    /// Selected mods stay selected if the same instance is shown already.
+    async fn sync_mods(&self, instance: SimpleInstance, window: Weak<MainWindow>) {
+        debug!("Reading mods of {:?}...", instance.name);
+        let mods = ModInfo::get_all(&instance.minecraft_path).await;
+        let backups = ModBackup::get_all(&instance.minecraft_path).await;
+        let updates = self.mod_updates.read().unwrap().get(&instance.id).cloned().unwrap_or_default();
+        info!("Found {} mod(s) in {:?}", mods.len(), instance.name);
+
+        window.upgrade_in_event_loop(move |win| {
+            let cur_instance = win.global::<CurrentInstance>();
+            let selected = if cur_instance.get_current_mods().0 == instance.name {
+                YetaLauncher::get_selected_mods(&win)
+            } else { Vec::new() };
+
+            cur_instance.invoke_set_name(instance.name.into());
+            cur_instance.invoke_set_mods(ModelRc::new(VecModel::from(
+                mods.iter().map(|mod_info| SlMod {
+                    selected: selected.contains(&mod_info.file_name),
+                    update: updates.iter().find(|update| update.file_name == mod_info.file_name)
+                        .map_or(String::new(), |update| update.new_version.to_string()).into(),
+                    can_rollback: backups.iter().any(|backup| backup.new_file == mod_info.file_name),
+                    ..mod_info.to_slint()
+                }).collect::<Vec<SlMod>>()
+            )));
+        }).unwrap();
+    }
+
+    async fn sync_packs(instance: SimpleInstance, window: Weak<MainWindow>) {
+        let resource_packs = PackInfo::get_all(&instance, PackType::ResourcePack).await;
+        let shader_packs = PackInfo::get_all(&instance, PackType::ShaderPack).await;
+        info!("Found {} resource pack(s) and {} shader pack(s) in {:?}", resource_packs.len(), shader_packs.len(), instance.name);
+
+        for pack in &resource_packs {
+            if let Some(warning) = pack.get_format_warning(&instance.mc_version) {
+                warn!("{:?}: {warning}", pack.file_name);
+            }
+        }
+
+        window.upgrade_in_event_loop(move |win| {
+            let cur_instance = win.global::<CurrentInstance>();
+            let to_model = |packs: &[PackInfo]| ModelRc::new(VecModel::from(
+                packs.iter().map(|pack| pack.to_slint(&instance.mc_version)).collect::<Vec<SlPack>>()
+            ));
+
+            cur_instance.set_resource_packs(to_model(&resource_packs));
+            cur_instance.set_shader_packs(to_model(&shader_packs));
+            cur_instance.set_packs_instance(instance.name.into());
+        }).unwrap();
+    }
+
+    async fn sync_worlds(instance: SimpleInstance, window: Weak<MainWindow>) {
+        let worlds = WorldInfo::get_all(&instance.minecraft_path).await;
+        let backups = WorldBackup::get_all(&instance.minecraft_path).await;
+        info!("Found {} world(s) and {} backup(s) in {:?}", worlds.len(), backups.len(), instance.name);
+
+        window.upgrade_in_event_loop(move |win| {
+            let cur_instance = win.global::<CurrentInstance>();
+            cur_instance.set_worlds(ModelRc::new(VecModel::from(
+                worlds.iter().map(WorldInfo::to_slint).collect::<Vec<SlWorld>>()
+            )));
+            cur_instance.set_world_backups(ModelRc::new(VecModel::from(
+                backups.iter().map(WorldBackup::to_slint).collect::<Vec<SlWorldBackup>>()
+            )));
+            cur_instance.set_worlds_instance(instance.name.into());
+        }).unwrap();
+    }
+
+    /// Reads a single world of an instance by its folder name, which must not lead outside the saves folder
+    async fn get_world(app: &YetaLauncher, instance_id: i32, folder_name: &str) -> Option<WorldInfo> {
+        let instance = app.get_instance(instance_id)?;
+        if !is_safe_relative_path(folder_name) {
+            warn!("Refusing to access world at {folder_name:?}");
+            return None;
+        }
+
+        WorldInfo::get(&instance.minecraft_path.join("saves").join(folder_name)).await.map_err(
+            |err| error!("Failed to read world {folder_name:?}: {err}")
+        ).ok()
+    }
+
+    async fn get_world_backup(app: &YetaLauncher, instance_id: i32, backup_id: &str) -> Option<WorldBackup> {
+        let instance = app.get_instance(instance_id)?;
+
+        WorldBackup::get_all(&instance.minecraft_path).await.into_iter()
+        .find(|backup| backup.get_id() == backup_id)
+        .or_else(|| {
+            warn!("No world backup {backup_id:?} in {:?}", instance.name);
+            None
+        })
+    }
+
+    async fn create_world_backup(app: &YetaLauncher, instance: &SimpleInstance, target: BackupTarget, notifier: &Notifier) {
+        let settings = app.settings.read().unwrap().backup_settings.clone();
+        let notifier = notifier.make_new();
+        notifier.send_msg(&format!("Backing up '{}'...", target.get_name()));
+
+        match WorldBackup::create(&instance.minecraft_path, target, &settings).await {
+            Ok(backup) => notifier.send_success(&format!("Backed up '{}'", backup.target.get_name())),
+            Err(err) => {
+                notifier.send_error(&format!("Failed to back up worlds: {err}"));
+                error!("Failed to back up worlds: {err}");
+            }
+        }
+    }
+
+    /// Applies the found updates for the given file names, or all of them
+    async fn apply_mod_updates(app: Arc<YetaLauncher>, instance: &SimpleInstance, file_names: Option<&[String]>, notifier: &Notifier) {
+        let updates: Vec<ModUpdate> = app.mod_updates.read().unwrap().get(&instance.id).into_iter().flatten()
+        .filter(|update| file_names.is_none_or(|names| names.contains(&update.file_name)))
+        .cloned()
+        .collect();
+
+        if updates.is_empty() {
+            return;
+        }
+
+        match ModUpdate::apply(instance, &updates, notifier).await {
+            Ok(_) => if let Some(remaining) = app.mod_updates.write().unwrap().get_mut(&instance.id) {
+                remaining.retain(|update| !updates.iter().any(|applied| applied.file_name == update.file_name));
+            },
+            Err(err) => {
+                notifier.make_new().send_error(&format!("Failed to update mods: {err}"));
+                error!("Failed to update mods: {err}");
+            }
+        }
+    }
+
+    fn get_selected_mods(window: &MainWindow) -> Vec<String> {
+        window.global::<CurrentInstance>().get_current_mods().1.iter()
+        .filter(|mod_info| mod_info.selected)
+        .map(|mod_info| mod_info.file_name.to_string())
+        .collect()
+    }
+
     fn sync_accounts(&self, window: Weak<MainWindow>) {
         window.unwrap().global::<Accounts>().set_accounts(self.accounts.read().unwrap().to_slint());
     }